@@ -8,7 +8,7 @@ fn main() {
     let lines = stdin.lock().lines();
     let input: Vec<i32> = lines.map(|x| x.unwrap().parse().unwrap()).collect();
     let sum: i32 = input.iter().sum();
-    println!("Sum: {}", input.iter().sum());
+    println!("Sum: {}", sum);
     let mut seen = HashSet::new();
     let mut sum = 0;
     for x in repeat(input.iter()).flatten() {
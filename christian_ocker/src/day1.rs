@@ -1,7 +1,6 @@
 use std::collections::HashSet;
 use std::iter;
 
-#[allow(dead_code)]
 pub fn solve_part1(input: &str) -> i32 {
     input
         .lines()
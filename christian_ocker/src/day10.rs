@@ -6,7 +6,8 @@ struct Star {
     vel_y: i32,
 }
 
-pub fn solve(input: &str) -> usize {
+// Returns the message and the second it appears at.
+pub fn align(input: &str) -> (String, usize) {
     let mut stars: Vec<Star> = input.lines().map(|line| {
         let mut star : Star = Default::default();
         scan!(line.bytes() => "position=<{},{}> velocity=<{},{}>",star.x, star.y, star.vel_x, star.vel_y);
@@ -45,15 +46,22 @@ pub fn solve(input: &str) -> usize {
         }
     }
 
+    let mut message = String::new();
     for y in 0..min_height {
         for x in 0..min_width {
             if min_m[x + y * min_width] {
-                print!("#");
+                message.push('#');
             } else {
-                print!(" ");
+                message.push(' ');
             }
         }
-        println!("");
+        message.push('\n');
     }
-    min_step
+    (message, min_step)
+}
+
+pub fn solve(input: &str) -> usize {
+    let (message, step) = align(input);
+    print!("{}", message);
+    step
 }
@@ -1,4 +1,3 @@
-#[allow(dead_code)]
 pub fn solve_checksum(input: &str) -> usize {
     let contains_n_let = |line: &str, n: usize| {
         char_iter::new('a', 'z')
@@ -14,7 +14,6 @@ fn parse_claim(input: &str) -> std::option::Option<(usize, usize, usize, usize,
     }
 }
 
-#[allow(dead_code)]
 pub fn solve_part1(input: &str) -> usize {
     let mut claim_map = vec![vec![0; 1000]; 1000];
 
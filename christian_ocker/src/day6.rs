@@ -14,8 +14,8 @@ fn manhattan_dist(ax: usize, ay: usize, bx: usize, by: usize) -> usize {
     ((ax as i32 - bx as i32).abs() + (ay as i32 - by as i32).abs()) as usize
 }
 
-#[allow(dead_code)]
-pub fn solve_part1(input: &str) -> usize {
+// Returns the map of the closest coordinates and the largest finite area.
+fn areas(input: &str) -> (Vec<Vec<Option<usize>>>, usize) {
     let coords = parse_coords(input);
 
     let width = *coords.iter().map(|(x, _y)| x).max().unwrap() + 1;
@@ -54,9 +54,25 @@ pub fn solve_part1(input: &str) -> usize {
         }
     }
 
-    for y in 0..height {
-        for x in 0..width {
-            if let Some(i) = map[y][x] {
+    let largest = (0..coords.len())
+        .filter(|i| finite[*i])
+        .map(|i| count[i])
+        .max()
+        .unwrap();
+    (map, largest)
+}
+
+pub fn largest_area(input: &str) -> usize {
+    areas(input).1
+}
+
+#[allow(dead_code)]
+pub fn solve_part1(input: &str) -> usize {
+    let (map, largest) = areas(input);
+
+    for row in map {
+        for closest in row {
+            if let Some(i) = closest {
                 print!("{}", i);
             } else {
                 print!(".");
@@ -65,11 +81,7 @@ pub fn solve_part1(input: &str) -> usize {
         println!("");
     }
 
-    (0..coords.len())
-        .filter(|i| finite[*i])
-        .map(|i| count[i])
-        .max()
-        .unwrap()
+    largest
 }
 
 pub fn solve(input: &str) -> usize {
@@ -14,14 +14,109 @@ extern crate scan_fmt;
 #[macro_use]
 extern crate text_io;
 
+#[macro_use]
+extern crate serde_json;
+
 use std::fs::File;
 use std::io::Read;
+use std::panic;
+use std::process;
+use std::time::Instant;
+
+type Part = fn(&str) -> Option<String>;
+
+fn parts(day: u8) -> Vec<(u8, Part)> {
+    let parts: Vec<(u8, Part)> = match day {
+        1 => vec![
+            (1, |input| Some(day1::solve_part1(input).to_string())),
+            (2, |input| Some(day1::solve(input).to_string())),
+        ],
+        2 => vec![
+            (1, |input| Some(day2::solve_checksum(input).to_string())),
+            (2, |input| Some(day2::solve(input))),
+        ],
+        3 => vec![
+            (1, |input| Some(day3::solve_part1(input).to_string())),
+            (2, |input| day3::solve(input).map(|id| id.to_string())),
+        ],
+        5 => vec![(2, |input| Some(day5::solve(input).to_string()))],
+        6 => vec![
+            (1, |input| Some(day6::largest_area(input).to_string())),
+            (2, |input| Some(day6::solve(input).to_string())),
+        ],
+        10 => vec![
+            (1, |input| Some(day10::align(input).0)),
+            (2, |input| Some(day10::align(input).1.to_string())),
+        ],
+        _ => vec![],
+    };
+    parts
+}
+
+// Prints one JSON object per part with the answer, the elapsed seconds and the error, if any.
+// Returns the number of parts which failed.
+fn print_json(day: u8, input_file: &str) -> usize {
+    let mut input = String::new();
+    let input = File::open(input_file)
+        .and_then(|mut f| f.read_to_string(&mut input))
+        .map(|_| input)
+        .map_err(|e| format!("{}: {}", input_file, e));
+
+    let mut failed = 0;
+    for (part, solve) in parts(day) {
+        let start = Instant::now();
+        let result = input.clone().and_then(|input| {
+            panic::catch_unwind(|| solve(&input)).map_err(|e| {
+                let message = e
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| e.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown cause".to_string());
+                format!("panicked: {}", message)
+            })
+        });
+        let elapsed = start.elapsed();
+        let (answer, error) = match result {
+            Ok(answer) => (answer, None),
+            Err(e) => {
+                failed += 1;
+                (None, Some(e))
+            }
+        };
+        println!(
+            "{}",
+            json!({
+                "day": day,
+                "part": part,
+                "answer": answer,
+                "elapsed": elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9,
+                "error": error,
+            })
+        );
+    }
+    failed
+}
 
 fn main() -> std::io::Result<()> {
-    let mut args = std::env::args().skip(1);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    let mut args = args.into_iter().filter(|arg| arg != "--json");
     let day: u8 = args.next().unwrap().parse().unwrap();
     let input_file = args.next().unwrap();
 
+    if json {
+        if parts(day).is_empty() {
+            eprintln!("invalid day: {}", day);
+            process::exit(1);
+        }
+        let failed = print_json(day, &input_file);
+        if failed > 0 {
+            eprintln!("{} parts failed", failed);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
     let mut input = String::new();
     File::open(input_file)?.read_to_string(&mut input)?;
 
@@ -32,7 +127,10 @@ fn main() -> std::io::Result<()> {
         5 => println!("{:?}", day5::solve(&input)),
         6 => println!("{:?}", day6::solve(&input)),
         10 => println!("{:?}", day10::solve(&input)),
-        _ => eprintln!("invalid day: {}", day),
+        _ => {
+            eprintln!("invalid day: {}", day);
+            process::exit(1);
+        }
     }
     Ok(())
 }
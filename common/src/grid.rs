@@ -0,0 +1,350 @@
+//! Rectangular 2D grid stored row by row.
+//!
+//! Positions are `(x, y)` with `x` the column and `y` the row, both starting at 0 in the top left
+//! corner. All iterators yield positions in reading order, i.e. row by row from top to bottom and
+//! left to right within a row, which is the order most puzzles break ties in.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::slice;
+use std::str::FromStr;
+
+/// Position `(x, y)` in a grid.
+pub type Pos = (usize, usize);
+
+/// Offsets of the 4-neighbourhood in reading order.
+const NEIGHBORS4: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// Offsets of the 8-neighbourhood in reading order.
+const NEIGHBORS8: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T: Clone> Grid<T> {
+    /// Grid of `width` x `height` copies of `cell`.
+    pub fn new(width: usize, height: usize, cell: T) -> Self {
+        Self {
+            cells: vec![cell; width * height],
+            width,
+            height,
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    /// Grid of `width` columns from cells given row by row.
+    ///
+    /// Panics if the cells don't fill the last row.
+    pub fn from_vec(width: usize, cells: Vec<T>) -> Self {
+        let height = cells.len().checked_div(width).unwrap_or(0);
+        assert_eq!(width * height, cells.len(), "incomplete last row");
+        Self {
+            cells,
+            width,
+            height,
+        }
+    }
+
+    /// Parses a map with one char per cell and one line per row.
+    ///
+    /// `cell` returns `None` for chars which are not a cell. All rows must have the same width.
+    pub fn parse_with<F>(input: &str, mut cell: F) -> Result<Self, ParseGridError>
+    where
+        F: FnMut(char) -> Option<T>,
+    {
+        let mut cells = Vec::new();
+        let mut width = None;
+        for (y, line) in input.lines().enumerate() {
+            let error = |col, kind| ParseGridError {
+                line: y + 1,
+                col,
+                kind,
+            };
+            let mut len = 0;
+            for (x, c) in line.chars().enumerate() {
+                cells.push(cell(c).ok_or_else(|| error(x + 1, ErrorKind::InvalidCell(c)))?);
+                len += 1;
+            }
+            match width {
+                None => width = Some(len),
+                Some(expected) if expected != len => {
+                    let kind = ErrorKind::Width {
+                        expected,
+                        found: len,
+                    };
+                    return Err(error(1, kind));
+                }
+                Some(_) => (),
+            }
+        }
+        Ok(Self::from_vec(width.unwrap_or(0), cells))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, (x, y): Pos) -> bool {
+        x < self.width && y < self.height
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<&T> {
+        if self.contains(pos) {
+            Some(&self.cells[pos.0 + self.width * pos.1])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, pos: Pos) -> Option<&mut T> {
+        if self.contains(pos) {
+            Some(&mut self.cells[pos.0 + self.width * pos.1])
+        } else {
+            None
+        }
+    }
+
+    /// Position at the signed offset `(dx, dy)` from `pos`, `None` if it is outside of the grid.
+    pub fn offset(&self, (x, y): Pos, (dx, dy): (isize, isize)) -> Option<Pos> {
+        let x = (x as isize).checked_add(dx)?;
+        let y = (y as isize).checked_add(dy)?;
+        if x < 0 || y < 0 {
+            return None;
+        }
+        let pos = (x as usize, y as usize);
+        if self.contains(pos) {
+            Some(pos)
+        } else {
+            None
+        }
+    }
+
+    /// Cell at the signed offset `(dx, dy)` from `pos`.
+    pub fn get_offset(&self, pos: Pos, offset: (isize, isize)) -> Option<&T> {
+        self.offset(pos, offset).map(|pos| &self[pos])
+    }
+
+    /// Horizontally and vertically adjacent positions in reading order.
+    pub fn neighbors4<'a>(&'a self, pos: Pos) -> impl Iterator<Item = Pos> + 'a {
+        NEIGHBORS4.iter().filter_map(move |&d| self.offset(pos, d))
+    }
+
+    /// Horizontally, vertically and diagonally adjacent positions in reading order.
+    pub fn neighbors8<'a>(&'a self, pos: Pos) -> impl Iterator<Item = Pos> + 'a {
+        NEIGHBORS8.iter().filter_map(move |&d| self.offset(pos, d))
+    }
+
+    /// All positions in reading order.
+    pub fn positions(&self) -> impl Iterator<Item = Pos> {
+        let width = self.width;
+        (0..self.cells.len()).map(move |i| (i % width, i / width))
+    }
+
+    /// All cells in reading order.
+    pub fn cells(&self) -> slice::Iter<'_, T> {
+        self.cells.iter()
+    }
+
+    pub fn cells_mut(&mut self) -> slice::IterMut<'_, T> {
+        self.cells.iter_mut()
+    }
+
+    /// All cells with their positions in reading order.
+    pub fn iter(&self) -> impl Iterator<Item = (Pos, &T)> {
+        self.positions().zip(self.cells.iter())
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    /// Rows from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // `chunks` panics on 0
+        self.cells.chunks(self.width.max(1))
+    }
+
+    /// Cells of the column `x` from top to bottom.
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        assert!(x < self.width, "column {} out of bounds", x);
+        self.cells.iter().skip(x).step_by(self.width)
+    }
+
+    /// Columns from left to right.
+    pub fn columns<'a>(&'a self) -> impl Iterator<Item = impl Iterator<Item = &'a T>> + 'a {
+        (0..self.width).map(move |x| self.column(x))
+    }
+
+    /// Grid of the same size with `f` applied to each cell.
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            cells: self.cells.iter().map(f).collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+impl<T> Index<Pos> for Grid<T> {
+    type Output = T;
+    fn index(&self, pos: Pos) -> &Self::Output {
+        match self.get(pos) {
+            Some(cell) => cell,
+            None => panic!(
+                "{:?} out of bounds of a {}x{} grid",
+                pos, self.width, self.height
+            ),
+        }
+    }
+}
+
+impl<T> IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, pos: Pos) -> &mut Self::Output {
+        let (width, height) = (self.width, self.height);
+        match self.get_mut(pos) {
+            Some(cell) => cell,
+            None => panic!("{:?} out of bounds of a {}x{} grid", pos, width, height),
+        }
+    }
+}
+
+/// Parses a map whose cells are made from chars; see `Grid::parse_with`.
+impl<T: From<char>> FromStr for Grid<T> {
+    type Err = ParseGridError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(input, |c| Some(T::from(c)))
+    }
+}
+
+/// Renders one line per row, each terminated by a newline.
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The char is not a cell.
+    InvalidCell(char),
+    /// A row has a different width than the first one.
+    Width { expected: usize, found: usize },
+}
+
+/// Error of `Grid::parse_with`, located by line and column starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseGridError {
+    pub line: usize,
+    pub col: usize,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for ParseGridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} col {}: ", self.line, self.col)?;
+        match self.kind {
+            ErrorKind::InvalidCell(c) => write!(f, "invalid cell {:?}", c),
+            ErrorKind::Width { expected, found } => {
+                write!(f, "expected {} cells, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl Error for ParseGridError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "#.#\n.#.\n##.\n.#.\n";
+
+    fn parse(input: &str) -> Result<Grid<bool>, ParseGridError> {
+        Grid::parse_with(input, |c| match c {
+            '#' => Some(true),
+            '.' => Some(false),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_parse() {
+        let grid = parse(MAP).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 4));
+        assert!(grid[(0, 0)]);
+        assert!(!grid[(2, 2)]);
+        assert_eq!(grid.get((3, 0)), None);
+
+        let e = parse("#.#\n.#\n").unwrap_err();
+        assert_eq!(e.to_string(), "line 2 col 1: expected 3 cells, found 2");
+        let e = parse("#.#\n.x.\n").unwrap_err();
+        assert_eq!(e.to_string(), "line 2 col 2: invalid cell 'x'");
+
+        let grid: Grid<char> = MAP.parse().unwrap();
+        assert_eq!(grid.to_string(), MAP);
+        assert_eq!(parse("").unwrap().rows().count(), 0);
+    }
+
+    #[test]
+    fn test_offsets() {
+        let grid = Grid::new(3, 2, 0);
+        assert_eq!(grid.offset((0, 0), (-1, 0)), None);
+        assert_eq!(grid.offset((0, 0), (2, 1)), Some((2, 1)));
+        assert_eq!(grid.offset((2, 1), (0, 1)), None);
+        assert_eq!(grid.get_offset((1, 1), (-1, -1)), Some(&0));
+        assert_eq!(
+            grid.neighbors4((1, 0)).collect::<Vec<_>>(),
+            vec![(0, 0), (2, 0), (1, 1)]
+        );
+        assert_eq!(
+            grid.neighbors8((0, 1)).collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (1, 1)]
+        );
+        assert_eq!(grid.neighbors8((1, 0)).count(), 5);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut grid = Grid::from_vec(3, (0..6).collect());
+        assert_eq!(grid.row(1), &[3, 4, 5]);
+        assert_eq!(grid.column(1).cloned().collect::<Vec<_>>(), vec![1, 4]);
+        let columns: Vec<Vec<_>> = grid.columns().map(|c| c.cloned().collect()).collect();
+        assert_eq!(columns, vec![vec![0, 3], vec![1, 4], vec![2, 5]]);
+        assert_eq!(grid.positions().nth(4), Some((1, 1)));
+        assert_eq!(grid.iter().find(|&(_, &c)| c == 5), Some(((2, 1), &5)));
+
+        grid[(2, 0)] = 7;
+        grid.row_mut(1)[0] = 8;
+        assert_eq!(grid.map(|c| c % 2).to_string(), "011\n001\n");
+    }
+}
@@ -0,0 +1,9 @@
+//! Building blocks shared by the solutions of all authors.
+//!
+//! Depends on nothing but `std`, so that every solution crate can pull it in with
+//! `common = { path = "../common" }`.
+
+pub mod grid;
+pub mod search;
+
+pub use crate::grid::{Grid, Pos};
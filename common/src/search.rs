@@ -0,0 +1,317 @@
+//! Shortest path searches over graphs given by a neighbour or edge function.
+//!
+//! Nodes are any hashable values, e.g. grid positions or `(tool, position)` pairs, and the graph
+//! is explored lazily from the start node, so it doesn't have to be finite.
+//!
+//! Ties between equally short paths are broken by the order in which the neighbour function yields
+//! the nodes: `bfs` finds the lexicographically smallest of all shortest paths. With the neighbours
+//! of `Grid` this is reading order, i.e. the first step of the path is the first one in reading
+//! order which lies on any shortest path.
+
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::ops::Add;
+
+/// Costs of and shortest paths to all nodes reached by a search.
+#[derive(Debug, Clone)]
+pub struct Search<N: Eq + Hash, C> {
+    start: N,
+    /// Cost of the node and its predecessor on the shortest path.
+    nodes: HashMap<N, (C, Option<N>)>,
+}
+
+impl<N: Eq + Hash + Clone, C: Copy> Search<N, C> {
+    fn new(start: N, zero: C) -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(start.clone(), (zero, None));
+        Self { start, nodes }
+    }
+
+    pub fn start(&self) -> &N {
+        &self.start
+    }
+
+    /// Cost of the shortest path to `node`, `None` if it was not reached.
+    pub fn cost(&self, node: &N) -> Option<C> {
+        self.nodes.get(node).map(|&(cost, _)| cost)
+    }
+
+    pub fn contains(&self, node: &N) -> bool {
+        self.nodes.contains_key(node)
+    }
+
+    /// All reached nodes with their costs, in no particular order.
+    pub fn costs(&self) -> impl Iterator<Item = (&N, C)> {
+        self.nodes.iter().map(|(node, &(cost, _))| (node, cost))
+    }
+
+    pub fn into_costs(self) -> HashMap<N, C> {
+        self.nodes
+            .into_iter()
+            .map(|(node, (cost, _))| (node, cost))
+            .collect()
+    }
+
+    /// Shortest path from the start to `node`, both included.
+    pub fn path(&self, node: &N) -> Option<Vec<N>> {
+        let mut path = vec![node.clone()];
+        let mut pred = &self.nodes.get(node)?.1;
+        while let Some(node) = pred {
+            path.push(node.clone());
+            pred = &self.nodes[node].1;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Node of `targets` with the cheapest path; ties are broken by the order of `targets`.
+    pub fn nearest<I>(&self, targets: I) -> Option<(N, C)>
+    where
+        I: IntoIterator<Item = N>,
+        C: Ord,
+    {
+        let mut nearest: Option<(N, C)> = None;
+        for node in targets {
+            match (self.cost(&node), &nearest) {
+                (Some(cost), Some((_, min))) if cost < *min => nearest = Some((node, cost)),
+                (Some(cost), None) => nearest = Some((node, cost)),
+                _ => (),
+            }
+        }
+        nearest
+    }
+}
+
+/// Breadth first search from `start` in a graph with unit edge costs.
+pub fn bfs<N, I, F>(start: N, mut neighbors: F) -> Search<N, usize>
+where
+    N: Eq + Hash + Clone,
+    I: IntoIterator<Item = N>,
+    F: FnMut(&N) -> I,
+{
+    let mut search = Search::new(start.clone(), 0);
+    let mut queue = VecDeque::new();
+    queue.push_back((start, 0));
+    while let Some((node, cost)) = queue.pop_front() {
+        for next in neighbors(&node) {
+            if let Entry::Vacant(e) = search.nodes.entry(next.clone()) {
+                e.insert((cost + 1, Some(node.clone())));
+                queue.push_back((next, cost + 1));
+            }
+        }
+    }
+    search
+}
+
+/// Dijkstra's search from `start`; `edges` yields the neighbours of a node with the edge costs.
+///
+/// Explores the whole reachable graph, use `shortest_path` if only one goal is needed.
+pub fn dijkstra<N, C, I, F>(start: N, edges: F) -> Search<N, C>
+where
+    N: Eq + Hash + Clone,
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (N, C)>,
+    F: FnMut(&N) -> I,
+{
+    run(start, edges, |_| C::default(), |_| false).0
+}
+
+/// Cheapest path from `start` to the first node satisfying `is_goal`, with its cost.
+pub fn shortest_path<N, C, I, F, G>(start: N, edges: F, is_goal: G) -> Option<(Vec<N>, C)>
+where
+    N: Eq + Hash + Clone,
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (N, C)>,
+    F: FnMut(&N) -> I,
+    G: FnMut(&N) -> bool,
+{
+    astar(start, edges, |_| C::default(), is_goal)
+}
+
+/// A* search for the cheapest path from `start` to the first node satisfying `is_goal`.
+///
+/// `heuristic` must never overestimate the cost to the nearest goal, otherwise the found path
+/// might not be the cheapest one.
+pub fn astar<N, C, I, F, H, G>(start: N, edges: F, heuristic: H, is_goal: G) -> Option<(Vec<N>, C)>
+where
+    N: Eq + Hash + Clone,
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (N, C)>,
+    F: FnMut(&N) -> I,
+    H: FnMut(&N) -> C,
+    G: FnMut(&N) -> bool,
+{
+    let (search, goal) = run(start, edges, heuristic, is_goal);
+    let goal = goal?;
+    Some((search.path(&goal)?, search.cost(&goal)?))
+}
+
+/// Node in the open set, ordered by its priority only.
+struct Open<N, C> {
+    priority: C,
+    cost: C,
+    node: N,
+}
+
+impl<N, C: Ord> PartialEq for Open<N, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<N, C: Ord> Eq for Open<N, C> {}
+
+impl<N, C: Ord> PartialOrd for Open<N, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N, C: Ord> Ord for Open<N, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` is a max-heap
+        other.priority.cmp(&self.priority)
+    }
+}
+
+/// A* until a goal is closed; Dijkstra if the heuristic is 0.
+fn run<N, C, I, F, H, G>(
+    start: N,
+    mut edges: F,
+    mut heuristic: H,
+    mut is_goal: G,
+) -> (Search<N, C>, Option<N>)
+where
+    N: Eq + Hash + Clone,
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (N, C)>,
+    F: FnMut(&N) -> I,
+    H: FnMut(&N) -> C,
+    G: FnMut(&N) -> bool,
+{
+    let zero = C::default();
+    let mut search = Search::new(start.clone(), zero);
+    let mut closed = HashSet::new();
+    let mut open = BinaryHeap::new();
+    open.push(Open {
+        priority: heuristic(&start),
+        cost: zero,
+        node: start,
+    });
+
+    while let Some(Open { cost, node, .. }) = open.pop() {
+        if !closed.insert(node.clone()) {
+            continue; // already reached with a lower cost
+        }
+        if is_goal(&node) {
+            return (search, Some(node));
+        }
+        for (next, edge_cost) in edges(&node) {
+            let next_cost = cost + edge_cost;
+            let is_shorter = match search.nodes.get(&next) {
+                Some(&(known_cost, _)) => next_cost < known_cost,
+                None => true,
+            };
+            if is_shorter {
+                search
+                    .nodes
+                    .insert(next.clone(), (next_cost, Some(node.clone())));
+                open.push(Open {
+                    priority: next_cost + heuristic(&next),
+                    cost: next_cost,
+                    node: next,
+                });
+            }
+        }
+    }
+    (search, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    const MAZE: &str = "\
+#######
+#.....#
+#.#.#.#
+#.....#
+#######";
+
+    fn maze() -> Grid<bool> {
+        Grid::parse_with(MAZE, |c| Some(c == '.')).unwrap()
+    }
+
+    #[test]
+    fn test_bfs() {
+        let maze = maze();
+        let search = bfs((1, 1), |&pos| {
+            let maze = &maze;
+            maze.neighbors4(pos).filter(move |&pos| maze[pos])
+        });
+        assert_eq!(search.cost(&(5, 3)), Some(6));
+        assert_eq!(search.cost(&(0, 0)), None);
+        assert_eq!(search.costs().count(), 13);
+        // reading order prefers going right before going down
+        assert_eq!(
+            search.path(&(3, 3)),
+            Some(vec![(1, 1), (2, 1), (3, 1), (3, 2), (3, 3)])
+        );
+        assert_eq!(search.path(&(1, 1)), Some(vec![(1, 1)]));
+        assert_eq!(
+            search.nearest(vec![(5, 3), (1, 3), (5, 1)]),
+            Some(((1, 3), 2))
+        );
+    }
+
+    fn edges(&n: &u32) -> Vec<(u32, u32)> {
+        // 0 -> 1 is expensive, the detour over 2 is cheap
+        match n {
+            0 => vec![(1, 10), (2, 1)],
+            1 => vec![(3, 1)],
+            2 => vec![(1, 2), (3, 7)],
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        let search = dijkstra(0, edges);
+        assert_eq!(search.cost(&1), Some(3));
+        assert_eq!(search.cost(&3), Some(4));
+        assert_eq!(search.path(&3), Some(vec![0, 2, 1, 3]));
+        assert_eq!(search.into_costs().len(), 4);
+
+        assert_eq!(
+            shortest_path(0, edges, |&n| n == 3),
+            Some((vec![0, 2, 1, 3], 4))
+        );
+        assert_eq!(shortest_path(1, edges, |&n| n == 0), None);
+    }
+
+    #[test]
+    fn test_astar() {
+        let maze = maze();
+        let goal = (5, 3);
+        let distance = |&(x, y): &(usize, usize)| {
+            ((x as isize - goal.0 as isize).abs() + (y as isize - goal.1 as isize).abs()) as usize
+        };
+        let (path, cost) = astar(
+            (1, 1),
+            |&pos| {
+                maze.neighbors4(pos)
+                    .filter(|&pos| maze[pos])
+                    .map(|pos| (pos, 1))
+                    .collect::<Vec<_>>()
+            },
+            distance,
+            |&pos| pos == goal,
+        )
+        .unwrap();
+        assert_eq!(cost, 6);
+        assert_eq!(path.len(), 7);
+    }
+}
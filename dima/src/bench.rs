@@ -0,0 +1,247 @@
+//! Timing of the parsing and both parts of each day.
+//!
+//! Every step is run repeatedly on the same input and summarized by its minimum, median and
+//! maximum time. The medians can be saved as a baseline, which later runs are compared to.
+
+use crate::solution::{Day, Part};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Baseline of this machine; not checked in since timings of different machines don't compare.
+pub const BASELINE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/bench-baseline.toml");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Step {
+    Parse,
+    Part1,
+    Part2,
+}
+
+impl Step {
+    pub const ALL: [Step; 3] = [Step::Parse, Step::Part1, Step::Part2];
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Parse => write!(f, "parse"),
+            Step::Part1 => write!(f, "part 1"),
+            Step::Part2 => write!(f, "part 2"),
+        }
+    }
+}
+
+/// Minimum, median and maximum of repeated runs of a step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub runs: usize,
+    pub min: Duration,
+    pub median: Duration,
+    pub max: Duration,
+}
+
+impl Stats {
+    fn new(mut times: Vec<Duration>) -> Self {
+        times.sort();
+        Stats {
+            runs: times.len(),
+            min: times[0],
+            median: times[times.len() / 2],
+            max: times[times.len() - 1],
+        }
+    }
+}
+
+/// Limits of the repetitions of a step: it runs `runs` times, but stops early once it took
+/// `time_limit` in total. Every step runs at least once.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub runs: usize,
+    pub time_limit: Duration,
+}
+
+fn repeat<F: FnMut()>(limits: Limits, mut f: F) -> Stats {
+    let mut times = Vec::new();
+    let mut total = Duration::default();
+    while times.is_empty() || (times.len() < limits.runs && total < limits.time_limit) {
+        let start = Instant::now();
+        f();
+        let time = start.elapsed();
+        total += time;
+        times.push(time);
+    }
+    Stats::new(times)
+}
+
+/// Times the steps of `day` on `input`.
+pub fn bench(day: &Day, input: &str, limits: Limits) -> Result<Vec<(Step, Stats)>, Box<dyn Error>> {
+    let parsed = day.parse(input)?;
+    let mut stats = vec![(
+        Step::Parse,
+        repeat(limits, || {
+            let _ = day.parse(input);
+        }),
+    )];
+    for (step, part) in Step::ALL[1..].iter().zip(&Part::ALL) {
+        stats.push((
+            *step,
+            repeat(limits, || {
+                let _ = day.part(*part, &parsed);
+            }),
+        ));
+    }
+    Ok(stats)
+}
+
+/// Median times of earlier runs in nanoseconds, by day and step.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    #[serde(rename = "timing", default)]
+    timings: Vec<Timing>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Timing {
+    day: u8,
+    step: Step,
+    median: u64,
+}
+
+impl Baseline {
+    /// Loads the baseline at `path`; empty if there is none yet.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Baseline::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let content = toml::to_string(self)?;
+        fs::write(path, content).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn get(&self, day: u8, step: Step) -> Option<Duration> {
+        self.timings
+            .iter()
+            .find(|t| t.day == day && t.step == step)
+            .map(|t| Duration::from_nanos(t.median))
+    }
+
+    /// Replaces the timing of `step` of `day`, keeping the ones of all other steps.
+    pub fn set(&mut self, day: u8, step: Step, median: Duration) {
+        let mut timings: BTreeMap<(u8, Step), u64> = self
+            .timings
+            .drain(..)
+            .map(|t| ((t.day, t.step), t.median))
+            .collect();
+        timings.insert((day, step), median.as_nanos() as u64);
+        self.timings = timings
+            .into_iter()
+            .map(|((day, step), median)| Timing { day, step, median })
+            .collect();
+    }
+}
+
+/// Duration with 3 significant digits in the most fitting unit.
+pub fn format_duration(d: Duration) -> String {
+    let nanos = d.as_nanos() as f64;
+    let (value, unit) = if nanos < 1e3 {
+        (nanos, "ns")
+    } else if nanos < 1e6 {
+        (nanos / 1e3, "µs")
+    } else if nanos < 1e9 {
+        (nanos / 1e6, "ms")
+    } else {
+        (nanos / 1e9, "s")
+    };
+    let precision = if value < 10.0 {
+        2
+    } else if value < 100.0 {
+        1
+    } else {
+        0
+    };
+    format!("{:.*}{}", precision, value, unit)
+}
+
+/// Change of a median relative to the baseline, e.g. `2.00x faster`.
+pub fn format_change(baseline: Duration, median: Duration) -> String {
+    let (baseline, median) = (baseline.as_nanos() as f64, median.as_nanos() as f64);
+    if median <= baseline {
+        format!("{:.2}x faster", baseline / median.max(1.0))
+    } else {
+        format!("{:.2}x slower", median / baseline.max(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let ms = Duration::from_millis;
+        let stats = Stats::new(vec![ms(5), ms(1), ms(3)]);
+        assert_eq!(
+            stats,
+            Stats {
+                runs: 3,
+                min: ms(1),
+                median: ms(3),
+                max: ms(5)
+            }
+        );
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut calls = 0;
+        let limits = Limits {
+            runs: 5,
+            time_limit: Duration::from_secs(60),
+        };
+        assert_eq!(repeat(limits, || calls += 1).runs, 5);
+        assert_eq!(calls, 5);
+
+        let limits = Limits {
+            runs: 5,
+            time_limit: Duration::default(),
+        };
+        assert_eq!(repeat(limits, || ()).runs, 1);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_duration(Duration::from_nanos(512)), "512ns");
+        assert_eq!(format_duration(Duration::from_micros(1500)), "1.50ms");
+        assert_eq!(format_duration(Duration::from_millis(12345)), "12.3s");
+        let ms = Duration::from_millis;
+        assert_eq!(format_change(ms(10), ms(5)), "2.00x faster");
+        assert_eq!(format_change(ms(10), ms(15)), "1.50x slower");
+    }
+
+    #[test]
+    fn test_baseline() -> Result<(), Box<dyn Error>> {
+        let mut baseline = Baseline::default();
+        baseline.set(9, Step::Part2, Duration::from_millis(300));
+        baseline.set(1, Step::Parse, Duration::from_micros(20));
+        baseline.set(9, Step::Part2, Duration::from_millis(200));
+        let baseline: Baseline = toml::from_str(&toml::to_string(&baseline)?)?;
+        assert_eq!(baseline.timings.len(), 2);
+        assert_eq!(
+            baseline.get(9, Step::Part2),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(baseline.get(9, Step::Part1), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+//! Differential testing of the solutions of all authors.
+//!
+//! Every implementation of a day gets the same input and the answers are compared with each other.
+//! The dima solutions run in-process, the others are described in `crosscheck.toml` by the
+//! command running them and patterns extracting the answers from their output.
+
+use crate::runner;
+use crate::solution::{self, Part};
+use crate::verify;
+use serde::Deserialize;
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Implementations of the other authors.
+pub const IMPLEMENTATIONS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/crosscheck.toml");
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    #[serde(rename = "implementation", default)]
+    pub implementations: Vec<Implementation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Implementation {
+    pub author: String,
+    pub day: u8,
+    /// Directory of the implementation, relative to the manifest.
+    pub dir: String,
+    /// Command to run in `dir`. The placeholders `{dir}`, `{input}` and `{input_words}` are
+    /// replaced by the absolute `dir`, the path of the input and its whitespace separated words.
+    /// The input is passed on stdin unless one of the latter two is used.
+    pub command: Vec<String>,
+    /// For solutions reading a fixed file from the current directory: the command runs in a
+    /// temporary directory containing the input under this name.
+    pub input_file: Option<String>,
+    /// The author's own input, relative to `dir`.
+    pub input: Option<String>,
+    /// Whether `input` is compiled into the command, e.g. with `include_str!`, so that it only
+    /// runs on that input.
+    #[serde(default)]
+    pub embedded: bool,
+    /// Patterns extracting the answers from the output, see `verify::extract`. Parts without a
+    /// pattern are not compared.
+    pub part1: Option<String>,
+    pub part2: Option<String>,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+}
+
+/// Answer of an implementation for a part; `Ok(None)` if the part is not compared.
+pub type Answer = Result<Option<String>, String>;
+
+/// Answers of all implementations of a day for one input.
+#[derive(Debug)]
+pub struct Report {
+    pub answers: Vec<(String, [Answer; 2])>,
+}
+
+impl Report {
+    /// Parts for which the implementations found different answers.
+    pub fn disagreements(&self) -> Vec<Part> {
+        Part::ALL
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                let distinct: BTreeSet<String> = self
+                    .answers
+                    .iter()
+                    .filter_map(|(_, answers)| answers[*i].as_ref().ok())
+                    .filter_map(|answer| answer.as_ref())
+                    .map(|answer| verify::normalize(answer))
+                    .collect();
+                distinct.len() > 1
+            })
+            .map(|(_, &part)| part)
+            .collect()
+    }
+}
+
+fn solve_dima(day: u8, input: &Path) -> [Answer; 2] {
+    let answers = solution::find(day)
+        .ok_or_else(|| format!("invalid day: {}", day).into())
+        .and_then(|solution| {
+            let input = runner::read_input(&input.to_string_lossy())?;
+            runner::solve(&solution, &input, &Part::ALL)
+        });
+    match answers {
+        Ok(answers) => [answers[0].clone(), answers[1].clone()],
+        Err(e) => [Err(e.to_string()), Err(e.to_string())],
+    }
+}
+
+fn run(implementation: &Implementation, root: &Path, input: &Path) -> Result<String, String> {
+    let dir = fs::canonicalize(root.join(&implementation.dir))
+        .map_err(|e| format!("{}: {}", implementation.dir, e))?;
+    let input = fs::canonicalize(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+    let content = fs::read_to_string(&input).map_err(|e| e.to_string())?;
+
+    let mut stdin = Some(input.as_path());
+    let mut command = Vec::new();
+    for arg in &implementation.command {
+        if arg == "{input_words}" {
+            command.extend(content.split_whitespace().map(String::from));
+            stdin = None;
+        } else {
+            if arg.contains("{input}") {
+                stdin = None;
+            }
+            command.push(
+                arg.replace("{dir}", &dir.to_string_lossy())
+                    .replace("{input}", &input.to_string_lossy()),
+            );
+        }
+    }
+
+    match &implementation.input_file {
+        Some(file_name) => {
+            let tmp = std::env::temp_dir().join(format!(
+                "aoc-crosscheck-{}-{}-day{}",
+                process::id(),
+                implementation.author,
+                implementation.day
+            ));
+            fs::create_dir_all(&tmp).map_err(|e| e.to_string())?;
+            fs::write(tmp.join(file_name), &content).map_err(|e| e.to_string())?;
+            let output = verify::run_command(&command, &tmp, stdin);
+            let _ = fs::remove_dir_all(&tmp);
+            output
+        }
+        None => verify::run_command(&command, &dir, stdin),
+    }
+}
+
+fn solve(implementation: &Implementation, root: &Path, input: &Path) -> [Answer; 2] {
+    let output = run(implementation, root, input);
+    let extract = |pattern: &Option<String>| -> Answer {
+        match (pattern, &output) {
+            (None, _) => Ok(None),
+            (Some(_), Err(e)) => Err(e.clone()),
+            (Some(pattern), Ok(output)) => verify::extract(output, Some(pattern))
+                .map(Some)
+                .map_err(|e| e.to_string()),
+        }
+    };
+    [
+        extract(&implementation.part1),
+        extract(&implementation.part2),
+    ]
+}
+
+/// Whether `implementation` can run on `input`, i.e. its input isn't embedded or is `input`.
+fn runs_on(implementation: &Implementation, root: &Path, input: &Path) -> bool {
+    match (implementation.embedded, &implementation.input) {
+        (false, _) => true,
+        (true, None) => false,
+        (true, Some(own)) => {
+            let own = root.join(&implementation.dir).join(own);
+            match (fs::canonicalize(own), fs::canonicalize(input)) {
+                (Ok(own), Ok(input)) => own == input,
+                _ => false,
+            }
+        }
+    }
+}
+
+/// Runs all implementations of `day` on `input`.
+pub fn crosscheck(manifest: &Path, day: u8, input: &Path) -> Result<Report, Box<dyn Error>> {
+    let root = manifest.parent().unwrap_or_else(|| Path::new("."));
+    let implementations = Manifest::load(manifest)?.implementations;
+
+    let mut answers = vec![("dima".to_string(), solve_dima(day, input))];
+    for implementation in implementations
+        .iter()
+        .filter(|i| i.day == day && runs_on(i, root, input))
+    {
+        answers.push((
+            implementation.author.clone(),
+            solve(implementation, root, input),
+        ));
+    }
+    Ok(Report { answers })
+}
+
+/// Inputs of `day`: dima's, the embedded ones and, if `all` is set, the ones of the other
+/// authors.
+pub fn inputs(manifest: &Path, day: u8, all: bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let root = manifest.parent().unwrap_or_else(|| Path::new("."));
+    let mut inputs = vec![runner::input_path(Path::new(runner::INPUT_DIR), day)];
+    for implementation in Manifest::load(manifest)?.implementations {
+        if let (true, Some(input)) = (implementation.day == day, &implementation.input) {
+            let input = root.join(&implementation.dir).join(input);
+            if (all || implementation.embedded) && !inputs.contains(&input) {
+                inputs.push(input);
+            }
+        }
+    }
+    Ok(inputs)
+}
+
+/// Days with at least one implementation besides dima's.
+pub fn days(manifest: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    let days: BTreeSet<u8> = Manifest::load(manifest)?
+        .implementations
+        .iter()
+        .map(|i| i.day)
+        .collect();
+    Ok(days.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(answers: &[(&str, &str)]) -> Report {
+        Report {
+            answers: answers
+                .iter()
+                .map(|(a, b)| {
+                    let answer = |s: &str| match s {
+                        "" => Ok(None),
+                        "error" => Err("error".to_string()),
+                        s => Ok(Some(s.to_string())),
+                    };
+                    ("author".to_string(), [answer(a), answer(b)])
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_disagreements() {
+        let agree = report(&[("3", "x"), ("3", ""), ("error", "x")]);
+        assert!(agree.disagreements().is_empty());
+        let disagree = report(&[("3", "x"), ("4", "x")]);
+        assert_eq!(disagree.disagreements(), vec![Part::One]);
+    }
+
+    #[test]
+    fn test_manifest() -> Result<(), Box<dyn Error>> {
+        let manifest = Manifest::load(Path::new(IMPLEMENTATIONS))?;
+        for implementation in &manifest.implementations {
+            assert!((1..=25).contains(&implementation.day));
+            assert!(implementation.part1.is_some() || implementation.part2.is_some());
+            assert!(!implementation.embedded || implementation.input.is_some());
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,16 @@
+use crate::parse::{self, ParseError};
+use crate::solution::Solution;
 use itertools::{FoldWhile, Itertools};
 use std::collections::BTreeSet;
-use std::num::ParseIntError;
-use std::str::FromStr;
 
-fn parse(input: &str) -> Result<Vec<i64>, ParseIntError> {
-    input.lines().map(i64::from_str).collect()
+fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    parse::lines(input)
+        .map(|mut line| {
+            let n = line.number()?;
+            line.end()?;
+            Ok(n)
+        })
+        .collect()
 }
 
 fn solve1(seq: &[i64]) -> i64 {
@@ -28,8 +34,24 @@ fn solve2(seq: &[i64]) -> i64 {
         .into_inner()
 }
 
-pub fn solve(input: &str) -> Result<(i64, i64), ParseIntError> {
-    parse(input).map(|seq| (solve1(&seq), solve2(&seq)))
+pub struct Day1;
+
+impl Solution for Day1 {
+    type Input = Vec<i64>;
+    type Part1 = i64;
+    type Part2 = i64;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(seq: &Self::Input) -> i64 {
+        solve1(seq)
+    }
+
+    fn part2(seq: &Self::Input) -> i64 {
+        solve2(seq)
+    }
 }
 
 #[cfg(test)]
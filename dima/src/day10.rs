@@ -1,12 +1,13 @@
+use crate::parse::{self, ParseError};
+use crate::solution::Solution;
 use std::f32;
 use std::ops::Add;
 use std::ops::Mul;
 use std::ops::Sub;
 use std::str;
-use text_io::{scan, try_scan};
 
 #[derive(Clone, Copy, Debug)]
-struct Point {
+pub struct Point {
     x: isize,
     y: isize,
 }
@@ -58,7 +59,7 @@ impl Sub<Point> for Point {
 }
 
 #[derive(Clone, Copy, Debug)]
-struct Vector {
+pub struct Vector {
     x: isize,
     y: isize,
 }
@@ -79,28 +80,31 @@ impl Mul<&Vector> for isize {
     }
 }
 
-fn parse(input: &str) -> Vec<(Point, Vector)> {
-    input
-        .lines()
-        .map(|line| {
-            let x;
-            let y;
-            let vx;
-            let vy;
-            scan!(line.bytes().filter(|&x| x != ' ' as u8) =>
-                "position=<{},{}>velocity=<{},{}>", x, y, vx, vy);
-            (Point { x, y }, Vector { x: vx, y: vy })
+fn parse(input: &str) -> Result<Vec<(Point, Vector)>, ParseError> {
+    parse::lines(input)
+        .map(|mut line| {
+            line.tag("position=<")?;
+            let x = line.number()?;
+            line.tag(",")?;
+            let y = line.number()?;
+            line.tag(">")?;
+            line.tag("velocity=<")?;
+            let vx = line.number()?;
+            line.tag(",")?;
+            let vy = line.number()?;
+            line.tag(">")?;
+            line.end()?;
+            Ok((Point { x, y }, Vector { x: vx, y: vy }))
         })
         .collect()
 }
 
-pub fn solve(input: &str) -> isize {
-    let configuration = parse(input);
-
+/// Time at which the points are closest together
+fn message_time(configuration: &[(Point, Vector)]) -> isize {
     let mut prev_weighted_dist = f32::MAX;
     let mut t = 0;
 
-    let t = loop {
+    loop {
         let min = configuration
             .iter()
             .map(|(pt, v)| pt + t * v)
@@ -117,8 +121,10 @@ pub fn solve(input: &str) -> isize {
 
         prev_weighted_dist = weighted_dist;
         t += 1;
-    };
+    }
+}
 
+fn render(configuration: &[(Point, Vector)], t: isize) -> String {
     let (min, max) = configuration
         .iter()
         .fold((Point::MAX, Point::MIN), |(min, max), (pt, v)| {
@@ -130,7 +136,7 @@ pub fn solve(input: &str) -> isize {
 
     let mut fields = Vec::with_capacity(w * h);
     fields.resize((w * h) as usize, ' ' as u8);
-    for (pt, v) in &configuration {
+    for (pt, v) in configuration {
         let pt = pt + t * v - min;
         let idx = pt.x + (w as isize) * pt.y;
         fields[idx as usize] = '*' as u8;
@@ -142,6 +148,25 @@ pub fn solve(input: &str) -> isize {
         res += line;
         res.push('\n');
     }
-    println!("{}", res);
-    t
+    res
+}
+
+pub struct Day10;
+
+impl Solution for Day10 {
+    type Input = Vec<(Point, Vector)>;
+    type Part1 = String;
+    type Part2 = isize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(configuration: &Self::Input) -> String {
+        render(configuration, message_time(configuration))
+    }
+
+    fn part2(configuration: &Self::Input) -> isize {
+        message_time(configuration)
+    }
 }
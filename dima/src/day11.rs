@@ -1,3 +1,6 @@
+use crate::parse::{Cursor, ParseError};
+use crate::solution::Solution;
+
 fn make_grid(grid_serial_number: i64) -> Vec<i64> {
     let mut grid = Vec::with_capacity(300 * 300);
     for y in 1..=300 {
@@ -79,10 +82,27 @@ fn solve2(grid: &[i64]) -> (usize, usize, usize) {
     (max_x + 1, max_y + 1, max_size)
 }
 
-pub fn solve(input: &str) -> ((usize, usize), (usize, usize, usize)) {
-    let grid_serial_number: i64 = input.parse().unwrap();
-    let grid = make_grid(grid_serial_number);
-    (solve1(&grid), solve2(&grid))
+pub struct Day11;
+
+impl Solution for Day11 {
+    type Input = Vec<i64>;
+    type Part1 = (usize, usize);
+    type Part2 = (usize, usize, usize);
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        let mut line = Cursor::new(1, input);
+        let grid_serial_number = line.number()?;
+        line.end()?;
+        Ok(make_grid(grid_serial_number))
+    }
+
+    fn part1(grid: &Self::Input) -> (usize, usize) {
+        solve1(grid)
+    }
+
+    fn part2(grid: &Self::Input) -> (usize, usize, usize) {
+        solve2(grid)
+    }
 }
 
 #[cfg(test)]
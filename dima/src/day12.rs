@@ -1,10 +1,12 @@
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
 use std::fmt::{self, Write};
 use std::mem;
 use std::ops::Index;
 use std::ops::IndexMut;
 
-#[derive(Default, Clone)]
-struct Field {
+#[derive(Debug, Default, Clone)]
+pub struct Field {
     lhs: Vec<bool>,
     rhs: Vec<bool>,
 }
@@ -87,34 +89,66 @@ impl IndexMut<isize> for Field {
 }
 
 #[derive(Debug, Default)]
-struct Rule {
+pub struct Rule {
     pattern: [bool; 5],
     // is_alive: bool,
 }
 
+/// Consumes pots given as `#` (with plant) or `.` (without plant).
+fn parse_pots(line: &mut Cursor) -> Result<Vec<bool>, ParseError> {
+    line.skip_spaces();
+    let pots = line.take_while(|c| c == '#' || c == '.');
+    if pots.is_empty() {
+        return Err(line.expected("pots"));
+    }
+    Ok(pots.chars().map(|c| c == '#').collect())
+}
+
 impl Rule {
-    fn parse(input: &str) -> Self {
-        let mut rule = Self::default();
-        let pattern = input[0..=5].chars().map(|c| c == '#');
-        for (bit, val) in rule.pattern.iter_mut().zip(pattern) {
-            *bit = val;
+    /// Parses a rule like `..#.. => #`; `None` if the pot stays empty.
+    fn parse(line: &mut Cursor) -> Result<Option<Self>, ParseError> {
+        let col = line.col();
+        let pattern = parse_pots(line)?;
+        if pattern.len() != 5 {
+            return Err(ParseError::invalid(
+                line.line(),
+                col,
+                "pattern must have 5 pots",
+            ));
+        }
+        line.tag("=>")?;
+        let col = line.col() + 1;
+        let is_alive = parse_pots(line)?;
+        if is_alive.len() != 1 {
+            return Err(ParseError::invalid(
+                line.line(),
+                col,
+                "result must be one pot",
+            ));
         }
-        // rule.is_alive = input.chars().nth(9) == Some('#');
-        rule
+        line.end()?;
+
+        let mut rule = Self::default();
+        rule.pattern.copy_from_slice(&pattern);
+        Ok(if is_alive[0] { Some(rule) } else { None })
     }
 }
 
-fn parse(input: &str) -> (Field, Vec<Rule>) {
-    let mut lines = input.lines();
-    let first_line = lines.next().unwrap();
-    let initial_generation = first_line.chars().skip(15).map(|c| c == '#').collect();
+fn parse(input: &str) -> Result<(Field, Vec<Rule>), ParseError> {
+    let mut lines = parse::lines(input);
+    let mut first_line = lines
+        .next()
+        .ok_or_else(|| ParseError::eof(0, "initial state"))?;
+    first_line.tag("initial state:")?;
+    let initial_generation = parse_pots(&mut first_line)?;
+    first_line.end()?;
     let field = Field::with_positive(initial_generation);
-    let rules = lines
-        .skip(1)
-        .filter(|p| p.chars().nth(9) == Some('#'))
-        .map(Rule::parse)
-        .collect();
-    (field, rules)
+
+    let mut rules = Vec::new();
+    for mut line in lines.skip(1) {
+        rules.extend(Rule::parse(&mut line)?);
+    }
+    Ok((field, rules))
 }
 
 fn solve1(mut gen: Field, rules: &[Rule]) -> isize {
@@ -177,9 +211,24 @@ fn solve2(mut gen: Field, rules: &[Rule]) -> isize {
     panic!("should not land here");
 }
 
-pub fn solve(input: &str) -> (isize, isize) {
-    let (gen, rules) = parse(input);
-    (solve1(gen.clone(), &rules), solve2(gen, &rules))
+pub struct Day12;
+
+impl Solution for Day12 {
+    type Input = (Field, Vec<Rule>);
+    type Part1 = isize;
+    type Part2 = isize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1((gen, rules): &Self::Input) -> isize {
+        solve1(gen.clone(), rules)
+    }
+
+    fn part2((gen, rules): &Self::Input) -> isize {
+        solve2(gen.clone(), rules)
+    }
 }
 
 #[cfg(test)]
@@ -205,7 +254,7 @@ mod tests {
 
     #[test]
     fn test_parse() {
-        let (field, rules) = parse(INPUT);
+        let (field, rules) = parse(INPUT).unwrap();
         assert_eq!(format!("{}", field), "#..#.#..##......###...###");
         assert_eq!(rules[0].pattern, [false, false, false, true, true]);
         assert_eq!(rules[1].pattern, [false, false, true, false, false]);
@@ -213,5 +262,11 @@ mod tests {
             rules.last().unwrap().pattern,
             [true, true, true, true, false]
         );
+        assert_eq!(
+            parse("initial state: #.\n\n..#. => #")
+                .unwrap_err()
+                .to_string(),
+            "line 3 col 1: pattern must have 5 pots"
+        );
     }
 }
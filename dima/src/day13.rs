@@ -1,9 +1,11 @@
+use crate::parse::ParseError;
+use crate::solution::Solution;
 use std::cmp::{Ord, Ordering};
 use std::fmt::{self, Write};
 use std::iter;
 use std::mem;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Direction {
     Up,
     Down,
@@ -43,7 +45,7 @@ impl From<char> for Direction {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Cart {
     pos: (usize, usize),
     dir: Direction,
@@ -85,8 +87,8 @@ impl Into<char> for &Cart {
     }
 }
 
-#[derive(Debug)]
-struct Configuration {
+#[derive(Debug, Clone)]
+pub struct Configuration {
     tracks: Vec<char>,
     width: usize,
     height: usize,
@@ -164,41 +166,41 @@ impl Configuration {
     }
 }
 
-fn parse(input: &str) -> Configuration {
-    let width = input.lines().map(|l| l.len()).max().unwrap();
+fn parse(input: &str) -> Result<Configuration, ParseError> {
+    let width = input.lines().map(|l| l.len()).max().unwrap_or(0);
     let height = input.lines().count();
 
     let mut tracks = Vec::new();
     let mut carts = Vec::new();
     for (i, line) in input.lines().enumerate() {
-        tracks.extend(line.chars().enumerate().map(|(j, c)| match c {
-            '^' | 'v' => {
-                carts.push(Cart {
-                    pos: (i, j),
-                    dir: Direction::from(c),
-                    state: 0,
-                });
-                '|'
-            }
-            '<' | '>' => {
-                carts.push(Cart {
-                    pos: (i, j),
-                    dir: Direction::from(c),
-                    state: 0,
-                });
-                '-'
-            }
-            other => other,
-        }));
+        for (j, c) in line.chars().enumerate() {
+            let track = match c {
+                '^' | 'v' | '<' | '>' => {
+                    carts.push(Cart {
+                        pos: (i, j),
+                        dir: Direction::from(c),
+                        state: 0,
+                    });
+                    if c == '^' || c == 'v' {
+                        '|'
+                    } else {
+                        '-'
+                    }
+                }
+                ' ' | '|' | '-' | '/' | '\\' | '+' => c,
+                _ => return Err(ParseError::unknown(i + 1, j + 1, "track", &c.to_string())),
+            };
+            tracks.push(track);
+        }
         tracks.extend(iter::repeat(' ').take(width - line.len()));
     }
 
-    Configuration {
+    Ok(Configuration {
         tracks,
         width,
         height,
         carts,
-    }
+    })
 }
 
 impl fmt::Display for Configuration {
@@ -218,8 +220,22 @@ impl fmt::Display for Configuration {
     }
 }
 
-pub fn solve(input: &str) -> ((usize, usize), (usize, usize)) {
-    let conf = parse(input);
-    let conf2 = parse(input);
-    (conf.first_crash(), conf2.last_car())
+pub struct Day13;
+
+impl Solution for Day13 {
+    type Input = Configuration;
+    type Part1 = (usize, usize);
+    type Part2 = (usize, usize);
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(conf: &Self::Input) -> (usize, usize) {
+        conf.clone().first_crash()
+    }
+
+    fn part2(conf: &Self::Input) -> (usize, usize) {
+        conf.clone().last_car()
+    }
 }
@@ -1,5 +1,5 @@
-use std::error::Error;
-use std::str;
+use crate::parse::{Cursor, ParseError};
+use crate::solution::Solution;
 
 fn score(num_recipies: usize) -> usize {
     let mut board: Vec<u8> = Vec::with_capacity(num_recipies + 10);
@@ -53,9 +53,31 @@ fn num_recipies(score: &str) -> usize {
     }
 }
 
-pub fn solve(input: &str) -> Result<(usize, usize), Box<Error>> {
-    let n: usize = str::parse(input)?;
-    Ok((score(n), num_recipies(input)))
+pub struct Day14;
+
+impl Solution for Day14 {
+    type Input = (usize, String);
+    type Part1 = String;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        let mut line = Cursor::new(1, input);
+        let digits = line.take_while(|c| c.is_ascii_digit());
+        line.end()?;
+        let n = digits
+            .parse()
+            .map_err(|_| ParseError::invalid(1, 1, "expected a number of recipes"))?;
+        Ok((n, digits.to_string()))
+    }
+
+    fn part1((n, _): &Self::Input) -> String {
+        // the score consists of exactly 10 digits including leading zeros
+        format!("{:010}", score(*n))
+    }
+
+    fn part2((_, digits): &Self::Input) -> usize {
+        num_recipies(digits)
+    }
 }
 
 #[cfg(test)]
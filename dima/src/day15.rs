@@ -1,71 +1,156 @@
-use std::collections::{HashMap, VecDeque};
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
+use common::grid::{Grid, Pos};
+use common::search;
 use std::fmt::{self, Write};
-use std::ops::Index;
-use std::ops::IndexMut;
+use std::str::FromStr;
+use std::thread;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Field {
     Wall,
     Open,
     Npc(usize),
 }
 
-#[derive(Debug)]
-struct Map {
-    fields: Vec<Field>,
-    width: usize,
-    height: usize,
+type Map = Grid<Field>;
+
+/// A race of npcs, which fights all other races.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Race {
+    /// The character of its npcs on the map.
+    pub symbol: char,
+    pub hit_points: i32,
+    pub attack_power: i32,
+    /// Whether an attack also hits the allies of the attacker next to the target.
+    pub friendly_fire: bool,
 }
 
-impl Map {
-    fn neighbors<'a>(&'a self, (x, y): (usize, usize)) -> impl Iterator<Item = Position> + 'a {
-        [(0, -1), (-1, 0), (1, 0), (0, 1)]
-            .into_iter()
-            .map(move |(dx, dy)| ((x as isize + dx) as usize, (y as isize + dy) as usize))
-            .filter(move |&(x, y)| x < self.width && y < self.height)
+impl Race {
+    pub fn new(symbol: char) -> Self {
+        Race {
+            symbol,
+            hit_points: 200,
+            attack_power: 3,
+            friendly_fire: false,
+        }
     }
+}
 
-    fn open_neighbors<'a>(&'a self, pos: (usize, usize)) -> impl Iterator<Item = Position> + 'a {
-        self.neighbors(pos).filter(move |&pos| match self[pos] {
-            Field::Open => true,
-            _ => false,
-        })
+/// Which of the enemies in range an npc attacks; ties are broken in reading order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetOrder {
+    FewestHitPoints,
+    MostHitPoints,
+    ReadingOrder,
+}
+
+/// The rules of combat, `Rules::default()` are the ones of the puzzle: elves `E` and goblins `G`
+/// with 200 hit points and an attack power of 3 each, moving horizontally and vertically and
+/// attacking the weakest enemy in range.
+///
+/// Rules can be parsed from lines like `race O 300 5 friendly-fire`, `diagonal` and
+/// `target most-hit-points`, with the races in the order given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    pub races: Vec<Race>,
+    /// Whether npcs can move diagonally, too; they always attack horizontally or vertically.
+    pub diagonal: bool,
+    pub target_order: TargetOrder,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            races: vec![Race::new('E'), Race::new('G')],
+            diagonal: false,
+            target_order: TargetOrder::FewestHitPoints,
+        }
     }
 }
 
-impl Index<(usize, usize)> for Map {
-    type Output = Field;
-    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
-        &self.fields[x + self.width * y]
+impl Rules {
+    /// The index of the race with `symbol`.
+    pub fn race(&self, symbol: char) -> Option<usize> {
+        self.races.iter().position(|race| race.symbol == symbol)
     }
 }
 
-impl IndexMut<(usize, usize)> for Map {
-    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
-        &mut self.fields[x + self.width * y]
+/// Consumes a word of letters and dashes after optional spaces.
+fn keyword<'a>(line: &mut Cursor<'a>) -> Result<(usize, &'a str), ParseError> {
+    line.skip_spaces();
+    let col = line.col();
+    match line.take_while(|c| c.is_ascii_alphabetic() || c == '-') {
+        "" => Err(line.expected("a word")),
+        word => Ok((col, word)),
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum Race {
-    Elf,
-    Goblin,
+impl FromStr for Rules {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules {
+            races: Vec::new(),
+            ..Rules::default()
+        };
+        for mut line in parse::lines(input) {
+            line.skip_spaces();
+            if line.is_empty() || line.rest().starts_with('#') {
+                continue;
+            }
+            let (col, word) = keyword(&mut line)?;
+            match word {
+                "race" => {
+                    line.skip_spaces();
+                    let col = line.col();
+                    let symbol = line.char()?;
+                    if "#. ".contains(symbol) || rules.race(symbol).is_some() {
+                        return Err(ParseError::invalid(line.line(), col, "invalid race symbol"));
+                    }
+                    let mut race = Race {
+                        hit_points: line.number()?,
+                        attack_power: line.number()?,
+                        ..Race::new(symbol)
+                    };
+                    if !line.rest().trim().is_empty() {
+                        line.tag("friendly-fire")?;
+                        race.friendly_fire = true;
+                    }
+                    rules.races.push(race);
+                }
+                "diagonal" => rules.diagonal = true,
+                "target" => {
+                    let (col, order) = keyword(&mut line)?;
+                    rules.target_order = match order {
+                        "fewest-hit-points" => TargetOrder::FewestHitPoints,
+                        "most-hit-points" => TargetOrder::MostHitPoints,
+                        "reading-order" => TargetOrder::ReadingOrder,
+                        _ => return Err(ParseError::unknown(line.line(), col, "order", order)),
+                    };
+                }
+                _ => return Err(ParseError::unknown(line.line(), col, "rule", word)),
+            }
+            line.end()?;
+        }
+        if rules.races.len() < 2 {
+            let lines = input.lines().count();
+            return Err(ParseError::eof(lines, "at least two races"));
+        }
+        Ok(rules)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Npc {
     pos: (usize, usize),
     hit_points: i32,
-    race: Race,
+    /// Index of the race in the rules.
+    race: usize,
     power: i32,
 }
 
-type Position = (usize, usize);
-
 impl Npc {
-    const DEFAULT_ATTACK_POWER: i32 = 3;
-    const INITIAL_HIT_POINTS: i32 = 200;
-
     fn is_alive(&self) -> bool {
         self.hit_points > 0
     }
@@ -75,23 +160,142 @@ impl Npc {
     }
 }
 
-impl fmt::Display for Npc {
+/// What happens during combat, in the order it happens; npcs are numbered in reading order at
+/// the start.
+///
+/// A log of events can be written to a file and replayed, one event per line like
+/// `moved 3 4,1 5,1`, `attacked 3 7 197`, `died 7` and `round 12`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Moved {
+        npc: usize,
+        from: Pos,
+        to: Pos,
+    },
+    /// The target has `hit_points` left.
+    Attacked {
+        npc: usize,
+        target: usize,
+        hit_points: i32,
+    },
+    Died {
+        npc: usize,
+    },
+    /// The full round with this number ended.
+    RoundEnded(usize),
+}
+
+impl fmt::Display for Event {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.race {
-            Race::Elf => write!(f, "E({})", self.hit_points),
-            Race::Goblin => write!(f, "G({})", self.hit_points),
+        match *self {
+            Event::Moved { npc, from, to } => {
+                write!(f, "moved {} {},{} {},{}", npc, from.0, from.1, to.0, to.1)
+            }
+            Event::Attacked {
+                npc,
+                target,
+                hit_points,
+            } => write!(f, "attacked {} {} {}", npc, target, hit_points),
+            Event::Died { npc } => write!(f, "died {}", npc),
+            Event::RoundEnded(round) => write!(f, "round {}", round),
         }
     }
 }
 
-#[derive(Debug)]
-struct Game {
+fn parse_pos(line: &mut Cursor) -> Result<Pos, ParseError> {
+    let x = line.number()?;
+    line.tag(",")?;
+    Ok((x, line.number()?))
+}
+
+fn parse_event(line: &mut Cursor) -> Result<Event, ParseError> {
+    let (col, word) = line.word()?;
+    let event = match word {
+        "moved" => Event::Moved {
+            npc: line.number()?,
+            from: parse_pos(line)?,
+            to: parse_pos(line)?,
+        },
+        "attacked" => Event::Attacked {
+            npc: line.number()?,
+            target: line.number()?,
+            hit_points: line.number()?,
+        },
+        "died" => Event::Died {
+            npc: line.number()?,
+        },
+        "round" => Event::RoundEnded(line.number()?),
+        _ => return Err(ParseError::unknown(line.line(), col, "event", word)),
+    };
+    line.end()?;
+    Ok(event)
+}
+
+/// Parses a log written with one `Event` per line.
+pub fn parse_log(input: &str) -> Result<Vec<Event>, ParseError> {
+    parse::lines(input)
+        .filter(|line| !line.rest().trim().is_empty())
+        .map(|mut line| parse_event(&mut line))
+        .collect()
+}
+
+/// Describes where `actual` differs from `expected` first, `None` if they are the same.
+pub fn diff(expected: &[Event], actual: &[Event]) -> Option<String> {
+    let idx = (0..expected.len().max(actual.len())).find(|&i| expected.get(i) != actual.get(i))?;
+    let round = 1 + expected[..idx.min(expected.len())]
+        .iter()
+        .filter(|event| matches!(event, Event::RoundEnded(_)))
+        .count();
+    let show = |event: Option<&Event>| event.map_or("the end".to_string(), |e| e.to_string());
+    Some(format!(
+        "round {}, event {}: expected {}, got {}",
+        round,
+        idx + 1,
+        show(expected.get(idx)),
+        show(actual.get(idx))
+    ))
+}
+
+#[derive(Debug, Clone)]
+pub struct Game {
     map: Map,
     npcs: Vec<Npc>,
+    rules: Rules,
+    /// Number of full rounds.
+    rounds: usize,
+    /// The events so far, if they are recorded.
+    log: Option<Vec<Event>>,
+    /// The paths of the moves in the current round to the chosen positions, if they are recorded.
+    paths: Option<Vec<Vec<Pos>>>,
+    /// The race whose first death ends the combat.
+    protected: Option<usize>,
+    /// Whether an npc of the protected race died.
+    casualty: bool,
 }
 
 impl Game {
-    fn round(&mut self) -> bool {
+    fn open_neighbors<'a>(&'a self, pos: Pos) -> impl Iterator<Item = Pos> + 'a {
+        self.map
+            .neighbors4(pos)
+            .filter(move |&pos| match self.map[pos] {
+                Field::Open => true,
+                _ => false,
+            })
+    }
+
+    /// The open positions an npc at `pos` can move to, in reading order.
+    fn moves<'a>(&'a self, pos: Pos) -> impl Iterator<Item = Pos> + 'a {
+        self.map
+            .neighbors8(pos)
+            .filter(move |&next| self.rules.diagonal || next.0 == pos.0 || next.1 == pos.1)
+            .filter(move |&next| matches!(self.map[next], Field::Open))
+    }
+
+    /// Plays a round; false if it didn't end because the combat did.
+    pub fn round(&mut self) -> bool {
+        if let Some(paths) = &mut self.paths {
+            paths.clear();
+        }
         let npcs = self.alive_npcs_in_order();
         for &idx in &npcs {
             if self.npcs[idx].is_alive() {
@@ -104,11 +308,119 @@ impl Game {
                     return false;
                 }
                 self.do_npc_turn(idx);
+                if self.casualty {
+                    return false;
+                }
             }
         }
+        self.rounds += 1;
+        self.emit(Event::RoundEnded(self.rounds));
         true
     }
 
+    fn emit(&mut self, event: Event) {
+        if let Some(log) = &mut self.log {
+            log.push(event);
+        }
+    }
+
+    /// Starts recording the events.
+    pub fn record(&mut self) {
+        self.log.get_or_insert_with(Vec::new);
+    }
+
+    /// The events recorded so far.
+    pub fn events(&self) -> &[Event] {
+        self.log.as_deref().unwrap_or(&[])
+    }
+
+    /// Starts recording the paths of the moves.
+    pub fn record_paths(&mut self) {
+        self.paths.get_or_insert_with(Vec::new);
+    }
+
+    /// The paths of the moves in the last round from the npcs to the positions they chose, if
+    /// they are recorded.
+    pub fn paths(&self) -> &[Vec<Pos>] {
+        self.paths.as_deref().unwrap_or(&[])
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn width(&self) -> usize {
+        self.map.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.map.height()
+    }
+
+    pub fn is_wall(&self, pos: Pos) -> bool {
+        matches!(self.map[pos], Field::Wall)
+    }
+
+    /// The race and hit points of the npc at `pos`.
+    pub fn npc_at(&self, pos: Pos) -> Option<(usize, i32)> {
+        match self.map[pos] {
+            Field::Npc(idx) => Some((self.npcs[idx].race, self.npcs[idx].hit_points)),
+            _ => None,
+        }
+    }
+
+    /// Replays `event`, which must be possible in this state.
+    pub fn apply(&mut self, event: &Event) -> Result<(), String> {
+        let alive = |game: &Game, idx: usize| match game.npcs.get(idx) {
+            Some(npc) if npc.is_alive() => Ok(npc.pos),
+            _ => Err(format!("{}: npc {} is not alive", event, idx)),
+        };
+        let adjacent =
+            |(x1, y1): Pos, (x2, y2): Pos| x1.max(x2) - x1.min(x2) + y1.max(y2) - y1.min(y2) == 1;
+        match *event {
+            Event::Moved { npc, from, to } => {
+                if alive(self, npc)? != from {
+                    return Err(format!("{}: npc {} is somewhere else", event, npc));
+                }
+                if !self.moves(from).any(|pos| pos == to) {
+                    return Err(format!("{}: can't move there", event));
+                }
+                self.map[from] = Field::Open;
+                self.map[to] = Field::Npc(npc);
+                self.npcs[npc].pos = to;
+            }
+            Event::Attacked {
+                npc,
+                target,
+                hit_points,
+            } => {
+                let (from, to) = (alive(self, npc)?, alive(self, target)?);
+                // a hit of friendly fire is next to the target instead
+                let race = &self.rules.races[self.npcs[npc].race];
+                let ally = self.npcs[npc].race == self.npcs[target].race;
+                if !(adjacent(from, to) || ally && race.friendly_fire) {
+                    return Err(format!("{}: the target is out of reach", event));
+                }
+                self.npcs[target].hit_points = hit_points;
+            }
+            Event::Died { npc } => match self.npcs.get(npc) {
+                Some(dead)
+                    if !dead.is_alive()
+                        && matches!(self.map[dead.pos], Field::Npc(idx) if idx == npc) =>
+                {
+                    self.map[dead.pos] = Field::Open;
+                }
+                _ => return Err(format!("{}: npc {} is not dying", event, npc)),
+            },
+            Event::RoundEnded(round) if round == self.rounds + 1 => self.rounds = round,
+            Event::RoundEnded(_) => {
+                return Err(format!("{}: round {} ended last", event, self.rounds));
+            }
+        }
+        self.emit(event.clone());
+        Ok(())
+    }
+
     fn do_npc_turn(&mut self, idx: usize) {
         if !self.attack(idx) {
             self.do_npc_move(idx);
@@ -118,11 +430,22 @@ impl Game {
 
     fn attack(&mut self, idx: usize) -> bool {
         if let Some(target_idx) = self.in_range_of_target(&self.npcs[idx]) {
-            let attack_power = self.npcs[idx].power;
-            let target_npc = &mut self.npcs[target_idx];
-            target_npc.hit(attack_power);
-            if !target_npc.is_alive() {
-                self.map[target_npc.pos] = Field::Open;
+            let race = self.npcs[idx].race;
+            let mut hits = vec![target_idx];
+            if self.rules.races[race].friendly_fire {
+                let allies = self
+                    .map
+                    .neighbors4(self.npcs[target_idx].pos)
+                    .filter_map(|pos| match self.map[pos] {
+                        Field::Npc(ally) if ally != idx && self.npcs[ally].race == race => {
+                            Some(ally)
+                        }
+                        _ => None,
+                    });
+                hits.extend(allies);
+            }
+            for target_idx in hits {
+                self.hit(idx, target_idx);
             }
             true
         } else {
@@ -130,9 +453,26 @@ impl Game {
         }
     }
 
+    fn hit(&mut self, idx: usize, target_idx: usize) {
+        let attack_power = self.npcs[idx].power;
+        let target_npc = &mut self.npcs[target_idx];
+        target_npc.hit(attack_power);
+        let (hit_points, pos) = (target_npc.hit_points, target_npc.pos);
+        self.emit(Event::Attacked {
+            npc: idx,
+            target: target_idx,
+            hit_points,
+        });
+        if hit_points <= 0 {
+            self.map[pos] = Field::Open;
+            self.emit(Event::Died { npc: target_idx });
+            self.casualty |= Some(self.npcs[target_idx].race) == self.protected;
+        }
+    }
+
     fn in_range_of_target(&self, npc: &Npc) -> Option<usize> {
         self.map
-            .neighbors(npc.pos)
+            .neighbors4(npc.pos)
             .filter_map(|pos| match &self.map[pos] {
                 Field::Npc(idx) => {
                     let target_npc = &self.npcs[*idx];
@@ -146,7 +486,12 @@ impl Game {
             })
             .min_by_key(|idx| {
                 let npc = &self.npcs[*idx];
-                (npc.hit_points, npc.pos.1, npc.pos.0)
+                let hit_points = match self.rules.target_order {
+                    TargetOrder::FewestHitPoints => npc.hit_points,
+                    TargetOrder::MostHitPoints => -npc.hit_points,
+                    TargetOrder::ReadingOrder => 0,
+                };
+                (hit_points, npc.pos.1, npc.pos.0)
             })
     }
 
@@ -158,72 +503,37 @@ impl Game {
             .iter()
             .cloned()
             .filter(|&target_idx| self.npcs[target_idx].race != self.npcs[idx].race);
-        let in_range = targets.flat_map(|idx| self.map.open_neighbors(self.npcs[idx].pos));
-        let mut in_range: Vec<Position> = in_range.collect();
+        let in_range = targets.flat_map(|idx| self.open_neighbors(self.npcs[idx].pos));
+        let mut in_range: Vec<Pos> = in_range.collect();
 
         if in_range.is_empty() {
             // nothing to do
             return false;
         }
 
-        // BFS from npc to in range positions
-        let mut distances = HashMap::new();
-        distances.insert(npc_pos, 0);
-        let mut queue = VecDeque::new();
-        queue.push_back(npc_pos);
-        while let Some(pos) = queue.pop_front() {
-            for neighbor_pos in self.map.open_neighbors(pos) {
-                if !distances.contains_key(&neighbor_pos) {
-                    distances.insert(neighbor_pos, distances[&pos] + 1);
-                    queue.push_back(neighbor_pos);
-                }
-            }
-        }
-
-        in_range.retain(|pos| distances.contains_key(pos));
-
-        let reachable = in_range
-            .into_iter()
-            .filter(|pos| distances.contains_key(pos));
-        let chosen = reachable.min_by_key(|pos| (distances[pos], pos.1, pos.0));
-        let chosen = match chosen {
-            Some(pos) => pos,
-            None => return true, // nowhere to move
-        };
-
-        // BFS from chosen pos to npc
-        queue.clear();
-        queue.push_back(chosen);
-        distances.clear();
-        distances.insert(chosen, 0);
-        while let Some(pos) = queue.pop_front() {
-            if pos == npc_pos {
-                // done
-                break;
-            }
-            for neighbor_pos in self.map.open_neighbors(pos) {
-                if !distances.contains_key(&neighbor_pos) {
-                    distances.insert(neighbor_pos, distances[&pos] + 1);
-                    queue.push_back(neighbor_pos);
-                }
-            }
-        }
-
-        // Next neightbor to move
-        let move_to = self
-            .map
-            .neighbors(npc_pos)
-            .filter_map(|pos| distances.get(&pos).map(|d| (d, pos.1, pos.0)))
-            .min();
-        let move_to_pos = match move_to {
-            Some((_, y, x)) => (x, y),
+        // The neighbors are in reading order, so the first step of the path to the nearest
+        // position is the first one in reading order.
+        let search = search::bfs(npc_pos, |&pos| self.moves(pos));
+        in_range.sort_by_key(|&(x, y)| (y, x));
+        let chosen = match search.nearest(in_range) {
+            Some((pos, _)) => pos,
             None => return false, // nowhere to move
         };
+        let path = search.path(&chosen).expect("reachable");
+        let move_to_pos = path[1];
+        if let Some(paths) = &mut self.paths {
+            paths.push(path);
+        }
 
         // move
         self.map[npc_pos] = Field::Open;
         self.map[move_to_pos] = Field::Npc(idx);
         self.npcs[idx].pos = move_to_pos;
+        self.emit(Event::Moved {
+            npc: idx,
+            from: npc_pos,
+            to: move_to_pos,
+        });
 
         true
     }
@@ -236,15 +546,14 @@ impl Game {
         npcs
     }
 
-    fn run(&mut self) -> usize {
-        // println!("{}", self);
-        let mut num_rounds = 0;
-        while self.round() {
-            num_rounds += 1;
-            // println!("{}", self);
-            // thread::sleep(time::Duration::from_millis(1000));
-        }
+    /// Runs the combat to the end and returns the outcome.
+    pub fn run(&mut self) -> usize {
+        while self.round() {}
+        self.outcome()
+    }
 
+    /// Number of full rounds times the hit points left.
+    pub fn outcome(&self) -> usize {
         let hit_points = self
             .npcs
             .iter()
@@ -252,106 +561,96 @@ impl Game {
             .map(|npc| npc.hit_points as usize)
             .sum::<usize>();
 
-        num_rounds * hit_points
+        self.rounds * hit_points
     }
 
-    // None, if any elf dies, otherwise the score
-    fn run_until_elf_dies(&mut self, elven_power: i32) -> Option<usize> {
-        // Upgrade elves
-        for elf in self.npcs.iter_mut().filter(|npc| npc.race == Race::Elf) {
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    pub fn set_elven_power(&mut self, elven_power: i32) {
+        let elves = self.rules.race('E');
+        for elf in self.npcs.iter_mut().filter(|npc| Some(npc.race) == elves) {
             elf.power = elven_power;
         }
+    }
 
-        let mut num_rounds = 0;
-        while self.round() {
-            if self
-                .npcs
-                .iter()
-                .any(|npc| !npc.is_alive() && npc.race == Race::Elf)
-            {
-                return None;
-            }
-            num_rounds += 1;
+    /// The outcome if no elf dies; stops at the first death of an elf.
+    fn run_until_elf_dies(&mut self, elven_power: i32) -> Option<usize> {
+        self.set_elven_power(elven_power);
+        self.protected = self.rules.race('E');
+        while self.round() {}
+        if self.casualty {
+            None
+        } else {
+            Some(self.outcome())
         }
-
-        let hit_points = self
-            .npcs
-            .iter()
-            .filter(|npc| npc.is_alive())
-            .map(|npc| npc.hit_points as usize)
-            .sum::<usize>();
-
-        Some(num_rounds * hit_points)
     }
 }
 
-impl From<&str> for Game {
-    fn from(input: &str) -> Game {
-        let mut fields = Vec::new();
+impl Game {
+    /// Parses a map of walls `#`, open fields `.`, elves `E` and goblins `G`.
+    pub fn parse(input: &str) -> Result<Game, ParseError> {
+        Self::with_rules(input, Rules::default())
+    }
+
+    /// Parses a map of walls `#`, open fields `.` and npcs of the races of `rules`.
+    pub fn with_rules(input: &str, rules: Rules) -> Result<Game, ParseError> {
+        let chars = Grid::parse_with(input, |c| match c {
+            '#' | '.' => Some(c),
+            _ if rules.race(c).is_some() => Some(c),
+            _ => None,
+        })?;
+
+        let mut map = Grid::new(chars.width(), chars.height(), Field::Open);
         let mut npcs = Vec::new();
-        let mut x = 0;
-        let mut y = 0;
-
-        for line in input.lines() {
-            x = 0;
-            for c in line.chars() {
-                let field = match c {
-                    '#' => Field::Wall,
-                    '.' => Field::Open,
-                    'E' => {
-                        let field = Field::Npc(npcs.len());
-                        npcs.push(Npc {
-                            pos: (x, y),
-                            hit_points: Npc::INITIAL_HIT_POINTS,
-                            race: Race::Elf,
-                            power: Npc::DEFAULT_ATTACK_POWER,
-                        });
-                        field
-                    }
-                    'G' => {
-                        let field = Field::Npc(npcs.len());
-                        npcs.push(Npc {
-                            pos: (x, y),
-                            hit_points: Npc::INITIAL_HIT_POINTS,
-                            race: Race::Goblin,
-                            power: Npc::DEFAULT_ATTACK_POWER,
-                        });
-                        field
-                    }
-                    _ => panic!("invalid input"),
-                };
-                fields.push(field);
-                x += 1;
-            }
-            y += 1;
+        for (pos, &c) in chars.iter() {
+            map[pos] = match c {
+                '#' => Field::Wall,
+                '.' => Field::Open,
+                _ => {
+                    let field = Field::Npc(npcs.len());
+                    let race = rules.race(c).expect("parsed race");
+                    npcs.push(Npc {
+                        pos,
+                        hit_points: rules.races[race].hit_points,
+                        race,
+                        power: rules.races[race].attack_power,
+                    });
+                    field
+                }
+            };
         }
 
-        let map = Map {
-            fields,
-            width: x,
-            height: y,
-        };
-        Self { map, npcs }
+        Ok(Self {
+            map,
+            npcs,
+            rules,
+            rounds: 0,
+            log: None,
+            paths: None,
+            protected: None,
+            casualty: false,
+        })
     }
 }
 
 impl fmt::Display for Game {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut npcs_per_line = Vec::new();
-        for y in 0..self.map.height {
+        for y in 0..self.map.height() {
             npcs_per_line.clear();
 
-            for x in 0..self.map.width {
+            for x in 0..self.map.width() {
                 let field = &self.map[(x, y)];
                 match field {
                     Field::Wall => f.write_char('#')?,
                     Field::Open => f.write_char('.')?,
                     Field::Npc(idx) => {
-                        npcs_per_line.push(&self.npcs[*idx]);
-                        match self.npcs[*idx].race {
-                            Race::Elf => f.write_char('E')?,
-                            Race::Goblin => f.write_char('G')?,
-                        }
+                        let npc = &self.npcs[*idx];
+                        let symbol = self.rules.races[npc.race].symbol;
+                        npcs_per_line.push(format!("{}({})", symbol, npc.hit_points));
+                        f.write_char(symbol)?;
                     }
                 };
             }
@@ -359,34 +658,120 @@ impl fmt::Display for Game {
             if !npcs_per_line.is_empty() {
                 write!(f, "   ")?;
             }
-            writeln!(
-                f,
-                "{}",
-                npcs_per_line
-                    .iter()
-                    .map(|npc| format!("{}", npc))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            )?;
+            writeln!(f, "{}", npcs_per_line.join(", "))?;
         }
         Ok(())
     }
 }
 
-pub fn solve(input: &str) -> (usize, usize) {
-    let mut game = Game::from(input);
-    let part_1 = game.run();
+/// Whether the elves fight a single race without friendly fire, so that the enemies only take
+/// the hits of the elves and the elves only the hits of the enemies.
+fn only_elves_hit(game: &Game) -> bool {
+    let races = &game.rules.races;
+    game.rules.race('E').is_some()
+        && races.len() == 2
+        && races.iter().all(|race| !race.friendly_fire)
+}
+
+/// The attack powers of the elves from 4 up to the one killing the enemies with a single hit,
+/// leaving out the ones which kill them with as many hits as a lower one if only elves hit them.
+///
+/// Then such powers give the same combat: the same enemies die in the same rounds, and the
+/// enemies are ordered by their hit points the same way.
+fn elven_powers(game: &Game) -> Vec<i32> {
+    let elves = game.rules.race('E');
+    let hit_points = game
+        .rules
+        .races
+        .iter()
+        .enumerate()
+        .filter(|&(race, _)| Some(race) != elves)
+        .map(|(_, race)| race.hit_points.max(1))
+        .max()
+        .unwrap_or(1);
+    let only_elves_hit = only_elves_hit(game);
+
+    let hits = |power: i32| (hit_points + power - 1) / power;
+    let max = hit_points.max(4);
+    (4..=max)
+        .filter(|&power| !only_elves_hit || power == 4 || hits(power) != hits(power - 1))
+        .collect()
+}
 
-    let mut min_elven_power = 4;
-    let min_power_score = loop {
-        let mut game = Game::from(input);
-        if let Some(score) = game.run_until_elf_dies(min_elven_power) {
-            break score;
+/// The outcomes of the combats with each of the attack `powers` of the elves, in parallel, or
+/// `None` if an elf dies.
+fn simulate(game: &Game, powers: &[i32]) -> Vec<Option<usize>> {
+    thread::scope(|scope| {
+        let runs: Vec<_> = powers
+            .iter()
+            .map(|&power| scope.spawn(move || game.clone().run_until_elf_dies(power)))
+            .collect();
+        runs.into_iter()
+            .map(|run| run.join().expect("simulation panicked"))
+            .collect()
+    })
+}
+
+/// The lowest attack power of the elves at which none of them dies, with the outcome.
+///
+/// If only elves hit the enemies, this is a binary search of the powers which simulates `threads`
+/// of them at a time, splitting the remaining ones evenly. It assumes that more power doesn't
+/// make it worse for the elves, which is usual but isn't guaranteed: the power
+/// found and the one below are simulated, but a lower power which works after some that don't is
+/// missed. Otherwise, e.g. with friendly fire or more races, all powers are simulated in order,
+/// `threads` at a time, so the result is the lowest working power without any assumption.
+pub fn min_elven_power(game: &Game, threads: usize) -> Option<(i32, usize)> {
+    let powers = elven_powers(game);
+    let threads = threads.max(1);
+    if !only_elves_hit(game) {
+        // friendly fire can make more power worse, e.g. when elves kill each other sooner
+        return powers.chunks(threads).find_map(|chunk| {
+            let outcomes = simulate(game, chunk);
+            let i = outcomes.iter().position(Option::is_some)?;
+            Some((chunk[i], outcomes[i].unwrap()))
+        });
+    }
+    let (mut lo, mut hi) = (0, powers.len());
+    let mut found = None;
+    while lo < hi {
+        // up to `threads` probes splitting lo..hi into equal parts, all powers if they are few
+        let count = threads.min(hi - lo);
+        let probes: Vec<usize> = (1..=count)
+            .map(|i| lo + (hi - lo) * i / (count + 1))
+            .collect();
+        let probed: Vec<i32> = probes.iter().map(|&idx| powers[idx]).collect();
+        let outcomes = simulate(game, &probed);
+        match outcomes.iter().position(Option::is_some) {
+            Some(i) => {
+                found = Some((powers[probes[i]], outcomes[i].unwrap()));
+                lo = if i == 0 { lo } else { probes[i - 1] + 1 };
+                hi = probes[i];
+            }
+            None => lo = probes[count - 1] + 1,
         }
-        min_elven_power += 1;
-    };
+    }
+    found
+}
+
+pub struct Day15;
+
+impl Solution for Day15 {
+    type Input = Game;
+    type Part1 = usize;
+    type Part2 = Option<usize>;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        Game::parse(input)
+    }
+
+    fn part1(game: &Self::Input) -> usize {
+        game.clone().run()
+    }
 
-    (part_1, min_power_score)
+    fn part2(game: &Self::Input) -> Option<usize> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        min_elven_power(game, threads).map(|(_, outcome)| outcome)
+    }
 }
 
 #[cfg(test)]
@@ -401,7 +786,7 @@ mod tests {
 
     #[test]
     fn test_parse() {
-        let game = Game::from(INPUT);
+        let game = Game::parse(INPUT).unwrap();
         const EXPECTED: &str = r#"#######
 #.G.E.#   G(200), E(200)
 #E.G.E#   E(200), G(200), E(200)
@@ -411,6 +796,303 @@ mod tests {
         assert_eq!(format!("{}", game), EXPECTED);
     }
 
+    const COMBAT: &str = r#"#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######"#;
+
+    fn recorded(input: &str) -> Game {
+        let mut game = Game::parse(input).unwrap();
+        game.record();
+        game.run();
+        game
+    }
+
+    #[test]
+    fn test_log() {
+        let game = recorded(COMBAT);
+        let events = game.events();
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| matches!(e, Event::RoundEnded(_)))
+                .count(),
+            47
+        );
+        assert_eq!(
+            events[0],
+            Event::Moved {
+                npc: 0,
+                from: (2, 1),
+                to: (3, 1)
+            }
+        );
+        assert!(events.contains(&Event::Died { npc: 1 }));
+        assert!(events.contains(&Event::Died { npc: 5 }));
+
+        let text: String = events.iter().map(|e| format!("{}\n", e)).collect();
+        assert!(text.starts_with("moved 0 2,1 3,1\n"));
+        assert_eq!(parse_log(&text).unwrap(), events);
+        assert!(parse_log("moved 0 2,1\n").is_err());
+        assert!(parse_log("fled 0\n").is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let game = recorded(COMBAT);
+        let mut replay = Game::parse(COMBAT).unwrap();
+        replay.record();
+        for event in game.events() {
+            replay.apply(event).unwrap();
+        }
+        assert_eq!(replay.to_string(), game.to_string());
+        assert_eq!(replay.outcome(), 27730);
+        assert_eq!(diff(game.events(), replay.events()), None);
+
+        let mut replay = Game::parse(COMBAT).unwrap();
+        let bad = Event::Moved {
+            npc: 0,
+            from: (2, 1),
+            to: (4, 1),
+        };
+        assert!(replay.apply(&bad).is_err());
+        assert!(replay.apply(&Event::RoundEnded(2)).is_err());
+        assert!(replay.apply(&Event::Died { npc: 0 }).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let expected = recorded(COMBAT).events().to_vec();
+        let mut actual = expected.clone();
+        let idx = expected
+            .iter()
+            .position(|e| *e == Event::RoundEnded(2))
+            .unwrap()
+            + 1;
+        if let Event::Attacked { hit_points, .. } = &mut actual[idx] {
+            *hit_points += 1;
+        } else if let Event::Moved { to, .. } = &mut actual[idx] {
+            to.0 += 1;
+        }
+        let report = diff(&expected, &actual).unwrap();
+        assert!(
+            report.starts_with(&format!("round 3, event {}: expected ", idx + 1)),
+            "{}",
+            report
+        );
+        assert_eq!(
+            diff(&expected, &expected[..3]),
+            Some(format!(
+                "round 1, event 4: expected {}, got the end",
+                expected[3]
+            ))
+        );
+    }
+
+    fn first_round(input: &str, rules: &str) -> Vec<Event> {
+        let mut game = Game::with_rules(input, rules.parse().unwrap()).unwrap();
+        game.record();
+        game.round();
+        game.events().to_vec()
+    }
+
+    #[test]
+    fn test_rules() {
+        let puzzle = "race E 200 3\nrace G 200 3\n";
+        assert_eq!(puzzle.parse(), Ok(Rules::default()));
+
+        let rules: Rules =
+            "# variant\nrace O 300 5 friendly-fire\nrace G 1 2\n\ndiagonal\ntarget reading-order"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            rules.races[0],
+            Race {
+                symbol: 'O',
+                hit_points: 300,
+                attack_power: 5,
+                friendly_fire: true
+            }
+        );
+        assert!(rules.diagonal);
+        assert_eq!(rules.target_order, TargetOrder::ReadingOrder);
+        assert_eq!(rules.race('G'), Some(1));
+
+        let error = |input: &str| input.parse::<Rules>().unwrap_err().to_string();
+        assert_eq!(
+            error("race E 200\nrace G 200 3"),
+            "line 1 col 11: expected a number, found end of line"
+        );
+        assert_eq!(
+            error("race E 1 1\nrace E 1 1"),
+            "line 2 col 6: invalid race symbol"
+        );
+        assert_eq!(
+            error("race E 1 1\nrace G 1 1 friends"),
+            "line 2 col 12: expected \"friendly-fire\", found \"friends\""
+        );
+        assert_eq!(
+            error("race E 1 1\nrace G 1 1\ntarget nearest"),
+            "line 3 col 8: unknown order \"nearest\""
+        );
+        assert_eq!(
+            error("race E 1 1"),
+            "line 2 col 1: expected at least two races, found end of input"
+        );
+
+        assert!(Game::with_rules("#EO#", rules.clone()).is_err());
+    }
+
+    #[test]
+    fn test_diagonal() {
+        const INPUT: &str = "#####\n#E#.#\n##..#\n#..G#\n#####";
+        let puzzle = "race E 200 3\nrace G 200 3";
+        assert_eq!(first_round(INPUT, puzzle), vec![Event::RoundEnded(1)]);
+        let events = first_round(INPUT, &format!("{}\ndiagonal", puzzle));
+        assert_eq!(
+            events[0],
+            Event::Moved {
+                npc: 0,
+                from: (1, 1),
+                to: (2, 2)
+            }
+        );
+    }
+
+    #[test]
+    fn test_races() {
+        const INPUT: &str = "#####\n#EGO#\n#####";
+        let races = "race E 200 3\nrace G 200 3\nrace O 300 4\n";
+        let attacked = |npc, target, hit_points| Event::Attacked {
+            npc,
+            target,
+            hit_points,
+        };
+        let expected = |target, hit_points| {
+            vec![
+                attacked(0, 1, 197),
+                attacked(1, target, hit_points),
+                attacked(2, 1, 193),
+                Event::RoundEnded(1),
+            ]
+        };
+        assert_eq!(first_round(INPUT, races), expected(0, 197));
+        let strongest = format!("{}target most-hit-points", races);
+        assert_eq!(first_round(INPUT, &strongest), expected(2, 297));
+        let reading_order = format!("{}target reading-order", races);
+        assert_eq!(first_round(INPUT, &reading_order), expected(0, 197));
+
+        let mut game = Game::with_rules(INPUT, races.parse().unwrap()).unwrap();
+        assert_eq!(game.run(), 58 * 213);
+        assert_eq!(game.to_string(), "#####\n#..O#   O(213)\n#####\n");
+    }
+
+    #[test]
+    fn test_friendly_fire() {
+        const INPUT: &str = "####\n#EG#\n#.E#\n####";
+        let rules = "race E 200 3 friendly-fire\nrace G 200 3";
+        let attacked = |npc, target, hit_points| Event::Attacked {
+            npc,
+            target,
+            hit_points,
+        };
+        let events = first_round(INPUT, rules);
+        assert_eq!(
+            events,
+            vec![
+                attacked(0, 1, 197),
+                attacked(0, 2, 197),
+                attacked(1, 2, 194),
+                attacked(2, 1, 194),
+                attacked(2, 0, 197),
+                Event::RoundEnded(1),
+            ]
+        );
+
+        let mut replay = Game::with_rules(INPUT, rules.parse().unwrap()).unwrap();
+        for event in &events {
+            replay.apply(event).unwrap();
+        }
+        let mut no_friendly_fire = Game::parse(INPUT).unwrap();
+        assert!(no_friendly_fire.apply(&events[0]).is_ok());
+        assert!(no_friendly_fire.apply(&events[1]).is_err());
+    }
+
+    #[test]
+    fn test_elven_powers() {
+        let game = Game::parse(COMBAT).unwrap();
+        let powers = elven_powers(&game);
+        assert_eq!(&powers[..3], &[4, 5, 6]);
+        assert_eq!(&powers[powers.len() - 4..], &[50, 67, 100, 200]);
+        // 17 and 18 both need 12 hits
+        assert!(powers.contains(&17) && !powers.contains(&18));
+
+        let rules = "race E 200 3\nrace G 200 3\nrace O 10 3".parse().unwrap();
+        let game = Game::with_rules(COMBAT, rules).unwrap();
+        assert_eq!(elven_powers(&game), (4..=200).collect::<Vec<_>>());
+
+        // the goblins hit each other as well, so 21 and 22 differ though both need 10 hits
+        let rules = "race E 200 3\nrace G 200 3 friendly-fire".parse().unwrap();
+        let game = Game::with_rules(COMBAT, rules).unwrap();
+        assert_eq!(elven_powers(&game), (4..=200).collect::<Vec<_>>());
+        assert_eq!(game.clone().run_until_elf_dies(21), Some(4662));
+        assert_eq!(game.clone().run_until_elf_dies(22), Some(4454));
+    }
+
+    #[test]
+    fn test_min_elven_power() {
+        let examples = [
+            (COMBAT, 15, 4988),
+            ("#######\n#E..EG#\n#.#G.E#\n#E.##E#\n#G..#.#\n#..E#.#\n#######", 4, 31284),
+            ("#######\n#E.G#.#\n#.#G..#\n#G.#.G#\n#G..#.#\n#...E.#\n#######", 15, 3478),
+            ("#######\n#.E...#\n#.#..G#\n#.###.#\n#E#G#G#\n#...#G#\n#######", 12, 6474),
+            (
+                "#########\n#G......#\n#.E.#...#\n#..##..G#\n#...##..#\n#...#...#\n#.G...G.#\n#.....G.#\n#########",
+                34,
+                1140,
+            ),
+        ];
+        for &(input, power, outcome) in &examples {
+            let game = Game::parse(input).unwrap();
+            for &threads in &[1, 3, 8] {
+                assert_eq!(
+                    min_elven_power(&game, threads),
+                    Some((power, outcome)),
+                    "{}",
+                    input
+                );
+            }
+        }
+        // the goblin kills the elf before it can attack
+        let rules = "race E 1 3\nrace G 200 3".parse().unwrap();
+        let hopeless = Game::with_rules("#GE#", rules).unwrap();
+        assert_eq!(min_elven_power(&hopeless, 2), None);
+
+        // elves hitting each other win with 20 but not with 21 or 22
+        let rules: Rules = "race E 200 3 friendly-fire\nrace G 200 3".parse().unwrap();
+        let input = "#######\n#E.G#.#\n#.#G..#\n#G.#.G#\n#G..#.#\n#...E.#\n#######";
+        let game = Game::with_rules(input, rules).unwrap();
+        assert_eq!(game.clone().run_until_elf_dies(21), None);
+        for &threads in &[1, 3, 8] {
+            assert_eq!(min_elven_power(&game, threads), Some((20, 2268)));
+        }
+    }
+
+    #[test]
+    fn test_stop_at_elf_death() {
+        let mut game = Game::parse(COMBAT).unwrap();
+        game.record();
+        assert_eq!(game.run_until_elf_dies(3), None);
+        let elves = game.rules.race('E');
+        match game.events().last() {
+            Some(&Event::Died { npc }) => assert_eq!(Some(game.npcs[npc].race), elves),
+            last => panic!("last event {:?}", last),
+        }
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -426,7 +1108,7 @@ mod tests {
 #######"#;
 
             // initial
-            let mut game = Game::from(INPUT);
+            let mut game = Game::parse(INPUT).unwrap();
             println!("{}", format!("{}", game));
             assert_eq!(
                 format!("{}", game),
@@ -582,7 +1264,7 @@ mod tests {
 
         #[test]
         fn test_run1() {
-            let mut game = Game::from(
+            let mut game = Game::parse(
                 r#"#######
 #.G...#
 #...EG#
@@ -590,7 +1272,8 @@ mod tests {
 #..G#E#
 #.....#
 #######"#,
-            );
+            )
+            .unwrap();
             let score = game.run();
             println!("{}", game);
             assert_eq!(
@@ -609,7 +1292,7 @@ mod tests {
 
         #[test]
         fn test_run2() {
-            let mut game = Game::from(
+            let mut game = Game::parse(
                 r#"#######
 #G..#E#
 #E#E.E#
@@ -617,7 +1300,8 @@ mod tests {
 #...#E#
 #...E.#
 #######"#,
-            );
+            )
+            .unwrap();
             let score = game.run();
             println!("{}", game);
             assert_eq!(
@@ -636,7 +1320,7 @@ mod tests {
 
         #[test]
         fn test_run3() {
-            let mut game = Game::from(
+            let mut game = Game::parse(
                 r#"#######
 #E..EG#
 #.#G.E#
@@ -644,7 +1328,8 @@ mod tests {
 #G..#.#
 #..E#.#
 #######"#,
-            );
+            )
+            .unwrap();
             let score = game.run();
             println!("{}", game);
             assert_eq!(
@@ -663,7 +1348,7 @@ mod tests {
 
         #[test]
         fn test_run4() {
-            let mut game = Game::from(
+            let mut game = Game::parse(
                 r#"#######
 #E.G#.#
 #.#G..#
@@ -671,7 +1356,8 @@ mod tests {
 #G..#.#
 #...E.#
 #######"#,
-            );
+            )
+            .unwrap();
             let score = game.run();
             println!("{}", game);
             assert_eq!(
@@ -690,7 +1376,7 @@ mod tests {
 
         #[test]
         fn test_run5() {
-            let mut game = Game::from(
+            let mut game = Game::parse(
                 r#"#######
 #.E...#
 #.#..G#
@@ -698,7 +1384,8 @@ mod tests {
 #E#G#G#
 #...#G#
 #######"#,
-            );
+            )
+            .unwrap();
             let score = game.run();
             println!("{}", game);
             assert_eq!(
@@ -717,7 +1404,7 @@ mod tests {
 
         #[test]
         fn test_run6() {
-            let mut game = Game::from(
+            let mut game = Game::parse(
                 r#"#########
 #G......#
 #.E.#...#
@@ -727,7 +1414,8 @@ mod tests {
 #.G...G.#
 #.....G.#
 #########"#,
-            );
+            )
+            .unwrap();
             let score = game.run();
             println!("{}", game);
             assert_eq!(
@@ -753,7 +1441,7 @@ mod tests {
 #.....#
 #...G.#
 #######"#;
-            let mut game = Game::from(INPUT);
+            let mut game = Game::parse(INPUT).unwrap();
             println!("{}", format!("{}", game));
             assert_eq!(
                 format!("{}", game),
@@ -803,7 +1491,7 @@ mod tests {
 
         #[test]
         fn test_move2() {
-            let mut game = Game::from(
+            let mut game = Game::parse(
                 r#"#########
 #G..G..G#
 #.......#
@@ -813,7 +1501,8 @@ mod tests {
 #.......#
 #G..G..G#
 #########"#,
-            );
+            )
+            .unwrap();
 
             game.round();
             println!("{}", game);
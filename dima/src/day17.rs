@@ -1,12 +1,13 @@
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
+use common::grid::{Grid, Pos};
 use itertools::Itertools;
-use text_io::{scan, try_scan};
 
 use std::fmt::{self, Write};
 use std::mem;
-use std::ops::{Index, IndexMut};
 
 #[derive(Clone, Copy, PartialEq, Eq)]
-enum Tile {
+pub enum Tile {
     Sand,
     Clay,
     Water,
@@ -14,75 +15,74 @@ enum Tile {
     Faucet,
 }
 
-impl Into<char> for Tile {
-    fn into(self) -> char {
-        match self {
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char(match self {
             Tile::Sand => '.',
             Tile::Clay => '#',
             Tile::Water => '~',
             Tile::Faucet => '+',
             Tile::Flow => '|',
-        }
+        })
     }
 }
 
-struct Ground {
-    tiles: Vec<Tile>,
-    width: usize,
-    height: usize,
-    cursors: Vec<(usize, usize)>,
+#[derive(Clone)]
+pub struct Ground {
+    tiles: Grid<Tile>,
+    cursors: Vec<Pos>,
 }
 
 impl Ground {
     fn flow(&mut self) -> bool {
-        let mut stack: Vec<(usize, usize)> = Vec::new();
+        let mut stack: Vec<Pos> = Vec::new();
         mem::swap(&mut self.cursors, &mut stack);
 
         while let Some((x, y)) = stack.pop() {
-            if y + 1 >= self.height {
+            if y + 1 >= self.tiles.height() {
                 continue;
             }
 
-            let tile = self[(x, y)];
+            let tile = self.tiles[(x, y)];
             if tile != Tile::Flow && tile != Tile::Faucet {
                 continue;
             }
 
-            match self[(x, y + 1)] {
+            match self.tiles[(x, y + 1)] {
                 Tile::Sand => {
-                    self[(x, y + 1)] = Tile::Flow;
+                    self.tiles[(x, y + 1)] = Tile::Flow;
                     self.cursors.push((x, y + 1));
                 }
                 Tile::Clay | Tile::Water => {
-                    if self[(x - 1, y)] == Tile::Sand {
-                        self[(x - 1, y)] = Tile::Flow;
+                    if self.tiles[(x - 1, y)] == Tile::Sand {
+                        self.tiles[(x - 1, y)] = Tile::Flow;
                         self.cursors.push((x - 1, y));
                     }
 
-                    if self[(x + 1, y)] == Tile::Sand {
-                        self[(x + 1, y)] = Tile::Flow;
+                    if self.tiles[(x + 1, y)] == Tile::Sand {
+                        self.tiles[(x + 1, y)] = Tile::Flow;
                         self.cursors.push((x + 1, y));
                     }
 
-                    if self[(x - 1, y)] == Tile::Clay
-                        || self[(x - 1, y)] == Tile::Flow
-                        || self[(x + 1, y)] == Tile::Clay
-                        || self[(x + 1, y)] == Tile::Flow
+                    if self.tiles[(x - 1, y)] == Tile::Clay
+                        || self.tiles[(x - 1, y)] == Tile::Flow
+                        || self.tiles[(x + 1, y)] == Tile::Clay
+                        || self.tiles[(x + 1, y)] == Tile::Flow
                     {
                         let dneg = (0..x)
                             .rev()
-                            .take_while(|&x| self[(x, y)] == Tile::Flow)
+                            .take_while(|&x| self.tiles[(x, y)] == Tile::Flow)
                             .count();
-                        let dpos = (x + 1..self.width)
-                            .take_while(|&x| self[(x, y)] == Tile::Flow)
+                        let dpos = (x + 1..self.tiles.width())
+                            .take_while(|&x| self.tiles[(x, y)] == Tile::Flow)
                             .count();
                         if x >= dneg + 1
-                            && self[(x - dneg - 1, y)] == Tile::Clay
-                            && self[(x + dpos + 1, y)] == Tile::Clay
+                            && self.tiles[(x - dneg - 1, y)] == Tile::Clay
+                            && self.tiles.get((x + dpos + 1, y)) == Some(&Tile::Clay)
                         {
                             for x in x - dneg..=x + dpos {
-                                self[(x, y)] = Tile::Water;
-                                if self[(x, y - 1)] == Tile::Flow {
+                                self.tiles[(x, y)] = Tile::Water;
+                                if self.tiles[(x, y - 1)] == Tile::Flow {
                                     self.cursors.push((x, y - 1));
                                 }
                             }
@@ -96,49 +96,41 @@ impl Ground {
     }
 }
 
-impl Index<(usize, usize)> for Ground {
-    type Output = Tile;
-    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
-        &self.tiles[x + y * self.width]
+impl fmt::Display for Ground {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.tiles)
     }
 }
 
-impl IndexMut<(usize, usize)> for Ground {
-    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
-        &mut self.tiles[x + y * self.width]
+/// Parses a vein of clay like `x=495, y=2..7` into its tiles.
+fn parse_vein(line: &mut Cursor) -> Result<Vec<(usize, usize)>, ParseError> {
+    let (col, a) = line.word()?;
+    if a != "x" && a != "y" {
+        return Err(ParseError::unknown(line.line(), col, "axis", a));
     }
+    line.tag("=")?;
+    let a_value = line.number()?;
+    line.tag(",")?;
+    line.tag(if a == "x" { "y=" } else { "x=" })?;
+    let b_from = line.number()?;
+    line.tag("..")?;
+    let b_to = line.number()?;
+    line.end()?;
+    Ok(if a == "x" {
+        (b_from..=b_to).map(|y| (a_value, y)).collect()
+    } else {
+        (b_from..=b_to).map(|x| (x, a_value)).collect()
+    })
 }
 
-impl fmt::Display for Ground {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                f.write_char(self[(x, y)].into())?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+fn parse(input: &str) -> Result<Ground, ParseError> {
+    let mut clay_tiles = Vec::new();
+    for mut line in parse::lines(input) {
+        clay_tiles.extend(parse_vein(&mut line)?);
+    }
+    if clay_tiles.is_empty() {
+        return Err(ParseError::eof(0, "a vein of clay"));
     }
-}
-
-fn parse(input: &str) -> Ground {
-    let clay_tiles: Vec<_> = input
-        .lines()
-        .flat_map(|line| {
-            let a: char;
-            let _b: char;
-            let a_value: usize;
-            let b_from: usize;
-            let b_to: usize;
-            scan!(line.bytes() => "{}={}, {}={}..{}", a, a_value, _b, b_from, b_to);
-            let tiles: Vec<_> = match a {
-                'x' => (b_from..=b_to).map(|y| (a_value, y)).collect(),
-                'y' => (b_from..=b_to).map(|x| (x, a_value)).collect(),
-                _ => panic!(),
-            };
-            tiles
-        })
-        .collect();
 
     let (mut offset_x, max_x) = clay_tiles
         .iter()
@@ -157,35 +149,47 @@ fn parse(input: &str) -> Ground {
 
     let width = max_x + 2 - offset_x;
     let height = max_y + 1 - offset_y;
-    let tiles = vec![Tile::Sand; width * height];
     let mut ground = Ground {
-        width,
-        height,
-        tiles,
+        tiles: Grid::new(width, height, Tile::Sand),
         cursors: Vec::new(),
     };
 
     for clay_pos in clay_tiles {
-        ground[(clay_pos.0 - offset_x, clay_pos.1 - offset_y)] = Tile::Clay;
+        ground.tiles[(clay_pos.0 - offset_x, clay_pos.1 - offset_y)] = Tile::Clay;
     }
     // add sprinkler
-    ground[(500 - offset_x, 0)] = Tile::Faucet;
+    ground.tiles[(500 - offset_x, 0)] = Tile::Faucet;
     ground.cursors.push((500 - offset_x, 0));
 
-    ground
+    Ok(ground)
 }
 
-pub fn solve(input: &str) -> (usize, usize) {
-    let mut ground = parse(input);
-    while ground.flow() {}
-    println!("{}", ground);
-    let part1 = ground
-        .tiles
-        .iter()
-        .filter(|&&t| t == Tile::Water || t == Tile::Flow)
-        .count();
-    let part2 = ground.tiles.iter().filter(|&&t| t == Tile::Water).count();
-    (part1, part2)
+pub struct Day17;
+
+impl Solution for Day17 {
+    type Input = Ground;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(ground: &Self::Input) -> usize {
+        let mut ground = ground.clone();
+        while ground.flow() {}
+        ground
+            .tiles
+            .cells()
+            .filter(|&&t| t == Tile::Water || t == Tile::Flow)
+            .count()
+    }
+
+    fn part2(ground: &Self::Input) -> usize {
+        let mut ground = ground.clone();
+        while ground.flow() {}
+        ground.tiles.cells().filter(|&&t| t == Tile::Water).count()
+    }
 }
 
 #[cfg(test)]
@@ -203,7 +207,7 @@ x=498, y=10..13
 x=504, y=10..13
 y=13, x=498..504"#;
 
-        let s = format!("{}", parse(INPUT));
+        let s = format!("{}", parse(INPUT).unwrap());
         println!("{}", s);
         assert_eq!(
             s,
@@ -1,5 +1,7 @@
+use crate::parse::ParseError;
+use crate::solution::Solution;
+use common::grid::Grid;
 use std::fmt::{self, Write};
-use std::mem;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum Acre {
@@ -8,61 +10,37 @@ enum Acre {
     Lumberyard,
 }
 
-impl From<char> for Acre {
-    fn from(c: char) -> Acre {
+impl Acre {
+    fn from_char(c: char) -> Option<Acre> {
         match c {
-            '.' => Acre::Open,
-            '|' => Acre::Trees,
-            '#' => Acre::Lumberyard,
-            otherwise => panic!("unknown char: '{}'", otherwise),
+            '.' => Some(Acre::Open),
+            '|' => Some(Acre::Trees),
+            '#' => Some(Acre::Lumberyard),
+            _ => None,
         }
     }
 }
 
-impl Into<char> for Acre {
-    fn into(self) -> char {
-        match self {
+impl fmt::Display for Acre {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char(match self {
             Acre::Open => '.',
             Acre::Trees => '|',
             Acre::Lumberyard => '#',
-        }
+        })
     }
 }
 
 #[derive(Clone, PartialEq, Eq)]
-struct Area(Vec<Acre>);
+pub struct Area(Grid<Acre>);
 
 impl Area {
-    fn dimension(&self) -> usize {
-        (self.0.len() as f64).sqrt() as usize
-    }
-
     fn next(&mut self) -> bool {
-        let d = self.dimension() as isize;
-        let mut next_area: Vec<_> = self
-            .0
+        let area = &self.0;
+        let next_area = area
             .iter()
-            .enumerate()
-            .map(|(idx, &acre)| {
-                let i = idx as isize / d;
-                let j = idx as isize % d;
-                let adj_positions = [
-                    (i - 1, j - 1),
-                    (i, j - 1),
-                    (i + 1, j - 1),
-                    (i - 1, j),
-                    (i + 1, j),
-                    (i - 1, j + 1),
-                    (i, j + 1),
-                    (i + 1, j + 1),
-                ];
-                let adj = adj_positions.into_iter().filter_map(|&(i, j)| {
-                    if 0 <= i && i < d && 0 <= j && j < d {
-                        Some(self.0[(i * d + j) as usize])
-                    } else {
-                        None
-                    }
-                });
+            .map(|(pos, &acre)| {
+                let adj = area.neighbors8(pos).map(|pos| area[pos]);
                 let (num_trees, num_lumberyards) =
                     adj.fold((0, 0), |(num_trees, num_lumberyards), acre| match acre {
                         Acre::Trees => (num_trees + 1, num_lumberyards),
@@ -94,39 +72,34 @@ impl Area {
                 }
             })
             .collect();
+        let next_area = Grid::from_vec(area.width(), next_area);
         let is_changed = next_area != self.0;
-        mem::swap(&mut next_area, &mut self.0);
+        self.0 = next_area;
 
         is_changed
     }
 
     fn resource_value(&self) -> usize {
-        let num_trees = self.0.iter().filter(|&&a| a == Acre::Trees).count();
-        let num_lumberyards = self.0.iter().filter(|&&a| a == Acre::Lumberyard).count();
+        let num_trees = self.0.cells().filter(|&&a| a == Acre::Trees).count();
+        let num_lumberyards = self.0.cells().filter(|&&a| a == Acre::Lumberyard).count();
         num_trees * num_lumberyards
     }
 }
 
 impl fmt::Display for Area {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let d = self.dimension();
-        for (i, &acre) in self.0.iter().enumerate() {
-            f.write_char(acre.into())?;
-            if i % d == d - 1 && i + 1 != self.0.len() {
-                f.write_char('\n')?;
-            }
-        }
-        Ok(())
+        write!(f, "{}", self.0)
     }
 }
 
-fn parse(input: &str) -> Area {
-    let acres = input
-        .chars()
-        .filter(|&c| c != '\n')
-        .map(Acre::from)
-        .collect();
-    Area(acres)
+fn parse(input: &str) -> Result<Area, ParseError> {
+    let area = Grid::parse_with(input, Acre::from_char)?;
+    let (width, height) = (area.width(), area.height());
+    if width != height {
+        let message = format!("area must be square, found {}x{}", width, height);
+        return Err(ParseError::invalid(width.min(height) + 1, 1, message));
+    }
+    Ok(Area(area))
 }
 
 fn part1(mut area: Area) -> usize {
@@ -157,9 +130,24 @@ fn part2(mut area: Area) -> usize {
     prev_generations[end_pos].resource_value()
 }
 
-pub fn solve(input: &str) -> (usize, usize) {
-    let area = parse(input);
-    (part1(area.clone()), part2(area))
+pub struct Day18;
+
+impl Solution for Day18 {
+    type Input = Area;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(area: &Self::Input) -> usize {
+        part1(area.clone())
+    }
+
+    fn part2(area: &Self::Input) -> usize {
+        part2(area.clone())
+    }
 }
 
 #[cfg(test)]
@@ -179,16 +167,16 @@ mod tests {
 
     #[test]
     fn test_parse() {
-        let a = parse(INPUT);
+        let a = parse(INPUT).unwrap();
         let s = format!("{}", a);
         println!("{}", s);
-        assert_eq!(a.dimension(), 10);
-        assert_eq!(s, INPUT);
+        assert_eq!(a.0.width(), 10);
+        assert_eq!(s, format!("{}\n", INPUT));
     }
 
     #[test]
     fn test_next() {
-        let mut a = parse(INPUT);
+        let mut a = parse(INPUT).unwrap();
         let s = format!("{}", a);
         println!("{}", s);
 
@@ -208,7 +196,8 @@ mod tests {
 ||##.####|
 ||#####|||
 ||||#|||||
-||||||||||"#
+||||||||||
+"#
         );
     }
 }
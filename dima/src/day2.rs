@@ -1,3 +1,5 @@
+use crate::parse::ParseError;
+use crate::solution::Solution;
 use itertools::iproduct;
 use std::collections::BTreeMap;
 
@@ -46,8 +48,24 @@ fn solve2(input: &str) -> Option<String> {
         })
 }
 
-pub fn solve(input: &str) -> (usize, Option<String>) {
-    (solve1(input), solve2(input))
+pub struct Day2;
+
+impl Solution for Day2 {
+    type Input = String;
+    type Part1 = usize;
+    type Part2 = Option<String>;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        Ok(input.to_string())
+    }
+
+    fn part1(input: &Self::Input) -> usize {
+        solve1(input)
+    }
+
+    fn part2(input: &Self::Input) -> Option<String> {
+        solve2(input)
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,8 @@
+use crate::parse::{ParseError, Reason};
+use crate::solution::Solution;
+use common::search;
 use nom::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 enum Pattern {
@@ -38,6 +41,36 @@ named!(parse<&[u8], Pattern>,
     delimited!(char!('^'), pattern, char!('$'))
 );
 
+/// Runs the nom parser and locates its error in `input`.
+fn parse_pattern(input: &str) -> Result<Pattern, ParseError> {
+    parse(input.as_bytes())
+        .map(|(_, pattern)| pattern)
+        .map_err(|e| {
+            let rest: &[u8] = match e {
+                nom::Err::Incomplete(_) => b"",
+                nom::Err::Error(context) | nom::Err::Failure(context) => match context {
+                    Context::Code(rest, _) => rest,
+                    Context::List(list) => list.first().map_or(&b""[..], |&(rest, _)| rest),
+                },
+            };
+            let offset = input.len() - rest.len();
+            let expected = if offset == 0 {
+                r#""^""#
+            } else {
+                r#"a direction, group or "$""#
+            };
+            let found = match input[offset..].chars().next() {
+                None => "end of input".to_string(),
+                Some(c) => format!("{:?}", c.to_string()),
+            };
+            let reason = Reason::Expected {
+                expected: expected.to_string(),
+                found,
+            };
+            ParseError::at_offset(input, offset, reason)
+        })
+}
+
 impl Pattern {
     fn walk(&self, graph: &mut Graph, origins: Vec<(isize, isize)>) -> Vec<(isize, isize)> {
         match self {
@@ -89,45 +122,32 @@ impl Pattern {
 type Graph = HashMap<(isize, isize), Vec<(isize, isize)>>;
 
 fn shortest_paths(graph: &Graph) -> HashMap<(isize, isize), usize> {
-    type Vertex = (isize, isize);
-    let mut stack: Vec<Vertex> = vec![(0, 0)];
-    let mut visited: HashSet<Vertex> = HashSet::new();
-    let mut dist: HashMap<Vertex, usize> = HashMap::new();
-    dist.insert((0, 0), 0);
-
-    // DFS
-    while let Some(v) = stack.pop() {
-        if let Some(neighbors) = graph.get(&v) {
-            for w in neighbors {
-                let dist_vw = dist[&v] + 1;
-                let dist_w = dist.entry(*w).or_insert(usize::max_value());
-                if dist_vw < *dist_w {
-                    *dist_w = dist_vw
-                }
-                if !visited.contains(&w) {
-                    stack.push(*w);
-                }
-            }
-        }
-        visited.insert(v);
-    }
-    dist
+    search::bfs((0, 0), |v| graph.get(v).into_iter().flatten().cloned()).into_costs()
 }
 
-pub fn solve(input: &str) -> (usize, usize) {
-    let (_, pattern) = parse(input.as_bytes()).expect("parser failed");
-    let mut graph = Graph::new();
-    pattern.walk(&mut graph, vec![(0, 0)]);
-    let dist = shortest_paths(&graph);
-    let max_shortest_path = dist.iter().map(|(_, &d)| d).max().unwrap();
-    let num_rooms_shortest_path_with_at_least_1000_doors = dist
-        .iter()
-        .filter_map(|(_, &d)| if d >= 1000 { Some(d) } else { None })
-        .count();
-    (
-        max_shortest_path,
-        num_rooms_shortest_path_with_at_least_1000_doors,
-    )
+pub struct Day20;
+
+impl Solution for Day20 {
+    type Input = HashMap<(isize, isize), usize>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        let pattern = parse_pattern(input)?;
+        let mut graph = Graph::new();
+        pattern.walk(&mut graph, vec![(0, 0)]);
+        Ok(shortest_paths(&graph))
+    }
+
+    fn part1(dist: &Self::Input) -> usize {
+        dist.iter().map(|(_, &d)| d).max().unwrap()
+    }
+
+    fn part2(dist: &Self::Input) -> usize {
+        dist.iter()
+            .filter_map(|(_, &d)| if d >= 1000 { Some(d) } else { None })
+            .count()
+    }
 }
 
 #[cfg(test)]
@@ -177,4 +197,13 @@ mod tests {
         let max_shortest_path = dist.iter().map(|(_, &d)| d).max().unwrap();
         assert_eq!(max_shortest_path, 18);
     }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            parse_pattern("^EN(W|S)X$").unwrap_err().to_string(),
+            r#"line 1 col 9: expected a direction, group or "$", found "X""#
+        );
+        assert!(parse_pattern("NE$").is_err());
+    }
 }
@@ -1,74 +1,75 @@
-use std::collections::HashSet;
+use crate::day19;
+use crate::parse::ParseError;
+use crate::solution::Solution;
+use elfcode::halting::{halting_values, HaltingError};
 
-// Manual de-assembling
-//
-// 06: f = c | 0x10000
-//     c = 16123384
-// 08: d = f & 0xff
-//     c = d + c
-//     c = c & 0xffffff
-//     c = c * 65899
-//     c = c & 0xffffff
-//     if f < 0x100
-//       goto 28
-//     else
-//       goto 17
-// 17: d = 0
-//     b = d + 1
-//     b = b * 0x100
-//     if f < b
-//       goto 24
-//     else
-//       goto 26
-// 24: d = d + 1
-//     goto 18
-// 26: f = d
-//     goto 08
-// 28: if c == a
-//       end
-//     else
-//       goto 06
+/// Values of register 0 which halt the program, from the fewest to the most instructions run, see
+/// `input/day21.notes` for how it works.
+fn parse(input: &str) -> Result<Vec<i64>, ParseError> {
+    let program = day19::parse(input)?;
+    let e = match halting_values(&program, 6) {
+        Ok(values) => return Ok(values),
+        Err(e) => e,
+    };
+    // the instructions follow the "#ip" line
+    let line = match &e {
+        HaltingError::NoEquality(pc) | HaltingError::Writes(pc) => pc + 2,
+        HaltingError::Checks(pcs) => pcs[1] + 2,
+        _ => 1,
+    };
+    Err(ParseError::invalid(line, 1, e.to_string()))
+}
+
+pub struct Day21;
+
+impl Solution for Day21 {
+    type Input = Vec<i64>;
+    type Part1 = Option<i64>;
+    type Part2 = Option<i64>;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
 
-fn deassembly() -> (Option<usize>, Option<usize>) {
-    let mut min_halt_value = None;
-    let mut last_halt_value = None;
-    let mut halt_values = HashSet::new();
+    fn part1(values: &Self::Input) -> Option<i64> {
+        values.first().cloned()
+    }
 
-    let mut c: u64 = 0;
-    let mut f;
-    loop {
-        f = c | 0x10000; // 06
-        c = 16123384;
-        loop {
-            let d = f & 0xff; // 08
-            c = d + c;
-            c = c & 0xffffff;
-            c = c * 65899;
-            c = c & 0xffffff;
-            if f < 0x100 {
-                if min_halt_value.is_none() {
-                    min_halt_value = Some(c as usize);
-                }
-                // It looks like halt values are coming peridoically,
-                // so we just compute the last value in the period here.
-                if halt_values.insert(c) {
-                    last_halt_value = Some(c as usize);
-                } else {
-                    return (min_halt_value, last_halt_value);
-                }
-                break;
-            } else {
-                for d in 0.. {
-                    if (d + 1) * 0x100 > f {
-                        f = d;
-                        break; // goto 08
-                    }
-                }
-            }
-        }
+    fn part2(values: &Self::Input) -> Option<i64> {
+        values.last().cloned()
     }
 }
 
-pub fn solve(_: &str) -> (Option<usize>, Option<usize>) {
-    deassembly()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("#ip 1\neqrr 2 0 3\naddr 3 1 1\naddi 2 1 0")
+                .unwrap_err()
+                .to_string(),
+            "line 4 col 1: instruction 2 writes register 0"
+        );
+        assert_eq!(
+            parse("#ip 1\nseti 5 0 2\naddr 3 1 1")
+                .unwrap_err()
+                .to_string(),
+            "line 1 col 1: no instruction reads register 0"
+        );
+    }
+
+    #[test]
+    fn test_halting() {
+        // tries 3, then 2, then 1 and halts
+        let values = parse(
+            "#ip 4\nseti 3 0 2\neqrr 2 0 3\naddr 3 4 4\nseti 4 0 4\nseti 99 0 4\n\
+             addi 2 -1 2\ngtri 2 0 3\naddr 3 4 4\nseti 99 0 4\nseti 0 0 4",
+        )
+        .unwrap();
+        assert_eq!(values, vec![3, 2, 1]);
+        assert_eq!(Day21::part1(&values), Some(3));
+        assert_eq!(Day21::part2(&values), Some(1));
+    }
 }
@@ -1,11 +1,11 @@
-use priority_queue::PriorityQueue;
-use revord::RevOrd;
-use std::collections::{btree_map, BTreeMap};
+use crate::parse::{self, ParseError};
+use crate::solution::Solution;
+use common::search;
+use std::collections::BTreeMap;
 use std::usize;
-use text_io::{scan, try_scan};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
-struct Coord {
+pub struct Coord {
     x: usize,
     y: usize,
 }
@@ -16,6 +16,14 @@ impl Coord {
     }
 }
 
+fn abs_diff(a: usize, b: usize) -> usize {
+    if a < b {
+        b - a
+    } else {
+        a - b
+    }
+}
+
 struct Cave {
     target: Coord,
     depth: usize,
@@ -71,38 +79,15 @@ impl Cave {
     }
 
     fn shortest_path_to_target(&mut self) -> usize {
-        let mut dist = BTreeMap::new();
-        let mut pq = PriorityQueue::new();
-
         let root = (Tool::Torch, Coord::new(0, 0));
-        dist.insert(root, 0);
-        pq.push(root, RevOrd(0));
-
-        while let Some((v, v_cost)) = pq.pop() {
-            if v.0 == Tool::Torch && v.1 == self.target {
-                return v_cost.0;
-            }
-
-            if dist[&v] < v_cost.0 {
-                continue; // better cost known
-            }
-
-            for (w, vw_cost) in self.edges(v) {
-                let w_cost = v_cost.0 + vw_cost;
-                match dist.entry(w) {
-                    btree_map::Entry::Vacant(e) => {
-                        pq.push(w, RevOrd(w_cost));
-                        e.insert(w_cost);
-                    }
-                    btree_map::Entry::Occupied(ref mut e) if w_cost < *e.get() => {
-                        pq.change_priority(&w, RevOrd(w_cost));
-                        e.insert(w_cost);
-                    }
-                    _ => (),
-                }
-            }
-        }
-        usize::MAX // unreachable
+        let goal = (Tool::Torch, self.target);
+        // Every step takes at least a minute, and switching to the torch seven.
+        let heuristic = |&(tool, pos): &(Tool, Coord)| {
+            let switch_cost = if tool == Tool::Torch { 0 } else { 7 };
+            abs_diff(pos.x, goal.1.x) + abs_diff(pos.y, goal.1.y) + switch_cost
+        };
+        search::astar(root, |&v| self.edges(v), heuristic, |&v| v == goal)
+            .map_or(usize::MAX, |(_, cost)| cost) // unreachable
     }
 
     // All edges from tool + pos with their corresponding costs.
@@ -187,18 +172,35 @@ enum Tool {
     ClimbingGear,
 }
 
-pub fn solve(input: &str) -> (usize, usize) {
-    let mut lines = input.lines();
-    let depth;
-    let target_x;
-    let target_y;
-    scan!(lines.next().unwrap().bytes() => "depth: {}", depth);
-    scan!(lines.next().unwrap().bytes() => "target: {},{}", target_x, target_y);
-    let mut cave = Cave::new(Coord::new(target_x, target_y), depth);
-
-    let risk = cave.risk_level();
-    let cost = cave.shortest_path_to_target();
-    (risk, cost)
+pub struct Day22;
+
+impl Solution for Day22 {
+    type Input = (Coord, usize);
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        let mut lines = parse::lines(input);
+        let mut line = lines.next().ok_or_else(|| ParseError::eof(0, "depth"))?;
+        line.tag("depth:")?;
+        let depth = line.number()?;
+        line.end()?;
+        let mut line = lines.next().ok_or_else(|| ParseError::eof(1, "target"))?;
+        line.tag("target:")?;
+        let target_x = line.number()?;
+        line.tag(",")?;
+        let target_y = line.number()?;
+        line.end()?;
+        Ok((Coord::new(target_x, target_y), depth))
+    }
+
+    fn part1(&(target, depth): &Self::Input) -> usize {
+        Cave::new(target, depth).risk_level()
+    }
+
+    fn part2(&(target, depth): &Self::Input) -> usize {
+        Cave::new(target, depth).shortest_path_to_target()
+    }
 }
 
 #[cfg(test)]
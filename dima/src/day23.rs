@@ -1,10 +1,11 @@
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::i64;
-use text_io::{scan, try_scan};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Coord(i64, i64, i64);
+pub struct Coord(i64, i64, i64);
 
 impl Coord {
     fn dist(&self, other: &Self) -> i64 {
@@ -32,17 +33,24 @@ impl Coord {
     }
 }
 
-fn parse(input: &str) -> Vec<(Coord, i64)> {
-    input
-        .lines()
-        .map(|l| {
-            let x: i64;
-            let y: i64;
-            let z: i64;
-            let r: i64;
-            scan!(l.bytes() => "pos=<{},{},{}>, r={}", x, y, z, r);
-            (Coord(x, y, z), r)
-        })
+/// Parses a nanobot like `pos=<0,0,0>, r=4`.
+fn parse_nanobot(line: &mut Cursor) -> Result<(Coord, i64), ParseError> {
+    line.tag("pos=<")?;
+    let x = line.number()?;
+    line.tag(",")?;
+    let y = line.number()?;
+    line.tag(",")?;
+    let z = line.number()?;
+    line.tag(">,")?;
+    line.tag("r=")?;
+    let r = line.number()?;
+    line.end()?;
+    Ok((Coord(x, y, z), r))
+}
+
+fn parse(input: &str) -> Result<Vec<(Coord, i64)>, ParseError> {
+    parse::lines(input)
+        .map(|mut line| parse_nanobot(&mut line))
         .collect()
 }
 
@@ -167,13 +175,24 @@ fn best_pos_dist(nanobots: &[(Coord, i64)]) -> i64 {
     0
 }
 
-pub fn solve(input: &str) -> (usize, i64) {
-    let nanobots = parse(input);
-    // assert_eq!(85761543, p2);
-    (
-        num_in_range_of_strongest(&nanobots),
-        best_pos_dist(&nanobots),
-    )
+pub struct Day23;
+
+impl Solution for Day23 {
+    type Input = Vec<(Coord, i64)>;
+    type Part1 = usize;
+    type Part2 = i64;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(nanobots: &Self::Input) -> usize {
+        num_in_range_of_strongest(nanobots)
+    }
+
+    fn part2(nanobots: &Self::Input) -> i64 {
+        best_pos_dist(nanobots)
+    }
 }
 
 #[cfg(test)]
@@ -191,7 +210,7 @@ pos=<0,0,3>, r=1
 pos=<1,1,1>, r=1
 pos=<1,1,2>, r=1
 pos=<1,3,1>, r=1"#;
-        let nanobots = parse(INPUT);
+        let nanobots = parse(INPUT).unwrap();
         assert_eq!(num_in_range_of_strongest(&nanobots), 7);
     }
 
@@ -203,7 +222,7 @@ pos=<16,12,12>, r=4
 pos=<14,14,14>, r=6
 pos=<50,50,50>, r=200
 pos=<10,10,10>, r=5"#;
-        let nanobots = parse(INPUT);
+        let nanobots = parse(INPUT).unwrap();
         assert_eq!(best_pos_dist(&nanobots), 36);
     }
 
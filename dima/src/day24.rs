@@ -1,6 +1,7 @@
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
 use std::cmp::Ordering;
 use std::collections::HashSet;
-use text_io::{scan, try_scan};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Army {
@@ -15,7 +16,7 @@ impl Default for Army {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
-struct Group {
+pub struct Group {
     army: Army,
     units: u64,
     hit_points: u64,
@@ -60,58 +61,69 @@ impl PartialOrd for Group {
     }
 }
 
-fn parse_line(line: &str, army: Army) -> Group {
+/// Parses a group like `17 units each with 5390 hit points (weak to radiation, bludgeoning)
+/// with an attack that does 4507 fire damage at initiative 2`.
+fn parse_group(line: &mut Cursor, army: Army) -> Result<Group, ParseError> {
     let mut group = Group {
         army,
         ..Group::default()
     };
 
-    let attrs_pos = line.find('(').and_then(|start| {
-        line[start + 1..]
-            .find(')')
-            .map(|end| (start, start + 1 + end + 1))
-    });
-
-    if let Some((attrs_start, attrs_end)) = attrs_pos {
-        scan!((&line[..attrs_start]).bytes() =>
-            "{} units each with {} hit points", group.units, group.hit_points);
-        scan!((&line[attrs_end..]).bytes() =>
-            " with an attack that does {} {} damage at initiative {}",
-            group.attack_damage, group.attack_type, group.initiative);
+    group.units = line.number()?;
+    line.tag("units each with")?;
+    group.hit_points = line.number()?;
+    line.tag("hit points")?;
 
-        for attr in (&line[attrs_start + 1..attrs_end - 1]).split("; ") {
-            let mut words = attr.splitn(3, ' ');
-            let v = match words.next().unwrap() {
+    if line.tag("(").is_ok() {
+        loop {
+            let (col, word) = line.word()?;
+            let attack_types = match word {
                 "weak" => &mut group.weaknesses,
                 "immune" => &mut group.immunities,
-                _ => panic!("wrong input"),
+                _ => return Err(ParseError::unknown(line.line(), col, "attribute", word)),
             };
-            v.extend(words.skip(1).next().unwrap().split(", ").map(String::from));
+            line.tag("to")?;
+            loop {
+                let (_, attack_type) = line.word()?;
+                attack_types.insert(attack_type.to_string());
+                if line.tag(",").is_err() {
+                    break;
+                }
+            }
+            if line.tag(";").is_err() {
+                break;
+            }
         }
-    } else {
-        scan!(line.bytes() =>
-            "{} units each with {} hit points with an attack that does {} {} damage at initiative {}",
-            group.units, group.hit_points, group.attack_damage, group.attack_type, group.initiative);
+        line.tag(")")?;
     }
 
-    group
+    line.tag("with an attack that does")?;
+    group.attack_damage = line.number()?;
+    group.attack_type = line.word()?.1.to_string();
+    line.tag("damage at initiative")?;
+    group.initiative = line.number()?;
+    line.end()?;
+    Ok(group)
 }
 
-fn parse(input: &str) -> Vec<Group> {
-    let mut parts = input.split("\n\n");
-    let immune_system = parts
-        .next()
-        .unwrap()
-        .lines()
-        .skip(1)
-        .map(|l| parse_line(l, Army::ImmuneSystem));
-    let infection = parts
-        .next()
-        .unwrap()
-        .lines()
-        .skip(1)
-        .map(|l| parse_line(l, Army::Infection));
-    immune_system.chain(infection).collect()
+fn parse(input: &str) -> Result<Vec<Group>, ParseError> {
+    let mut groups = Vec::new();
+    let mut army = None;
+    for mut line in parse::lines(input) {
+        if line.rest().trim().is_empty() {
+            continue;
+        } else if line.tag("Immune System:").is_ok() {
+            army = Some(Army::ImmuneSystem);
+        } else if line.tag("Infection:").is_ok() {
+            army = Some(Army::Infection);
+        } else {
+            let army = army.ok_or_else(|| line.expected("an army"))?;
+            groups.push(parse_group(&mut line, army)?);
+            continue;
+        }
+        line.end()?;
+    }
+    Ok(groups)
 }
 
 fn target_selection(groups: &mut [Group]) -> Vec<Option<usize>> {
@@ -212,20 +224,33 @@ fn run_with_boost(mut groups: Vec<Group>, boost: u64) -> Option<(Army, u64)> {
     Some((groups[0].army, groups.iter().map(|g| g.units).sum()))
 }
 
-pub fn solve(input: &str) -> (u64, u64) {
-    let groups = parse(input);
-    let p1 = run_with_boost(groups.clone(), 0);
+pub struct Day24;
 
-    let mut p2 = 0;
-    for boost in 1.. {
-        if let Some((winning_army, units)) = run_with_boost(groups.clone(), boost) {
-            if winning_army == Army::ImmuneSystem {
-                p2 = units;
-                break;
+impl Solution for Day24 {
+    type Input = Vec<Group>;
+    type Part1 = Option<u64>;
+    type Part2 = u64;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(groups: &Self::Input) -> Option<u64> {
+        run_with_boost(groups.clone(), 0).map(|(_, units)| units)
+    }
+
+    fn part2(groups: &Self::Input) -> u64 {
+        let mut p2 = 0;
+        for boost in 1.. {
+            if let Some((winning_army, units)) = run_with_boost(groups.clone(), boost) {
+                if winning_army == Army::ImmuneSystem {
+                    p2 = units;
+                    break;
+                }
             }
         }
+        p2
     }
-    (p1.unwrap().1, p2)
 }
 
 #[cfg(test)]
@@ -241,7 +266,7 @@ mod tests {
 Infection:
 801 units each with 4706 hit points (weak to radiation) with an attack that does 116 bludgeoning damage at initiative 1
 4485 units each with 2961 hit points (immune to radiation; weak to fire, cold) with an attack that does 12 slashing damage at initiative 4"#;
-        let groups = parse(INPUT);
+        let groups = parse(INPUT).unwrap();
         assert_eq!(
             run_with_boost(groups.clone(), 0),
             Some((Army::Infection, 5216))
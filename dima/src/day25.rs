@@ -1,8 +1,9 @@
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
 use std::mem;
-use text_io::{scan, try_scan};
 
 #[derive(Debug, Default)]
-struct Point(i64, i64, i64, i64);
+pub struct Point(i64, i64, i64, i64);
 
 impl Point {
     fn dist(&self, other: &Self) -> i64 {
@@ -13,14 +14,27 @@ impl Point {
     }
 }
 
-pub fn solve(input: &str) -> usize {
-    let points = input.lines().map(|s| {
-        let mut p = Point::default();
-        scan!(s.bytes() => "{},{},{},{}", p.0, p.1, p.2, p.3);
-        p
-    });
+fn parse_point(line: &mut Cursor) -> Result<Point, ParseError> {
+    let mut p = Point::default();
+    p.0 = line.number()?;
+    line.tag(",")?;
+    p.1 = line.number()?;
+    line.tag(",")?;
+    p.2 = line.number()?;
+    line.tag(",")?;
+    p.3 = line.number()?;
+    line.end()?;
+    Ok(p)
+}
+
+fn parse(input: &str) -> Result<Vec<Point>, ParseError> {
+    parse::lines(input)
+        .map(|mut line| parse_point(&mut line))
+        .collect()
+}
 
-    let mut constellations: Vec<Vec<Point>> = Vec::new();
+fn num_constellations(points: &[Point]) -> usize {
+    let mut constellations: Vec<Vec<&Point>> = Vec::new();
     for p in points {
         let in_range: Vec<_> = constellations
             .iter()
@@ -48,6 +62,24 @@ pub fn solve(input: &str) -> usize {
     constellations.iter().filter(|c| !c.is_empty()).count()
 }
 
+pub struct Day25;
+
+impl Solution for Day25 {
+    type Input = Vec<Point>;
+    type Part1 = usize;
+    type Part2 = ();
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(points: &Self::Input) -> usize {
+        num_constellations(points)
+    }
+
+    fn part2(_: &Self::Input) {}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,7 +94,7 @@ mod tests {
 0,0,0,6
 9,0,0,0
 12,0,0,0"#;
-        assert_eq!(solve(INPUT), 2);
+        assert_eq!(num_constellations(&parse(INPUT).unwrap()), 2);
     }
 
     #[test]
@@ -77,7 +109,7 @@ mod tests {
 -1,0,-1,0
 0,2,1,-2
 3,0,0,0"#;
-        assert_eq!(solve(INPUT), 4);
+        assert_eq!(num_constellations(&parse(INPUT).unwrap()), 4);
     }
 
     #[test]
@@ -92,7 +124,7 @@ mod tests {
 2,-2,0,-1
 1,-1,0,-1
 3,2,0,2"#;
-        assert_eq!(solve(INPUT), 3);
+        assert_eq!(num_constellations(&parse(INPUT).unwrap()), 3);
     }
 
     #[test]
@@ -107,6 +139,6 @@ mod tests {
 -2,2,3,-1
 1,2,2,0
 -1,-2,0,-2"#;
-        assert_eq!(solve(INPUT), 8);
+        assert_eq!(num_constellations(&parse(INPUT).unwrap()), 8);
     }
 }
@@ -1,11 +1,10 @@
-use lazy_static::lazy_static;
-use regex::{Match, Regex};
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
 
 use std::collections::{HashMap, HashSet};
-use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Eq)]
-struct Claim {
+pub struct Claim {
     id: u32,
     left: u32,
     top: u32,
@@ -13,27 +12,26 @@ struct Claim {
     height: u32,
 }
 
-impl FromStr for Claim {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^#(\d+) @ (\d+),(\d+): (\d+)x(\d+)$").unwrap();
-        }
-        let error_msg = || format!("cannot parse claim: {}", s);
-        let parse_match = |capture: Option<Match>| {
-            capture
-                .ok_or_else(error_msg)
-                .and_then(|m| str::parse(m.as_str()).map_err(|_| error_msg()))
-        };
-
-        RE.captures(s).ok_or_else(error_msg).and_then(|capture| {
-            Ok(Claim {
-                id: parse_match(capture.get(1))?,
-                left: parse_match(capture.get(2))?,
-                top: parse_match(capture.get(3))?,
-                width: parse_match(capture.get(4))?,
-                height: parse_match(capture.get(5))?,
-            })
+impl Claim {
+    /// Parses a claim like `#1397 @ 888,761: 25x24`.
+    fn parse(line: &mut Cursor) -> Result<Self, ParseError> {
+        line.tag("#")?;
+        let id = line.number()?;
+        line.tag("@")?;
+        let left = line.number()?;
+        line.tag(",")?;
+        let top = line.number()?;
+        line.tag(":")?;
+        let width = line.number()?;
+        line.tag("x")?;
+        let height = line.number()?;
+        line.end()?;
+        Ok(Claim {
+            id,
+            left,
+            top,
+            width,
+            height,
         })
     }
 }
@@ -65,21 +63,21 @@ impl Fabric {
     }
 }
 
-fn parse<'a>(input: &'a str) -> impl Iterator<Item = Result<Claim, String>> + 'a {
-    input.lines().map(Claim::from_str)
+fn parse(input: &str) -> Result<Vec<Claim>, ParseError> {
+    parse::lines(input)
+        .map(|mut line| Claim::parse(&mut line))
+        .collect()
 }
 
-pub fn solve(input: &str) -> Result<(u32, HashSet<u32>), String> {
-    let claims: Result<Vec<Claim>, _> = parse(input).collect();
-    let claims = claims?;
-
+/// Overlapping area and ids of claims which don't overlap with any other claim
+fn overlaps(claims: &[Claim]) -> (u32, HashSet<u32>) {
     let mut fabric = Fabric::default();
-    for claim in &claims {
+    for claim in claims {
         fabric.add_claim(claim);
     }
 
     let all_ids: HashSet<u32> = claims.iter().map(|claim| claim.id).collect();
-    let res = fabric.iter().fold(
+    fabric.iter().fold(
         (0, all_ids),
         |(mut overlapping_area, mut non_overlapping_ids), (ids, count)| {
             if *count >= 2 {
@@ -90,8 +88,27 @@ pub fn solve(input: &str) -> Result<(u32, HashSet<u32>), String> {
             }
             (overlapping_area, non_overlapping_ids)
         },
-    );
-    Ok(res)
+    )
+}
+
+pub struct Day3;
+
+impl Solution for Day3 {
+    type Input = Vec<Claim>;
+    type Part1 = u32;
+    type Part2 = HashSet<u32>;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(claims: &Self::Input) -> u32 {
+        overlaps(claims).0
+    }
+
+    fn part2(claims: &Self::Input) -> HashSet<u32> {
+        overlaps(claims).1
+    }
 }
 
 #[cfg(test)]
@@ -100,7 +117,7 @@ mod tests {
 
     #[test]
     fn test_parse_line() {
-        let parsed = Claim::from_str("#1397 @ 888,761: 25x24");
+        let parsed = Claim::parse(&mut Cursor::new(1, "#1397 @ 888,761: 25x24"));
         assert_eq!(
             parsed,
             Ok(Claim {
@@ -111,18 +128,22 @@ mod tests {
                 height: 24,
             })
         );
+        assert_eq!(
+            parse("#1 @ 1,3: 4x4\n#2 @ 3,1; 4x4")
+                .unwrap_err()
+                .to_string(),
+            r#"line 2 col 9: expected ":", found "; 4x4""#
+        );
     }
 
     #[test]
-    fn test_solve() {
+    fn test_overlaps() {
         let ids: HashSet<_> = [3u32].iter().cloned().collect();
-        assert_eq!(
-            solve(
-                r#"#1 @ 1,3: 4x4
+        let claims = parse(
+            r#"#1 @ 1,3: 4x4
 #2 @ 3,1: 4x4
-#3 @ 5,5: 2x2"#
-            ),
-            Ok((4, ids))
+#3 @ 5,5: 2x2"#,
         );
+        assert_eq!(claims.map(|claims| overlaps(&claims)), Ok((4, ids)));
     }
 }
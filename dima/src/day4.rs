@@ -1,5 +1,6 @@
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
 use itertools::Itertools;
-use text_io::{scan, try_scan};
 
 type Id = u16;
 type Date = (u16, u8, u8, u8, u8);
@@ -12,36 +13,46 @@ enum Event {
     WakeUp,
 }
 
-fn parse(input: &str) -> Vec<(Date, Event)> {
-    let mut events: Vec<_> = input
-        .lines()
-        .map(|line| {
-            let y: u16;
-            let m: u8;
-            let d: u8;
-            let h: u8;
-            let min: u8;
-            let event: String;
-            let suffix: String;
-
-            scan!(line.bytes() => "[{}-{}-{} {}:{}] {} {}", y, m, d, h, min, event, suffix);
-
-            let event = match &event[..] {
-                "Guard" => {
-                    let id: Id;
-                    scan!(suffix.bytes() => "#{}", id);
-                    Event::BeginShift(id)
-                }
-                "falls" => Event::FallAsleep,
-                "wakes" => Event::WakeUp,
-                _ => panic!("unexpected"),
-            };
+fn parse_event(line: &mut Cursor) -> Result<(Date, Event), ParseError> {
+    line.tag("[")?;
+    let y = line.number()?;
+    line.tag("-")?;
+    let m = line.number()?;
+    line.tag("-")?;
+    let d = line.number()?;
+    let h = line.number()?;
+    line.tag(":")?;
+    let min = line.number()?;
+    line.tag("]")?;
 
-            ((y, m, d, h, min), event)
-        })
-        .collect();
+    let (col, word) = line.word()?;
+    let event = match word {
+        "Guard" => {
+            line.tag("#")?;
+            let id = line.number()?;
+            line.tag("begins shift")?;
+            Event::BeginShift(id)
+        }
+        "falls" => {
+            line.tag("asleep")?;
+            Event::FallAsleep
+        }
+        "wakes" => {
+            line.tag("up")?;
+            Event::WakeUp
+        }
+        _ => return Err(ParseError::unknown(line.line(), col, "event", word)),
+    };
+    line.end()?;
+    Ok(((y, m, d, h, min), event))
+}
+
+fn parse(input: &str) -> Result<Vec<(Date, Event)>, ParseError> {
+    let mut events = parse::lines(input)
+        .map(|mut line| parse_event(&mut line))
+        .collect::<Result<Vec<_>, _>>()?;
     events.sort_by_key(|(date, _)| *date);
-    events
+    Ok(events)
 }
 
 fn events_to_minutes_table(events: &[(Date, Event)]) -> MinutesTable {
@@ -88,9 +99,10 @@ fn solve1(minutes: &MinutesTable) -> usize {
     max_asleep_guard as usize * max_asleep_minute
 }
 
-fn solve2(minutes: MinutesTable) -> usize {
+fn solve2(minutes: &MinutesTable) -> usize {
     let (_, most_frequently_asleep, minute) = minutes
-        .into_iter()
+        .iter()
+        .cloned()
         .enumerate()
         .filter_map(|(minute, mut entry)| {
             entry.sort();
@@ -107,9 +119,24 @@ fn solve2(minutes: MinutesTable) -> usize {
     most_frequently_asleep as usize * minute
 }
 
-pub fn solve(s: &str) -> (usize, usize) {
-    let minutes = events_to_minutes_table(&parse(s));
-    (solve1(&minutes), solve2(minutes))
+pub struct Day4;
+
+impl Solution for Day4 {
+    type Input = MinutesTable;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        Ok(events_to_minutes_table(&parse(input)?))
+    }
+
+    fn part1(minutes: &Self::Input) -> usize {
+        solve1(minutes)
+    }
+
+    fn part2(minutes: &Self::Input) -> usize {
+        solve2(minutes)
+    }
 }
 
 #[cfg(test)]
@@ -136,6 +163,7 @@ mod tests {
 [1518-11-05 00:45] falls asleep
 [1518-11-05 00:55] wakes up"#;
 
-        assert_eq!(solve(input), (240, 4455));
+        let minutes = events_to_minutes_table(&parse(input).unwrap());
+        assert_eq!((solve1(&minutes), solve2(&minutes)), (240, 4455));
     }
 }
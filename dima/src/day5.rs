@@ -1,3 +1,6 @@
+use crate::parse::ParseError;
+use crate::solution::Solution;
+
 fn compactor<I: Iterator<Item = char>>(input: I) -> Vec<char> {
     let mut stack: Vec<char> = Vec::new();
     for c in input {
@@ -17,7 +20,7 @@ fn compactor<I: Iterator<Item = char>>(input: I) -> Vec<char> {
     stack
 }
 
-pub fn solve2(input: &str) -> Option<usize> {
+fn solve2(input: &str) -> Option<usize> {
     let mut units: Vec<_> = input.chars().map(|c| c.to_ascii_lowercase()).collect();
     units.sort();
     units.dedup();
@@ -27,8 +30,24 @@ pub fn solve2(input: &str) -> Option<usize> {
         .min()
 }
 
-pub fn solve(input: &str) -> (usize, Option<usize>) {
-    (compactor(input.chars()).len(), solve2(input))
+pub struct Day5;
+
+impl Solution for Day5 {
+    type Input = String;
+    type Part1 = usize;
+    type Part2 = Option<usize>;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        Ok(input.to_string())
+    }
+
+    fn part1(input: &Self::Input) -> usize {
+        compactor(input.chars()).len()
+    }
+
+    fn part2(input: &Self::Input) -> Option<usize> {
+        solve2(input)
+    }
 }
 
 #[cfg(test)]
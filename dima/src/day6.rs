@@ -1,7 +1,9 @@
+use crate::parse::{self, ParseError};
+use crate::solution::Solution;
 use itertools::iproduct;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Point {
+pub struct Point {
     x: i32,
     y: i32,
 }
@@ -60,27 +62,42 @@ fn solve2(points: &[Point], width: i32, height: i32, max_distance: i32) -> usize
         .sum()
 }
 
-fn parse(input: &str) -> (Vec<Point>, i32, i32) {
-    let points: Vec<Point> = input
-        .lines()
-        .map(|line| {
-            let mut it = line.split(", ");
-            let x = it.next().unwrap().parse().unwrap();
-            let y = it.next().unwrap().parse().unwrap();
-            Point { x, y }
+fn parse(input: &str) -> Result<(Vec<Point>, i32, i32), ParseError> {
+    let points = parse::lines(input)
+        .map(|mut line| {
+            let x = line.number()?;
+            line.tag(",")?;
+            let y = line.number()?;
+            line.end()?;
+            Ok(Point { x, y })
         })
-        .collect();
+        .collect::<Result<Vec<_>, ParseError>>()?;
+    if points.is_empty() {
+        return Err(ParseError::eof(0, "a point"));
+    }
     let width = points.iter().map(|pt| pt.x).max().unwrap() + 1;
     let height = points.iter().map(|pt| pt.y).max().unwrap() + 1;
-    (points, width, height)
+    Ok((points, width, height))
 }
 
-pub fn solve(input: &str) -> (usize, usize) {
-    let (points, width, height) = parse(input);
-    (
-        solve1(&points[..], width, height),
-        solve2(&points[..], width, height, 10_000),
-    )
+pub struct Day6;
+
+impl Solution for Day6 {
+    type Input = (Vec<Point>, i32, i32);
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1((points, width, height): &Self::Input) -> usize {
+        solve1(&points[..], *width, *height)
+    }
+
+    fn part2((points, width, height): &Self::Input) -> usize {
+        solve2(&points[..], *width, *height, 10_000)
+    }
 }
 
 #[cfg(test)]
@@ -96,13 +113,13 @@ mod test {
 
     #[test]
     fn test_solve1() {
-        let (points, width, height) = parse(INPUT);
+        let (points, width, height) = parse(INPUT).unwrap();
         assert_eq!(solve1(&points[..], width, height), 17);
     }
 
     #[test]
     fn test_solve2() {
-        let (points, width, height) = parse(INPUT);
+        let (points, width, height) = parse(INPUT).unwrap();
         assert_eq!(solve2(&points[..], width, height, 32), 16);
     }
 }
@@ -1,20 +1,32 @@
+use crate::parse::{self, Cursor, ParseError};
+use crate::solution::Solution;
 use itertools::Itertools;
-use text_io::{scan, try_scan};
 
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::collections::HashMap;
 
-fn parse(input: &str) -> Vec<(char, char)> {
-    input
-        .lines()
-        .map(|line| {
-            let from: char;
-            let to: char;
-            scan!(line.bytes() => "Step {} must be finished before step {} can begin.", from, to);
-            (from, to)
+fn parse_step(line: &mut Cursor) -> Result<char, ParseError> {
+    line.skip_spaces();
+    match line.rest().chars().next() {
+        Some(step) if step.is_ascii_uppercase() => line.char(),
+        _ => Err(line.expected("a step from A to Z")),
+    }
+}
+
+fn parse(input: &str) -> Result<Vec<(char, char)>, ParseError> {
+    let edges = parse::lines(input)
+        .map(|mut line| {
+            line.tag("Step")?;
+            let from = parse_step(&mut line)?;
+            line.tag("must be finished before step")?;
+            let to = parse_step(&mut line)?;
+            line.tag("can begin.")?;
+            line.end()?;
+            Ok((from, to))
         })
-        .sorted()
+        .collect::<Result<Vec<_>, ParseError>>()?;
+    Ok(edges.into_iter().sorted())
 }
 
 // Entry for BinaryHeap to inverse sorting -> min heap
@@ -65,7 +77,7 @@ fn lex_top_sort(edges: &[(char, char)]) -> String {
     res
 }
 
-pub fn simulation(edges: &[(char, char)], num_workers: usize, task_duration: usize) -> usize {
+fn simulation(edges: &[(char, char)], num_workers: usize, task_duration: usize) -> usize {
     let mut out_edges = HashMap::new();
     let mut in_degrees = HashMap::new();
     for &(from, to) in edges {
@@ -114,9 +126,24 @@ pub fn simulation(edges: &[(char, char)], num_workers: usize, task_duration: usi
     t
 }
 
-pub fn solve(input: &str) -> (String, usize) {
-    let edges = parse(input);
-    (lex_top_sort(&edges), simulation(&edges, 5, 60))
+pub struct Day7;
+
+impl Solution for Day7 {
+    type Input = Vec<(char, char)>;
+    type Part1 = String;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(edges: &Self::Input) -> String {
+        lex_top_sort(edges)
+    }
+
+    fn part2(edges: &Self::Input) -> usize {
+        simulation(edges, 5, 60)
+    }
 }
 
 #[cfg(test)]
@@ -134,7 +161,7 @@ Step F must be finished before step E can begin."#;
     #[test]
     fn test_parse() {
         assert_eq!(
-            parse(INPUT),
+            parse(INPUT).unwrap(),
             vec![
                 ('A', 'B'),
                 ('A', 'D'),
@@ -158,11 +185,11 @@ Step F must be finished before step E can begin."#;
             lex_top_sort(&vec![('A', 'B'), ('A', 'C'), ('A', 'D'), ('C', 'E')]),
             "ABCDE"
         );
-        assert_eq!(lex_top_sort(&parse(INPUT)), "CABDFE");
+        assert_eq!(lex_top_sort(&parse(INPUT).unwrap()), "CABDFE");
     }
 
     #[test]
     fn test_simulation() {
-        assert_eq!(simulation(&parse(INPUT), 2, 0), 15);
+        assert_eq!(simulation(&parse(INPUT).unwrap(), 2, 0), 15);
     }
 }
@@ -1,5 +1,33 @@
-fn parse<'a>(input: &'a str) -> impl Iterator<Item = usize> + 'a {
-    input.split(" ").map(|s| s.parse().unwrap())
+use crate::parse::{Cursor, ParseError};
+use crate::solution::Solution;
+
+/// Number of entries of the node starting at `input[0]`, `None` if it is truncated.
+fn node_len(input: &[usize]) -> Option<usize> {
+    let (&len_children, &len_metadata) = (input.get(0)?, input.get(1)?);
+    let mut len = 2;
+    for _ in 0..len_children {
+        len += node_len(&input[len..])?;
+    }
+    len += len_metadata;
+    if len <= input.len() {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+fn parse(input: &str) -> Result<Vec<usize>, ParseError> {
+    let mut line = Cursor::new(1, input);
+    let mut tree = Vec::new();
+    while !line.is_empty() {
+        tree.push(line.number()?);
+        line.skip_spaces();
+    }
+    match node_len(&tree) {
+        None => Err(line.invalid("truncated tree")),
+        Some(len) if len < tree.len() => Err(line.invalid("numbers after the tree")),
+        Some(_) => Ok(tree),
+    }
 }
 
 fn checksum(input: &mut Iterator<Item = usize>) -> usize {
@@ -22,8 +50,24 @@ fn value(input: &mut Iterator<Item = usize>) -> usize {
     }
 }
 
-pub fn solve(input: &str) -> (usize, usize) {
-    (checksum(&mut parse(input)), value(&mut parse(input)))
+pub struct Day8;
+
+impl Solution for Day8 {
+    type Input = Vec<usize>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(tree: &Self::Input) -> usize {
+        checksum(&mut tree.iter().cloned())
+    }
+
+    fn part2(tree: &Self::Input) -> usize {
+        value(&mut tree.iter().cloned())
+    }
 }
 
 #[cfg(test)]
@@ -31,15 +75,22 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_checksum() {
+    fn test_checksum() -> Result<(), ParseError> {
         assert_eq!(
-            checksum(&mut parse("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2")),
+            checksum(&mut parse("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2")?.into_iter()),
             138
         );
+        Ok(())
     }
 
     #[test]
-    fn test_value() {
-        assert_eq!(value(&mut parse("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2")), 66);
+    fn test_value() -> Result<(), ParseError> {
+        assert_eq!(
+            value(&mut parse("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2")?.into_iter()),
+            66
+        );
+        assert!(parse("2 3 0 3 10 11 12").is_err());
+        assert!(parse("0 1 3 4").is_err());
+        Ok(())
     }
 }
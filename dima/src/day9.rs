@@ -1,10 +1,16 @@
-use std::error::Error;
-use text_io::try_scan;
-
-fn parse(input: &str) -> Result<(usize, usize), Box<Error>> {
-    let num_players: usize;
-    let num_marbles: usize;
-    try_scan!(input.bytes() => "{} players; last marble is worth {} points", num_players, num_marbles);
+use crate::parse::{Cursor, ParseError};
+use crate::solution::Solution;
+
+fn parse(input: &str) -> Result<(usize, usize), ParseError> {
+    let mut line = Cursor::new(1, input);
+    let num_players = line.number()?;
+    if num_players == 0 {
+        return Err(ParseError::invalid(1, 1, "no players"));
+    }
+    line.tag("players; last marble is worth")?;
+    let num_marbles = line.number()?;
+    line.tag("points")?;
+    line.end()?;
     Ok((num_players, num_marbles))
 }
 
@@ -76,11 +82,24 @@ fn highest_score(num_players: usize, num_marbles: usize) -> usize {
     scores.into_iter().max().unwrap()
 }
 
-pub fn solve(input: &str) -> Result<(usize, usize), Box<Error>> {
-    let (num_players, num_marbles) = parse(input)?;
-    let part_1 = highest_score(num_players, num_marbles);
-    let part_2 = highest_score(num_players, 100 * num_marbles);
-    Ok((part_1, part_2))
+pub struct Day9;
+
+impl Solution for Day9 {
+    type Input = (usize, usize);
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError> {
+        parse(input)
+    }
+
+    fn part1(&(num_players, num_marbles): &Self::Input) -> usize {
+        highest_score(num_players, num_marbles)
+    }
+
+    fn part2(&(num_players, num_marbles): &Self::Input) -> usize {
+        highest_score(num_players, 100 * num_marbles)
+    }
 }
 
 #[cfg(test)]
@@ -88,7 +107,7 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_highest_score() -> Result<(), Box<Error>> {
+    fn test_highest_score() -> Result<(), ParseError> {
         const INPUTS: [&str; 6] = [
             "9 players; last marble is worth 25 points",
             "10 players; last marble is worth 1618 points",
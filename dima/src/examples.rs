@@ -0,0 +1,180 @@
+//! Worked examples of the puzzle statements in `puzzles/*.md`.
+//!
+//! `puzzles/examples.toml` locates each example by a regex matched against the statement: its
+//! named groups `input` and `answer` are the example's input and stated answer. The examples are
+//! extracted when the tests run, so they don't have to be copied into the solutions by hand.
+
+use crate::solution::Part;
+use crate::verify;
+use regex::Regex;
+use serde::Deserialize;
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Locations of the examples in the puzzle statements `day<N>.md` next to it.
+pub const EXAMPLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../puzzles/examples.toml");
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    #[serde(rename = "example", default)]
+    pub specs: Vec<Spec>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Spec {
+    pub day: u8,
+    pub part: u8,
+    /// Regex applied to the statement; every match is an example.
+    pub pattern: String,
+    /// Template of the input, `{name}` is replaced by the named group `name`. Defaults to the
+    /// group `input`.
+    pub input: Option<String>,
+    /// Joins lines indented further than the rest of the input to the previous line.
+    #[serde(default)]
+    pub unwrap: bool,
+    /// Strips the whitespace around each line of the input.
+    #[serde(default)]
+    pub trim: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Example {
+    pub day: u8,
+    pub part: Part,
+    pub input: String,
+    pub answer: String,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+}
+
+/// Turns an input quoted in a statement into the form of a puzzle input.
+fn format_input(input: &str, unwrap: bool, trim: bool) -> String {
+    let input = verify::normalize(input);
+    let mut lines: Vec<String> = Vec::new();
+    for line in input.lines() {
+        match lines.last_mut() {
+            Some(last) if unwrap && line.starts_with(char::is_whitespace) => {
+                last.push(' ');
+                last.push_str(line.trim_start());
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    if trim {
+        for line in &mut lines {
+            *line = line.trim().to_string();
+        }
+    }
+    lines.join("\n")
+}
+
+/// Examples of `spec` in `statement`.
+pub fn extract(spec: &Spec, statement: &str) -> Result<Vec<Example>, Box<dyn Error>> {
+    let re = Regex::new(&spec.pattern)?;
+    let part = spec.part.to_string().parse::<Part>()?;
+    let template = spec.input.as_ref().map_or("{input}", String::as_str);
+
+    let mut examples = Vec::new();
+    for caps in re.captures_iter(statement) {
+        let mut input = template.to_string();
+        for name in re.capture_names().flatten() {
+            if let Some(m) = caps.name(name) {
+                input = input.replace(&format!("{{{}}}", name), m.as_str());
+            }
+        }
+        let answer = caps
+            .name("answer")
+            .ok_or_else(|| format!("pattern without answer: {:?}", spec.pattern))?;
+        examples.push(Example {
+            day: spec.day,
+            part,
+            input: format_input(&input, spec.unwrap, spec.trim),
+            answer: answer.as_str().to_string(),
+        });
+    }
+    if examples.is_empty() {
+        return Err(format!(
+            "day {} part {}: no example matches {:?}",
+            spec.day, spec.part, spec.pattern
+        )
+        .into());
+    }
+    Ok(examples)
+}
+
+/// All examples of the manifest at `path`, in the order of the manifest.
+pub fn examples(path: &Path) -> Result<Vec<Example>, Box<dyn Error>> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut examples = Vec::new();
+    for spec in Manifest::load(path)?.specs {
+        let statement = dir.join(format!("day{}.md", spec.day));
+        let statement = fs::read_to_string(&statement)
+            .map_err(|e| format!("{}: {}", statement.display(), e))?;
+        examples.extend(extract(&spec, &statement)?);
+    }
+    Ok(examples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{runner, solution};
+
+    #[test]
+    fn test_format_input() {
+        assert_eq!(
+            format_input("\n    a b\n     c\n    d\n\n", true, false),
+            "a b c\nd"
+        );
+        assert_eq!(format_input("     1\n    12\n", false, false), " 1\n12");
+        assert_eq!(format_input("     1\n    12\n", false, true), "1\n12");
+    }
+
+    #[test]
+    fn test_extract() -> Result<(), Box<dyn Error>> {
+        let spec = Spec {
+            day: 9,
+            part: 1,
+            pattern: r"\* (?P<players>\d+) players: (?P<answer>\d+)".to_string(),
+            input: Some("{players} players".to_string()),
+            unwrap: false,
+            trim: false,
+        };
+        let examples = extract(&spec, "* 10 players: 8317\n* 13 players: 146373\n")?;
+        let examples: Vec<(&str, &str)> = examples
+            .iter()
+            .map(|e| (e.input.as_str(), e.answer.as_str()))
+            .collect();
+        assert_eq!(
+            examples,
+            vec![("10 players", "8317"), ("13 players", "146373")]
+        );
+        assert!(extract(&spec, "no examples").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_examples() -> Result<(), Box<dyn Error>> {
+        let mut failures = Vec::new();
+        for example in examples(Path::new(EXAMPLES))? {
+            let day = solution::find(example.day).unwrap();
+            let answer = runner::solve(&day, &example.input, &[example.part])?.remove(0);
+            let answer = runner::format_answer(&answer);
+            if verify::normalize(&answer) != verify::normalize(&example.answer) {
+                failures.push(format!(
+                    "day {} part {}: expected {}, got {}",
+                    example.day, example.part, example.answer, answer
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+        Ok(())
+    }
+}
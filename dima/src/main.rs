@@ -1,3 +1,5 @@
+mod bench;
+mod crosscheck;
 mod day1;
 mod day10;
 mod day11;
@@ -23,53 +25,590 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+mod examples;
+mod parse;
+mod runner;
+mod solution;
+mod verify;
+mod viewer;
 
-use regex;
+use crate::solution::Part;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::fs::File;
-use std::io::Read;
-
-fn main() -> Result<(), Box<Error>> {
-    let mut args = std::env::args().skip(1);
-    let input_file = args.next().ok_or("Usage: advent-of-code-2018 <day.txt>")?;
-
-    let re = regex::Regex::new(r"^.*day(\d+).txt$")?;
-    let day: u8 = re
-        .captures(&input_file)
-        .and_then(|c| c.get(1).map(|g| g.as_str()))
-        .ok_or_else(|| format!("can't deduce day from: {}", input_file))?
-        .parse()?;
-
-    let mut input = String::new();
-    File::open(input_file)?.read_to_string(&mut input)?;
-
-    match day {
-        1 => println!("{:?}", day1::solve(&input)),
-        2 => println!("{:?}", day2::solve(&input)),
-        3 => println!("{:?}", day3::solve(&input)),
-        4 => println!("{:?}", day4::solve(&input)),
-        5 => println!("{:?}", day5::solve(&input)),
-        6 => println!("{:?}", day6::solve(&input)),
-        7 => println!("{:?}", day7::solve(&input)),
-        8 => println!("{:?}", day8::solve(&input)),
-        9 => println!("{:?}", day9::solve(&input)),
-        10 => println!("{:?}", day10::solve(&input)),
-        11 => println!("{:?}", day11::solve(&input)),
-        12 => println!("{:?}", day12::solve(&input)),
-        13 => println!("{:?}", day13::solve(&input)),
-        14 => println!("{:?}", day14::solve(&input)),
-        15 => println!("{:?}", day15::solve(&input)),
-        16 => println!("{:?}", day16::solve(&input)),
-        17 => println!("{:?}", day17::solve(&input)),
-        18 => println!("{:?}", day18::solve(&input)),
-        19 => println!("{:?}", day19::solve(&input)),
-        20 => println!("{:?}", day20::solve(&input)),
-        21 => println!("{:?}", day21::solve(&input)),
-        22 => println!("{:?}", day22::solve(&input)),
-        23 => println!("{:?}", day23::solve(&input)),
-        24 => println!("{:?}", day24::solve(&input)),
-        25 => println!("{:?}", day25::solve(&input)),
-        _ => eprintln!("invalid day: {}", day),
+use std::path::Path;
+use std::process;
+
+fn main() {
+    if let Err(e) = try_main() {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn try_main() -> Result<(), Box<dyn Error>> {
+    let matches = App::new("advent-of-code-2018")
+        .about("Solutions of Advent of Code 2018")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("list").about("Lists all solved days"))
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Solves a single day or all days")
+                .arg(
+                    Arg::with_name("day")
+                        .long("day")
+                        .short("d")
+                        .takes_value(true)
+                        .help("Day to solve; deduced from the input file name if omitted"),
+                )
+                .arg(
+                    Arg::with_name("part")
+                        .long("part")
+                        .short("p")
+                        .takes_value(true)
+                        .possible_values(&["1", "2"])
+                        .help("Only solve this part"),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .conflicts_with_all(&["day", "input"])
+                        .help("Solves every day with an input file in the input directory"),
+                )
+                .arg(
+                    Arg::with_name("input-dir")
+                        .long("input-dir")
+                        .takes_value(true)
+                        .default_value(runner::INPUT_DIR)
+                        .help("Directory with input files named day<N>.txt"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format; json prints one object per day and part"),
+                )
+                .arg(Arg::with_name("input").help(
+                    "Input file or - for stdin; defaults to day<N>.txt in the input directory",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Checks solutions against the answers recorded in manifests")
+                .arg(
+                    Arg::with_name("manifest")
+                        .multiple(true)
+                        .default_value(verify::ANSWERS)
+                        .help("answers.toml of an author"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("crosscheck")
+                .about("Compares the answers of all authors' solutions on the same input")
+                .arg(
+                    Arg::with_name("day")
+                        .long("day")
+                        .short("d")
+                        .takes_value(true)
+                        .multiple(true)
+                        .help("Days to check; defaults to all days with other implementations"),
+                )
+                .arg(
+                    Arg::with_name("input")
+                        .long("input")
+                        .takes_value(true)
+                        .requires("day")
+                        .conflicts_with("all-inputs")
+                        .help("Input to use instead of input/day<N>.txt"),
+                )
+                .arg(
+                    Arg::with_name("all-inputs")
+                        .long("all-inputs")
+                        .help("Also runs every implementation on the inputs of the other authors"),
+                )
+                .arg(
+                    Arg::with_name("manifest")
+                        .long("manifest")
+                        .takes_value(true)
+                        .default_value(crosscheck::IMPLEMENTATIONS)
+                        .help("Description of the other authors' implementations"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("examples")
+                .about("Runs all authors' solutions on the examples of the puzzle statements")
+                .arg(
+                    Arg::with_name("day")
+                        .long("day")
+                        .short("d")
+                        .takes_value(true)
+                        .multiple(true)
+                        .help("Days to check; defaults to all days with examples"),
+                )
+                .arg(
+                    Arg::with_name("examples")
+                        .long("examples")
+                        .takes_value(true)
+                        .default_value(examples::EXAMPLES)
+                        .help("Locations of the examples in the puzzle statements"),
+                )
+                .arg(
+                    Arg::with_name("manifest")
+                        .long("manifest")
+                        .takes_value(true)
+                        .default_value(crosscheck::IMPLEMENTATIONS)
+                        .help("Description of the other authors' implementations"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Times parsing and both parts of each day")
+                .arg(
+                    Arg::with_name("day")
+                        .long("day")
+                        .short("d")
+                        .takes_value(true)
+                        .multiple(true)
+                        .help("Days to time; defaults to all days with an input file"),
+                )
+                .arg(
+                    Arg::with_name("runs")
+                        .long("runs")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Number of runs of each step"),
+                )
+                .arg(
+                    Arg::with_name("time-limit")
+                        .long("time-limit")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Seconds after which the runs of a step stop early"),
+                )
+                .arg(
+                    Arg::with_name("input-dir")
+                        .long("input-dir")
+                        .takes_value(true)
+                        .default_value(runner::INPUT_DIR)
+                        .help("Directory with input files named day<N>.txt"),
+                )
+                .arg(
+                    Arg::with_name("baseline")
+                        .long("baseline")
+                        .takes_value(true)
+                        .default_value(bench::BASELINE)
+                        .help("Timings to compare to"),
+                )
+                .arg(
+                    Arg::with_name("save-baseline")
+                        .long("save-baseline")
+                        .help("Stores the median times in the baseline"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("battle")
+                .about("Simulates the combat of day 15 and writes, replays or compares its events")
+                .arg(
+                    Arg::with_name("elf-power")
+                        .long("elf-power")
+                        .takes_value(true)
+                        .help("Attack power of the elves [default: 3]"),
+                )
+                .arg(
+                    Arg::with_name("rules")
+                        .long("rules")
+                        .takes_value(true)
+                        .help("File with the rules of combat; defaults to the ones of the puzzle"),
+                )
+                .arg(
+                    Arg::with_name("log")
+                        .long("log")
+                        .takes_value(true)
+                        .help("Writes the events of the combat to this file, one per line"),
+                )
+                .arg(
+                    Arg::with_name("replay")
+                        .long("replay")
+                        .takes_value(true)
+                        .conflicts_with_all(&["log", "diff", "elf-power"])
+                        .help(
+                            "Replays the events of this file and prints the map after each round",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("animate")
+                        .long("animate")
+                        .conflicts_with("replay")
+                        .help("Shows the combat in the terminal; keys: space, n, +, - and q"),
+                )
+                .arg(
+                    Arg::with_name("diff")
+                        .long("diff")
+                        .takes_value(true)
+                        .help("Compares the events of the combat to the ones in this file"),
+                )
+                .arg(
+                    Arg::with_name("input")
+                        .help("Input file; defaults to day15.txt in the input directory"),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("list", Some(_)) => {
+            for day in solution::registry() {
+                println!("{}", day.day);
+            }
+            Ok(())
+        }
+        ("run", Some(matches)) => run(matches),
+        ("verify", Some(matches)) => verify(matches),
+        ("crosscheck", Some(matches)) => crosscheck(matches),
+        ("examples", Some(matches)) => examples(matches),
+        ("bench", Some(matches)) => bench(matches),
+        ("battle", Some(matches)) => battle(matches),
+        _ => unreachable!("clap requires a subcommand"),
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let parts = match matches.value_of("part") {
+        Some(part) => vec![part.parse::<Part>()?],
+        None => Part::ALL.to_vec(),
+    };
+    let input_dir = Path::new(matches.value_of("input-dir").unwrap());
+
+    let json = matches.value_of("format") == Some("json");
+
+    if matches.is_present("all") && json {
+        let mut records = Vec::new();
+        for (day, path) in runner::input_files(input_dir)? {
+            if let Some(solution) = solution::find(day) {
+                let input = runner::read_input(&path.to_string_lossy());
+                records.extend(runner::solve_records(&solution, input, &parts));
+            }
+        }
+        return print_records(&records);
+    }
+
+    if matches.is_present("all") {
+        let mut rows = Vec::new();
+        for (day, path) in runner::input_files(input_dir)? {
+            if let Some(solution) = solution::find(day) {
+                let answers = runner::read_input(&path.to_string_lossy())
+                    .and_then(|input| runner::solve(&solution, &input, &parts))
+                    .map_err(|e| e.to_string());
+                rows.push((day, answers));
+            }
+        }
+        runner::print_table(&parts, &rows);
+        return Ok(());
+    }
+
+    let day: u8 = match (matches.value_of("day"), matches.value_of("input")) {
+        (Some(day), _) => day.parse()?,
+        (None, Some(path)) => runner::day_from_path(Path::new(path))
+            .ok_or_else(|| format!("can't deduce day from: {}; use --day", path))?,
+        (None, None) => return Err("either --day, an input file or --all is required".into()),
+    };
+    let solution = solution::find(day).ok_or_else(|| format!("invalid day: {}", day))?;
+    let input = match matches.value_of("input") {
+        Some(path) => runner::read_input(path),
+        None => runner::read_input(&runner::input_path(input_dir, day).to_string_lossy()),
+    };
+    if json {
+        return print_records(&runner::solve_records(&solution, input, &parts));
+    }
+
+    let input = input?;
+    let answers = runner::solve(&solution, &input, &parts)?;
+    for (part, answer) in parts.iter().zip(&answers) {
+        let answer = runner::format_answer(answer);
+        if answer.contains('\n') {
+            println!("Part {}:\n{}", part, answer);
+        } else {
+            println!("Part {}: {}", part, answer);
+        }
+    }
+    let failed = answers.iter().filter(|answer| answer.is_err()).count();
+    if failed > 0 {
+        return Err(format!("{} parts failed", failed).into());
+    }
+    Ok(())
+}
+
+/// Prints one line of JSON per record; fails if any part failed.
+fn print_records(records: &[runner::Record]) -> Result<(), Box<dyn Error>> {
+    for record in records {
+        println!("{}", serde_json::to_string(record)?);
+    }
+    let failed = records.iter().filter(|r| r.error.is_some()).count();
+    if failed > 0 {
+        return Err(format!("{} parts failed", failed).into());
+    }
+    Ok(())
+}
+
+fn verify(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    use crate::verify::Outcome;
+
+    let (mut ok, mut failed, mut skipped) = (0, 0, 0);
+    for manifest in matches.values_of("manifest").unwrap() {
+        for (entry, outcome) in verify::verify(Path::new(manifest))? {
+            let label = format!("{}: day {} part {}", manifest, entry.day, entry.part);
+            match outcome {
+                Outcome::Match => ok += 1,
+                Outcome::Mismatch(answer) => {
+                    failed += 1;
+                    println!("{}: wrong answer", label);
+                    print!("{}", verify::diff(&entry.answer, &answer));
+                }
+                Outcome::Failed(e) => {
+                    failed += 1;
+                    println!("{}: {}", label, e);
+                }
+                Outcome::Skipped(reason) => {
+                    skipped += 1;
+                    println!("{}: skipped: {}", label, reason);
+                }
+            }
+        }
+    }
+
+    println!("{} ok, {} failed, {} skipped", ok, failed, skipped);
+    if failed > 0 {
+        return Err(format!("{} answers don't match", failed).into());
+    }
+    Ok(())
+}
+
+fn crosscheck(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let manifest = Path::new(matches.value_of("manifest").unwrap());
+    let days = match matches.values_of("day") {
+        Some(days) => days.map(str::parse).collect::<Result<Vec<u8>, _>>()?,
+        None => crosscheck::days(manifest)?,
+    };
+
+    let mut disagreements = 0;
+    for day in days {
+        let inputs = match matches.value_of("input") {
+            Some(input) => vec![input.into()],
+            None => crosscheck::inputs(manifest, day, matches.is_present("all-inputs"))?,
+        };
+        for input in inputs {
+            let report = crosscheck::crosscheck(manifest, day, &input)?;
+            let disagree = report.disagreements();
+            disagreements += disagree.len();
+
+            println!("day {} with {}", day, input.display());
+            for (author, answers) in &report.answers {
+                print!("  {:<16}", author);
+                for answer in answers {
+                    let answer = match answer {
+                        Ok(Some(answer)) if answer.contains('\n') => "(multi-line)".to_string(),
+                        Ok(Some(answer)) => answer.clone(),
+                        Ok(None) => "-".to_string(),
+                        Err(e) => format!("error: {}", e),
+                    };
+                    print!("  {:<28}", answer);
+                }
+                println!();
+            }
+            for part in disagree {
+                println!("  DISAGREEMENT in part {}", part);
+                let i = if part == Part::One { 0 } else { 1 };
+                for (author, answers) in &report.answers {
+                    match &answers[i] {
+                        Ok(Some(answer)) if answer.contains('\n') => {
+                            println!("  {}:", author);
+                            for line in answer.lines() {
+                                println!("    {}", line);
+                            }
+                        }
+                        Ok(Some(answer)) => println!("  {}: {}", author, answer),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    if disagreements > 0 {
+        return Err(format!("{} disagreements", disagreements).into());
+    }
+    Ok(())
+}
+
+fn examples(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let manifest = Path::new(matches.value_of("manifest").unwrap());
+    let days = match matches.values_of("day") {
+        Some(days) => days.map(str::parse).collect::<Result<Vec<u8>, _>>()?,
+        None => Vec::new(),
+    };
+
+    // Both parts of an example usually share the input, so run every implementation only once
+    // per input.
+    let mut inputs: BTreeMap<(u8, String), Vec<(Part, String)>> = BTreeMap::new();
+    for example in examples::examples(Path::new(matches.value_of("examples").unwrap()))? {
+        if days.is_empty() || days.contains(&example.day) {
+            inputs
+                .entry((example.day, example.input))
+                .or_default()
+                .push((example.part, example.answer));
+        }
+    }
+
+    let (mut ok, mut failed) = (0, 0);
+    let path = std::env::temp_dir().join(format!("aoc-example-{}.txt", std::process::id()));
+    for ((day, input), expected) in inputs {
+        std::fs::write(&path, &input)?;
+        let report = crosscheck::crosscheck(manifest, day, &path);
+        std::fs::remove_file(&path)?;
+
+        println!("day {} with {}", day, input.lines().next().unwrap_or(""));
+        for (author, answers) in &report?.answers {
+            for (part, answer) in &expected {
+                let i = if *part == Part::One { 0 } else { 1 };
+                let outcome = match &answers[i] {
+                    Ok(None) => continue,
+                    Ok(Some(actual)) if verify::normalize(actual) == verify::normalize(answer) => {
+                        ok += 1;
+                        "ok".to_string()
+                    }
+                    Ok(Some(actual)) => {
+                        failed += 1;
+                        format!("expected {}, got {}", answer, actual)
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        format!("error: {}", e)
+                    }
+                };
+                println!("  {:<16}  part {}: {}", author, part, outcome);
+            }
+        }
+    }
+
+    println!("{} ok, {} failed", ok, failed);
+    if failed > 0 {
+        return Err(format!("{} answers don't match", failed).into());
+    }
+    Ok(())
+}
+
+fn bench(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    use crate::bench::{format_change, format_duration, Baseline, Limits};
+    use std::time::Duration;
+
+    let days = match matches.values_of("day") {
+        Some(days) => days.map(str::parse).collect::<Result<Vec<u8>, _>>()?,
+        None => Vec::new(),
+    };
+    let limits = Limits {
+        runs: matches.value_of("runs").unwrap().parse()?,
+        time_limit: Duration::from_secs(matches.value_of("time-limit").unwrap().parse()?),
+    };
+    let input_dir = Path::new(matches.value_of("input-dir").unwrap());
+    let baseline_path = Path::new(matches.value_of("baseline").unwrap());
+    let mut baseline = Baseline::load(baseline_path)?;
+
+    println!(
+        "day  {:<6}  {:>4}  {:>8}  {:>8}  {:>8}  baseline",
+        "step", "runs", "min", "median", "max"
+    );
+    for (day, path) in runner::input_files(input_dir)? {
+        if !days.is_empty() && !days.contains(&day) {
+            continue;
+        }
+        let solution = match solution::find(day) {
+            Some(solution) => solution,
+            None => continue,
+        };
+        let input = runner::read_input(&path.to_string_lossy())?;
+        for (step, stats) in bench::bench(&solution, &input, limits)? {
+            let change = match baseline.get(day, step) {
+                Some(median) => format_change(median, stats.median),
+                None => "-".to_string(),
+            };
+            println!(
+                "{:>3}  {:<6}  {:>4}  {:>8}  {:>8}  {:>8}  {}",
+                day,
+                step,
+                stats.runs,
+                format_duration(stats.min),
+                format_duration(stats.median),
+                format_duration(stats.max),
+                change
+            );
+            if matches.is_present("save-baseline") {
+                baseline.set(day, step, stats.median);
+            }
+        }
+    }
+
+    if matches.is_present("save-baseline") {
+        baseline.save(baseline_path)?;
+    }
+    Ok(())
+}
+
+fn battle(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    use crate::day15::{self, Game, Rules};
+
+    let path = match matches.value_of("input") {
+        Some(path) => path.to_string(),
+        None => runner::input_path(Path::new(runner::INPUT_DIR), 15)
+            .to_string_lossy()
+            .into_owned(),
+    };
+    let rules = match matches.value_of("rules") {
+        Some(rules) => runner::read_input(rules)?
+            .parse()
+            .map_err(|e| format!("{}: {}", rules, e))?,
+        None => Rules::default(),
+    };
+    let mut game = Game::with_rules(&runner::read_input(&path)?, rules)?;
+    game.record();
+
+    if let Some(replay) = matches.value_of("replay") {
+        let events = day15::parse_log(&runner::read_input(replay)?)
+            .map_err(|e| format!("{}: {}", replay, e))?;
+        println!("{}", game);
+        for event in &events {
+            game.apply(event)
+                .map_err(|e| format!("{}: {}", replay, e))?;
+            if let day15::Event::RoundEnded(round) = event {
+                println!("After {} rounds:\n{}", round, game);
+            }
+        }
+        println!("Outcome: {}", game.outcome());
+        return Ok(());
+    }
+
+    if let Some(power) = matches.value_of("elf-power") {
+        game.set_elven_power(power.parse()?);
+    }
+    if matches.is_present("animate") {
+        viewer::animate(&mut game)?;
+    }
+    let outcome = game.run();
+    print!("{}", game);
+    println!("Combat ends after {} full rounds", game.rounds());
+    println!("Outcome: {}", outcome);
+
+    if let Some(log) = matches.value_of("log") {
+        let text: String = game.events().iter().map(|e| format!("{}\n", e)).collect();
+        std::fs::write(log, text).map_err(|e| format!("{}: {}", log, e))?;
+    }
+    if let Some(expected) = matches.value_of("diff") {
+        let events = day15::parse_log(&runner::read_input(expected)?)
+            .map_err(|e| format!("{}: {}", expected, e))?;
+        if let Some(difference) = day15::diff(&events, game.events()) {
+            return Err(format!("{} differs: {}", expected, difference).into());
+        }
+        println!("same events as {}", expected);
     }
     Ok(())
 }
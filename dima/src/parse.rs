@@ -0,0 +1,325 @@
+//! Error type of all parsers and a cursor for parsing the inputs line by line.
+//!
+//! Errors are located by line and column, both starting at 1, so that a broken input can be
+//! fixed without a debugger, e.g. `day19: line 7 col 1: unknown opcode "adx"`.
+
+use common::grid::{ErrorKind, ParseGridError};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// Something else than `expected` was found.
+    Expected { expected: String, found: String },
+    /// A token that is not one of the known ones, e.g. an opcode.
+    Unknown { what: &'static str, token: String },
+    /// The input is well-formed, but doesn't make sense.
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Set by `solution::Day::parse`.
+    pub day: Option<u8>,
+    pub line: usize,
+    pub col: usize,
+    pub reason: Reason,
+}
+
+impl ParseError {
+    pub fn new(line: usize, col: usize, reason: Reason) -> Self {
+        ParseError {
+            day: None,
+            line,
+            col,
+            reason,
+        }
+    }
+
+    pub fn unknown(line: usize, col: usize, what: &'static str, token: &str) -> Self {
+        let token = token.to_string();
+        Self::new(line, col, Reason::Unknown { what, token })
+    }
+
+    pub fn invalid(line: usize, col: usize, message: impl Into<String>) -> Self {
+        Self::new(line, col, Reason::Invalid(message.into()))
+    }
+
+    /// Error for an input that ends after `lines` lines.
+    pub fn eof(lines: usize, expected: impl Into<String>) -> Self {
+        let reason = Reason::Expected {
+            expected: expected.into(),
+            found: "end of input".to_string(),
+        };
+        Self::new(lines + 1, 1, reason)
+    }
+
+    /// Error at the byte `offset` into `input`, or at the char containing it.
+    pub fn at_offset(input: &str, offset: usize, reason: Reason) -> Self {
+        let (mut line, mut col) = (1, 1);
+        for (_, c) in input
+            .char_indices()
+            .take_while(|&(i, c)| i + c.len_utf8() <= offset)
+        {
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        Self::new(line, col, reason)
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Expected { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            Reason::Unknown { what, token } => write!(f, "unknown {} {:?}", what, token),
+            Reason::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(day) = self.day {
+            write!(f, "day{}: ", day)?;
+        }
+        write!(f, "line {} col {}: {}", self.line, self.col, self.reason)
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseGridError> for ParseError {
+    fn from(e: ParseGridError) -> Self {
+        let reason = match e.kind {
+            ErrorKind::InvalidCell(c) => Reason::Unknown {
+                what: "cell",
+                token: c.to_string(),
+            },
+            ErrorKind::Width { expected, found } => {
+                Reason::Invalid(format!("expected {} cells, found {}", expected, found))
+            }
+        };
+        ParseError::new(e.line, e.col, reason)
+    }
+}
+
+impl From<elfcode::ParseError> for ParseError {
+    fn from(e: elfcode::ParseError) -> Self {
+        ParseError::invalid(e.line, e.col, e.message)
+    }
+}
+
+/// Position in a line of the input.
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    line: usize,
+    col: usize,
+    rest: &'a str,
+}
+
+/// Cursors at the start of the lines of `input`.
+pub fn lines(input: &str) -> impl Iterator<Item = Cursor<'_>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| Cursor::new(i + 1, line))
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(line: usize, text: &'a str) -> Self {
+        Cursor {
+            line,
+            col: 1,
+            rest: text,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// The not yet parsed rest of the line.
+    pub fn rest(&self) -> &'a str {
+        self.rest
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    fn advance(&mut self, len: usize) -> &'a str {
+        let (token, rest) = self.rest.split_at(len);
+        self.col += token.chars().count();
+        self.rest = rest;
+        token
+    }
+
+    fn found(&self) -> String {
+        match self.rest.chars().next() {
+            None => "end of line".to_string(),
+            Some(_) => {
+                let token: String = self.rest.chars().take(12).collect();
+                format!("{:?}", token)
+            }
+        }
+    }
+
+    /// Error at the current position, found is the rest of the line.
+    pub fn expected(&self, expected: impl Into<String>) -> ParseError {
+        let reason = Reason::Expected {
+            expected: expected.into(),
+            found: self.found(),
+        };
+        ParseError::new(self.line, self.col, reason)
+    }
+
+    /// Error at the current position.
+    pub fn invalid(&self, message: impl Into<String>) -> ParseError {
+        ParseError::invalid(self.line, self.col, message)
+    }
+
+    pub fn skip_spaces(&mut self) {
+        let len = self.rest.len() - self.rest.trim_start_matches(' ').len();
+        self.advance(len);
+    }
+
+    /// Consumes `tag` after optional spaces.
+    pub fn tag(&mut self, tag: &str) -> Result<(), ParseError> {
+        self.skip_spaces();
+        if self.rest.starts_with(tag) {
+            self.advance(tag.len());
+            Ok(())
+        } else {
+            Err(self.expected(format!("{:?}", tag)))
+        }
+    }
+
+    /// Consumes the longest prefix whose chars satisfy `f`, which may be empty.
+    pub fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let len = self
+            .rest
+            .char_indices()
+            .find(|&(_, c)| !f(c))
+            .map_or(self.rest.len(), |(i, _)| i);
+        self.advance(len)
+    }
+
+    /// Consumes a word made of letters after optional spaces; returns it with its column.
+    pub fn word(&mut self) -> Result<(usize, &'a str), ParseError> {
+        self.skip_spaces();
+        let col = self.col;
+        match self.take_while(|c| c.is_ascii_alphabetic()) {
+            "" => Err(self.expected("a word")),
+            word => Ok((col, word)),
+        }
+    }
+
+    /// Consumes a signed integer after optional spaces.
+    pub fn number<T: FromStr>(&mut self) -> Result<T, ParseError> {
+        self.skip_spaces();
+        let sign = match self.rest.chars().next() {
+            Some('-') | Some('+') => 1,
+            _ => 0,
+        };
+        let len = sign
+            + self.rest[sign..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(self.rest.len() - sign);
+        match self.rest[..len].parse() {
+            Ok(number) if len > sign => {
+                self.advance(len);
+                Ok(number)
+            }
+            _ => Err(self.expected("a number")),
+        }
+    }
+
+    /// Consumes one char.
+    pub fn char(&mut self) -> Result<char, ParseError> {
+        let c = self
+            .rest
+            .chars()
+            .next()
+            .ok_or_else(|| self.expected("a char"))?;
+        self.advance(c.len_utf8());
+        Ok(c)
+    }
+
+    /// Fails unless the whole line was consumed, apart from trailing spaces.
+    pub fn end(&mut self) -> Result<(), ParseError> {
+        self.skip_spaces();
+        if self.rest.is_empty() {
+            Ok(())
+        } else {
+            Err(self.expected("end of line"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor() -> Result<(), ParseError> {
+        let mut cursor = Cursor::new(3, "pos=< -12,  3>, r=4 x");
+        cursor.tag("pos=<")?;
+        assert_eq!(cursor.number::<i64>()?, -12);
+        cursor.tag(",")?;
+        assert_eq!(cursor.number::<i64>()?, 3);
+        cursor.tag(">, r=")?;
+        assert_eq!(cursor.number::<u32>()?, 4);
+        assert_eq!(cursor.col(), 20);
+        assert_eq!(
+            cursor.clone().end().unwrap_err().to_string(),
+            r#"line 3 col 21: expected end of line, found "x""#
+        );
+        assert_eq!(cursor.word()?, (21, "x"));
+        cursor.end()
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut cursor = Cursor::new(1, "#ip x");
+        cursor.tag("#ip").unwrap();
+        assert_eq!(
+            cursor.number::<usize>().unwrap_err().to_string(),
+            r#"line 1 col 5: expected a number, found "x""#
+        );
+        assert!(Cursor::new(1, "-").number::<i32>().is_err());
+
+        let mut e = ParseError::unknown(7, 1, "opcode", "adx");
+        e.day = Some(19);
+        assert_eq!(
+            e.to_string(),
+            r#"day19: line 7 col 1: unknown opcode "adx""#
+        );
+        assert_eq!(
+            ParseError::eof(2, "a rule").to_string(),
+            "line 3 col 1: expected a rule, found end of input"
+        );
+        let e = ParseError::at_offset("ab\ncd", 4, Reason::Invalid("bad".into()));
+        assert_eq!((e.line, e.col), (2, 2));
+        // offsets inside a char point at it
+        let at = |offset| {
+            let e = ParseError::at_offset("é\nxé!", offset, Reason::Invalid("bad".into()));
+            (e.line, e.col)
+        };
+        assert_eq!(at(1), (1, 1));
+        assert_eq!(at(5), (2, 2));
+        assert_eq!(at(6), (2, 3));
+        assert_eq!(at(100), (2, 4));
+    }
+}
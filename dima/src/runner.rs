@@ -0,0 +1,224 @@
+use crate::solution::{Day, Part, Solved};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+
+use std::any::Any;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Directory with the puzzle inputs named `day<N>.txt`.
+pub const INPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/input");
+
+/// Reads an input file, or stdin if `path` is `-`.
+///
+/// Trailing newlines are stripped, since most of the parsers don't expect them.
+pub fn read_input(path: &str) -> Result<String, Box<dyn Error>> {
+    let mut input = String::new();
+    let res = if path == "-" {
+        io::stdin().read_to_string(&mut input)
+    } else {
+        File::open(path).and_then(|mut f| f.read_to_string(&mut input))
+    };
+    res.map_err(|e| format!("{}: {}", path, e))?;
+    let len = input.trim_end_matches(&['\n', '\r'][..]).len();
+    input.truncate(len);
+    Ok(input)
+}
+
+/// Deduces the day from an input file name like `day17.txt`.
+pub fn day_from_path(path: &Path) -> Option<u8> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^day(\d+)\.txt$").unwrap();
+    }
+    let file_name = path.file_name()?.to_str()?;
+    RE.captures(file_name)?.get(1)?.as_str().parse().ok()
+}
+
+/// Input file of a day in `dir`.
+pub fn input_path(dir: &Path, day: u8) -> PathBuf {
+    dir.join(format!("day{}.txt", day))
+}
+
+/// All input files in `dir` ordered by day.
+pub fn input_files(dir: &Path) -> io::Result<Vec<(u8, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(day) = day_from_path(&path) {
+            files.push((day, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Answers of the requested parts of a day.
+pub fn solve(day: &Day, input: &str, parts: &[Part]) -> Result<Vec<Solved>, Box<dyn Error>> {
+    let input = day.parse(input)?;
+    Ok(parts.iter().map(|&part| day.part(part, &input)).collect())
+}
+
+/// Answer as shown to the user.
+pub fn format_answer(answer: &Solved) -> String {
+    match answer {
+        Ok(Some(answer)) => answer.clone(),
+        Ok(None) => "-".to_string(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+/// Outcome of solving one part, printed as one line of JSON by `run --format json`.
+#[derive(Debug, Serialize)]
+pub struct Record {
+    pub day: u8,
+    pub part: u8,
+    pub answer: Option<String>,
+    /// Seconds spent on parsing the input and solving the part.
+    pub elapsed: f64,
+    pub error: Option<String>,
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown cause".to_string());
+    format!("panicked: {}", message)
+}
+
+/// Like `solve`, but times the parts and turns errors and panics into records instead of
+/// failing.
+pub fn solve_records(
+    day: &Day,
+    input: Result<String, Box<dyn Error>>,
+    parts: &[Part],
+) -> Vec<Record> {
+    let start = Instant::now();
+    let parsed = input.map_err(|e| e.to_string()).and_then(|input| {
+        panic::catch_unwind(AssertUnwindSafe(|| day.parse(&input)))
+            .map_err(panic_message)
+            .and_then(|parsed| parsed.map_err(|e| e.to_string()))
+    });
+    let parse_time = start.elapsed();
+
+    parts
+        .iter()
+        .map(|&part| {
+            let start = Instant::now();
+            let answer = parsed.as_ref().map_err(String::clone).and_then(|input| {
+                panic::catch_unwind(AssertUnwindSafe(|| day.part(part, input)))
+                    .map_err(panic_message)
+                    .and_then(|answer| answer)
+            });
+            let elapsed = parse_time + start.elapsed();
+            let (answer, error) = match answer {
+                Ok(answer) => (answer, None),
+                Err(e) => (None, Some(e)),
+            };
+            Record {
+                day: day.day,
+                part: part.into(),
+                answer,
+                elapsed: elapsed.as_secs_f64(),
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Answers of a day in the table printed by `print_table`, or the error why there are none.
+pub type Row = (u8, Result<Vec<Solved>, String>);
+
+/// Prints one row per day with a column per part.
+///
+/// Answers and errors spanning several lines (e.g. the message of day 10) are printed below their
+/// row.
+pub fn print_table(parts: &[Part], rows: &[Row]) {
+    let cell = |answer: &Solved| -> String {
+        match format_answer(answer) {
+            answer if answer.contains('\n') => "(see below)".into(),
+            answer => answer,
+        }
+    };
+
+    let mut widths: Vec<usize> = parts
+        .iter()
+        .map(|part| format!("part {}", part).len())
+        .collect();
+    for (_, answers) in rows {
+        if let Ok(answers) = answers {
+            for (width, answer) in widths.iter_mut().zip(answers) {
+                *width = (*width).max(cell(answer).len());
+            }
+        }
+    }
+
+    print!("day");
+    for (part, width) in parts.iter().zip(&widths) {
+        print!("  {:<width$}", format!("part {}", part), width = width);
+    }
+    println!();
+
+    for (day, answers) in rows {
+        print!("{:>3}", day);
+        match answers {
+            Ok(answers) => {
+                for (answer, width) in answers.iter().zip(&widths) {
+                    print!("  {:<width$}", cell(answer), width = width);
+                }
+                println!();
+                for (part, answer) in parts.iter().zip(answers) {
+                    let answer = format_answer(answer);
+                    if answer.contains('\n') {
+                        println!("     part {}:", part);
+                        for line in answer.lines() {
+                            println!("     {}", line);
+                        }
+                    }
+                }
+            }
+            Err(e) => println!("  error: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_from_path() {
+        assert_eq!(day_from_path(Path::new("input/day17.txt")), Some(17));
+        assert_eq!(day_from_path(Path::new("day1.txt")), Some(1));
+        assert_eq!(day_from_path(Path::new("input/day21.notes")), None);
+        assert_eq!(day_from_path(Path::new("input/my_day3.txt")), None);
+    }
+
+    #[test]
+    fn test_solve_records() {
+        let day = crate::solution::find(1).unwrap();
+        let records = solve_records(&day, Ok("+1\n-2\n+3\n+1".to_string()), &Part::ALL);
+        let answers: Vec<_> = records.iter().map(|r| r.answer.as_deref()).collect();
+        assert_eq!(answers, vec![Some("3"), Some("2")]);
+        assert!(records.iter().all(|r| r.error.is_none()));
+
+        let records = solve_records(&day, Err("no input".into()), &[Part::Two]);
+        assert_eq!(records[0].part, 2);
+        assert_eq!(records[0].answer, None);
+        assert_eq!(records[0].error.as_deref(), Some("no input"));
+    }
+
+    #[test]
+    fn test_input_files() -> Result<(), Box<dyn Error>> {
+        let files = input_files(Path::new(INPUT_DIR))?;
+        let days: Vec<u8> = files.iter().map(|(day, _)| *day).collect();
+        assert_eq!(days, (1..=25).collect::<Vec<_>>());
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use crate::parse::ParseError;
+use crate::{
+    day1, day10, day11, day12, day13, day14, day15, day16, day17, day18, day19, day2, day20, day21,
+    day22, day23, day24, day25, day3, day4, day5, day6, day7, day8, day9,
+};
+
+/// A day's puzzle, split into parsing the input and solving the two parts on the parsed input.
+pub trait Solution {
+    type Input;
+    type Part1: Answer;
+    type Part2: Answer;
+
+    fn parse(input: &str) -> Result<Self::Input, ParseError>;
+    fn part1(input: &Self::Input) -> Self::Part1;
+    fn part2(input: &Self::Input) -> Self::Part2;
+}
+
+/// Conversion of a part's result into the text one would submit on the puzzle page.
+pub trait Answer {
+    /// `None` if no answer was found.
+    fn answer(&self) -> Option<String>;
+
+    /// Why the part failed, if it did.
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
+
+macro_rules! display_answer {
+    ($($t:ty),*) => {
+        $(
+            impl Answer for $t {
+                fn answer(&self) -> Option<String> {
+                    Some(self.to_string())
+                }
+            }
+        )*
+    };
+}
+
+display_answer!(u32, u64, usize, i32, i64, isize, String);
+
+impl<A: Answer> Answer for Option<A> {
+    fn answer(&self) -> Option<String> {
+        self.as_ref().and_then(Answer::answer)
+    }
+}
+
+/// A part which fails with a reason, e.g. if the input doesn't determine the answer.
+impl<A: Answer, E: fmt::Display> Answer for Result<A, E> {
+    fn answer(&self) -> Option<String> {
+        self.as_ref().ok().and_then(Answer::answer)
+    }
+
+    fn error(&self) -> Option<String> {
+        self.as_ref().err().map(E::to_string)
+    }
+}
+
+impl<A: Answer, B: Answer> Answer for (A, B) {
+    fn answer(&self) -> Option<String> {
+        Some(format!("{},{}", self.0.answer()?, self.1.answer()?))
+    }
+}
+
+impl<A: Answer, B: Answer, C: Answer> Answer for (A, B, C) {
+    fn answer(&self) -> Option<String> {
+        Some(format!(
+            "{},{},{}",
+            self.0.answer()?,
+            self.1.answer()?,
+            self.2.answer()?
+        ))
+    }
+}
+
+/// A set is only an answer if it has exactly one element.
+impl<A: Answer + Eq + std::hash::Hash> Answer for HashSet<A> {
+    fn answer(&self) -> Option<String> {
+        if self.len() == 1 {
+            self.iter().next().and_then(Answer::answer)
+        } else {
+            None
+        }
+    }
+}
+
+/// Used for puzzles without a second part (day 25).
+impl Answer for () {
+    fn answer(&self) -> Option<String> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    One,
+    Two,
+}
+
+impl Part {
+    pub const ALL: [Part; 2] = [Part::One, Part::Two];
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Part::One => f.write_str("1"),
+            Part::Two => f.write_str("2"),
+        }
+    }
+}
+
+impl From<Part> for u8 {
+    fn from(part: Part) -> u8 {
+        match part {
+            Part::One => 1,
+            Part::Two => 2,
+        }
+    }
+}
+
+impl FromStr for Part {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(Part::One),
+            "2" => Ok(Part::Two),
+            _ => Err(format!("invalid part: {}", s)),
+        }
+    }
+}
+
+/// Parsed input of a day, as produced by `Day::parse`.
+pub type Parsed = Box<dyn Any>;
+
+/// Answer of a part, `Ok(None)` if none was found or `Err` with the reason the part failed.
+pub type Solved = Result<Option<String>, String>;
+
+fn solved<A: Answer>(result: A) -> Solved {
+    match result.error() {
+        Some(e) => Err(e),
+        None => Ok(result.answer()),
+    }
+}
+
+// Object safe version of `Solution`, which allows to store all days in one registry.
+trait Solver {
+    fn parse(&self, input: &str) -> Result<Parsed, ParseError>;
+    fn part(&self, part: Part, input: &Parsed) -> Solved;
+}
+
+struct Erased<S>(PhantomData<S>);
+
+impl<S: Solution> Solver for Erased<S>
+where
+    S::Input: 'static,
+{
+    fn parse(&self, input: &str) -> Result<Parsed, ParseError> {
+        let input = S::parse(input)?;
+        Ok(Box::new(input))
+    }
+
+    fn part(&self, part: Part, input: &Parsed) -> Solved {
+        let input = input
+            .downcast_ref::<S::Input>()
+            .expect("input was parsed by a different day");
+        match part {
+            Part::One => solved(S::part1(input)),
+            Part::Two => solved(S::part2(input)),
+        }
+    }
+}
+
+/// Entry of the registry.
+pub struct Day {
+    pub day: u8,
+    solver: Box<dyn Solver>,
+}
+
+impl Day {
+    fn new<S: Solution + 'static>(day: u8) -> Self
+    where
+        S::Input: 'static,
+    {
+        Self {
+            day,
+            solver: Box::new(Erased::<S>(PhantomData)),
+        }
+    }
+
+    /// Errors are attributed to this day.
+    pub fn parse(&self, input: &str) -> Result<Parsed, ParseError> {
+        self.solver.parse(input).map_err(|e| ParseError {
+            day: Some(self.day),
+            ..e
+        })
+    }
+
+    /// Panics if `input` was not parsed by this day.
+    pub fn part(&self, part: Part, input: &Parsed) -> Solved {
+        self.solver.part(part, input)
+    }
+}
+
+/// All solved days in ascending order.
+pub fn registry() -> Vec<Day> {
+    vec![
+        Day::new::<day1::Day1>(1),
+        Day::new::<day2::Day2>(2),
+        Day::new::<day3::Day3>(3),
+        Day::new::<day4::Day4>(4),
+        Day::new::<day5::Day5>(5),
+        Day::new::<day6::Day6>(6),
+        Day::new::<day7::Day7>(7),
+        Day::new::<day8::Day8>(8),
+        Day::new::<day9::Day9>(9),
+        Day::new::<day10::Day10>(10),
+        Day::new::<day11::Day11>(11),
+        Day::new::<day12::Day12>(12),
+        Day::new::<day13::Day13>(13),
+        Day::new::<day14::Day14>(14),
+        Day::new::<day15::Day15>(15),
+        Day::new::<day16::Day16>(16),
+        Day::new::<day17::Day17>(17),
+        Day::new::<day18::Day18>(18),
+        Day::new::<day19::Day19>(19),
+        Day::new::<day20::Day20>(20),
+        Day::new::<day21::Day21>(21),
+        Day::new::<day22::Day22>(22),
+        Day::new::<day23::Day23>(23),
+        Day::new::<day24::Day24>(24),
+        Day::new::<day25::Day25>(25),
+    ]
+}
+
+/// Looks up a day in the registry.
+pub fn find(day: u8) -> Option<Day> {
+    registry().into_iter().find(|d| d.day == day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_registry() {
+        let days: Vec<u8> = registry().iter().map(|d| d.day).collect();
+        assert_eq!(days, (1..=25).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_answer() {
+        assert_eq!(42usize.answer(), Some("42".into()));
+        assert_eq!((235usize, 85usize).answer(), Some("235,85".into()));
+        assert_eq!(Some(String::from("abc")).answer(), Some("abc".into()));
+        assert_eq!(None::<usize>.answer(), None);
+        assert_eq!(().answer(), None);
+    }
+
+    #[test]
+    fn test_day() -> Result<(), Box<dyn Error>> {
+        let day = find(8).unwrap();
+        let input = day.parse("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2")?;
+        assert_eq!(day.part(Part::One, &input), Ok(Some("138".into())));
+        assert_eq!(day.part(Part::Two, &input), Ok(Some("66".into())));
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+//! Checks solutions against the answers recorded in an `answers.toml` manifest.
+//!
+//! Entries without a `command` are solved in-process by the registered solution of the day.
+//! Entries with a `command` run it in `dir` (relative to the manifest) with `input` on stdin and
+//! extract the answer from its stdout with `pattern`. This allows to check other authors'
+//! binaries, e.g. `christian/answers.toml`.
+
+use crate::runner;
+use crate::solution::{self, Part};
+use regex::Regex;
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Manifest of the dima solutions.
+pub const ANSWERS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/answers.toml");
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    /// Default command of all entries.
+    pub command: Option<Vec<String>>,
+    /// Default input of all entries with a command.
+    pub input: Option<String>,
+    #[serde(rename = "answer", default)]
+    pub answers: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Entry {
+    pub day: u8,
+    pub part: u8,
+    pub answer: String,
+    /// Directory in which `command` runs, relative to the manifest.
+    pub dir: Option<String>,
+    pub command: Option<Vec<String>>,
+    /// Input file, relative to `dir`. Defaults to `input/day<N>.txt` for in-process entries.
+    pub input: Option<String>,
+    /// Regex applied to the output; the answer is made of its capture groups joined by `,`.
+    /// Defaults to the last non-empty line.
+    pub pattern: Option<String>,
+    /// Reason why the entry can't be checked automatically.
+    pub skip: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Match,
+    Mismatch(String),
+    Failed(String),
+    Skipped(String),
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+}
+
+/// Answers are compared modulo surrounding empty lines and whitespace around each line that is
+/// common to all lines, which only matter for the ASCII art of day 10.
+pub fn normalize(answer: &str) -> String {
+    let lines: Vec<&str> = answer.lines().map(str::trim_end).collect();
+    let first = lines.iter().position(|l| !l.is_empty()).unwrap_or(0);
+    let last = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(0, |i| i + 1);
+    let lines = &lines[first..last.max(first)];
+    let indent = lines
+        .iter()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or(""))
+        .collect();
+    lines.join("\n")
+}
+
+/// Extracts an answer from the output of a command.
+pub fn extract(output: &str, pattern: Option<&str>) -> Result<String, Box<dyn Error>> {
+    match pattern {
+        Some(pattern) => {
+            let re = Regex::new(pattern)?;
+            let caps = re
+                .captures(output)
+                .ok_or_else(|| format!("output doesn't match {:?}", pattern))?;
+            let groups: Vec<&str> = caps.iter().skip(1).flatten().map(|m| m.as_str()).collect();
+            Ok(if groups.is_empty() {
+                caps[0].to_string()
+            } else {
+                groups.join(",")
+            })
+        }
+        None => output
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(String::from)
+            .ok_or_else(|| "empty output".into()),
+    }
+}
+
+/// Runs `command` in `dir` with `input` on stdin and returns its stdout.
+pub fn run_command(command: &[String], dir: &Path, input: Option<&Path>) -> Result<String, String> {
+    let (program, args) = command.split_first().ok_or("empty command")?;
+    let stdin = match input {
+        Some(path) => File::open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .into(),
+        None => Stdio::null(),
+    };
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(stdin)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("{}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("`{}` failed: {}", command.join(" "), output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Checks all entries of the manifest at `path`.
+pub fn verify(path: &Path) -> Result<Vec<(Entry, Outcome)>, Box<dyn Error>> {
+    let manifest = Manifest::load(path)?;
+    let root = path.parent().unwrap_or_else(|| Path::new("."));
+
+    // Both parts are usually answered by the same run, so run each command and parse each input
+    // only once.
+    let mut outputs = HashMap::new();
+    let mut parsed = HashMap::new();
+
+    let mut results = Vec::new();
+    for entry in manifest.answers {
+        if let Some(reason) = &entry.skip {
+            let reason = reason.clone();
+            results.push((entry, Outcome::Skipped(reason)));
+            continue;
+        }
+
+        let dir = root.join(entry.dir.as_ref().map_or(".", String::as_str));
+        let answer = if let Some(command) = entry.command.as_ref().or(manifest.command.as_ref()) {
+            let input = entry.input.as_ref().or(manifest.input.as_ref());
+            let input = input.map(|input| dir.join(input));
+            let output = outputs
+                .entry((dir.clone(), command.clone(), input.clone()))
+                .or_insert_with(|| run_command(command, &dir, input.as_deref()));
+            output.clone().and_then(|output| {
+                extract(&output, entry.pattern.as_deref()).map_err(|e| e.to_string())
+            })
+        } else {
+            let part = entry.part.to_string().parse::<Part>()?;
+            let day =
+                solution::find(entry.day).ok_or_else(|| format!("invalid day: {}", entry.day))?;
+            let input = match &entry.input {
+                Some(input) => dir.join(input),
+                None => runner::input_path(&dir.join("input"), entry.day),
+            };
+            let input = parsed.entry(input.clone()).or_insert_with(|| {
+                runner::read_input(&input.to_string_lossy())
+                    .and_then(|input| day.parse(&input).map_err(Into::into))
+                    .map_err(|e| e.to_string())
+            });
+            match input {
+                Ok(input) => day
+                    .part(part, input)
+                    .and_then(|answer| answer.ok_or_else(|| "no answer".to_string())),
+                Err(e) => Err(e.clone()),
+            }
+        };
+
+        let outcome = match answer {
+            Ok(ref answer) if normalize(answer) == normalize(&entry.answer) => Outcome::Match,
+            Ok(answer) => Outcome::Mismatch(answer),
+            Err(e) => Outcome::Failed(e),
+        };
+        results.push((entry, outcome));
+    }
+    Ok(results)
+}
+
+/// Line-wise diff of an expected and an actual answer.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected = normalize(expected);
+    let actual = normalize(actual);
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => out += &format!("   {}\n", e),
+            (e, a) => {
+                if let Some(e) = e {
+                    out += &format!(" - {}\n", e);
+                }
+                if let Some(a) = a {
+                    out += &format!(" + {}\n", a);
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() -> Result<(), Box<dyn Error>> {
+        let output = "Sum: 569\nFirst repeated: 77666\n";
+        assert_eq!(extract(output, None)?, "First repeated: 77666");
+        assert_eq!(extract(output, Some(r"Sum: (\d+)"))?, "569");
+        assert_eq!(
+            extract("First crash @ (116,10)", Some(r"crash @ \((\d+),(\d+)\)"))?,
+            "116,10"
+        );
+        assert!(extract(output, Some("Water")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("\n  *  \n ** \n\n"), " *\n**");
+        assert_eq!(normalize("42"), "42");
+        assert_eq!(normalize(""), "");
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("a\nb", "a\nc"), "   a\n - b\n + c\n");
+    }
+
+    #[test]
+    fn test_answers() -> Result<(), Box<dyn Error>> {
+        let manifest = Manifest::load(Path::new(ANSWERS))?;
+        let mut parts: Vec<(u8, u8)> = manifest.answers.iter().map(|e| (e.day, e.part)).collect();
+        parts.sort();
+        parts.dedup();
+        assert_eq!(parts.len(), 49);
+        assert!(manifest.answers.iter().all(|e| e.command.is_none()));
+        Ok(())
+    }
+}
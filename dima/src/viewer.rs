@@ -0,0 +1,230 @@
+//! Terminal animation of the combat of day 15, round by round.
+//!
+//! Each frame shows the map with the npcs coloured by race, the paths of the moves of the last
+//! round up to the positions the npcs chose, and bars of the hit points beside each row.
+
+use crate::day15::Game;
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+use termion::color::{self, AnsiValue, Bg, Fg};
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use termion::{clear, cursor, style};
+
+/// Delays between the rounds in milliseconds, from slow to fast.
+const DELAYS: [u64; 7] = [1000, 500, 250, 100, 50, 20, 5];
+
+/// Width of the bars of hit points.
+const BAR: usize = 10;
+
+const HELP: &str = "space: pause  n: step  +/-: speed  q: quit";
+
+/// Colours of the races in the order of the rules: green elves, red goblins, then the others.
+const RACE_COLORS: [u8; 6] = [2, 1, 3, 4, 5, 6];
+
+const PATH_COLOR: AnsiValue = AnsiValue(8);
+const TARGET_COLOR: AnsiValue = AnsiValue(4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    paused: bool,
+    /// Index into `DELAYS`.
+    speed: usize,
+    finished: bool,
+}
+
+fn race_color(race: usize) -> Fg<AnsiValue> {
+    Fg(AnsiValue(RACE_COLORS[race % RACE_COLORS.len()]))
+}
+
+/// A bar of `BAR` chars filled in proportion to `hit_points` of `max`; at least one is filled
+/// while the npc is alive.
+fn hp_bar(hit_points: i32, max: i32) -> String {
+    let max = max.max(1) as usize;
+    let hit_points = hit_points.max(0) as usize;
+    let filled = (hit_points * BAR).div_ceil(max).min(BAR);
+    let mut bar = "█".repeat(filled);
+    bar.push_str(&"░".repeat(BAR - filled));
+    bar
+}
+
+/// The lines of a frame showing `game`.
+fn frame(game: &Game, state: State) -> Vec<String> {
+    let rules = game.rules();
+    let paths = game.paths();
+    let targets: HashSet<_> = paths.iter().filter_map(|path| path.last()).collect();
+    let on_path: HashSet<_> = paths.iter().flat_map(|path| &path[1..]).collect();
+
+    let status = if state.finished {
+        format!("combat ends, outcome {}", game.outcome())
+    } else if state.paused {
+        "paused".to_string()
+    } else {
+        format!("{} ms per round", DELAYS[state.speed])
+    };
+    let mut lines = vec![format!(
+        "{}Round {}{}  {}",
+        style::Bold,
+        game.rounds(),
+        style::Reset,
+        status
+    )];
+    for y in 0..game.height() {
+        let mut line = String::new();
+        let mut npcs = Vec::new();
+        for x in 0..game.width() {
+            let pos = (x, y);
+            let background = if targets.contains(&pos) {
+                Some(TARGET_COLOR)
+            } else if on_path.contains(&pos) {
+                Some(PATH_COLOR)
+            } else {
+                None
+            };
+            if let Some(background) = background {
+                line.push_str(&Bg(background).to_string());
+            }
+            match game.npc_at(pos) {
+                Some((race, hit_points)) => {
+                    let symbol = rules.races[race].symbol;
+                    line.push_str(&format!(
+                        "{}{}{}",
+                        race_color(race),
+                        symbol,
+                        Fg(color::Reset)
+                    ));
+                    npcs.push((race, hit_points));
+                }
+                None if game.is_wall(pos) => line.push('#'),
+                None => line.push('.'),
+            }
+            if background.is_some() {
+                line.push_str(&Bg(color::Reset).to_string());
+            }
+        }
+        for (race, hit_points) in npcs {
+            let race_rules = &rules.races[race];
+            line.push_str(&format!(
+                "   {}{} {}{} {:>3}",
+                race_color(race),
+                race_rules.symbol,
+                hp_bar(hit_points, race_rules.hit_points),
+                Fg(color::Reset),
+                hit_points
+            ));
+        }
+        lines.push(line);
+    }
+    lines.push(HELP.to_string());
+    lines
+}
+
+fn draw(screen: &mut impl Write, game: &Game, state: State) -> io::Result<()> {
+    write!(screen, "{}", clear::All)?;
+    for (y, line) in frame(game, state).iter().enumerate() {
+        write!(screen, "{}{}", cursor::Goto(1, y as u16 + 1), line)?;
+    }
+    screen.flush()
+}
+
+/// Plays the combat of `game` in the terminal until it is quit with `q`.
+///
+/// The game is left at the round shown last.
+pub fn animate(game: &mut Game) -> Result<(), Box<dyn Error>> {
+    let mut screen = AlternateScreen::from(io::stdout().into_raw_mode()?);
+    write!(screen, "{}", cursor::Hide)?;
+    let result = play(&mut screen, game);
+    write!(screen, "{}", cursor::Show)?;
+    result
+}
+
+fn play(screen: &mut impl Write, game: &mut Game) -> Result<(), Box<dyn Error>> {
+    let mut keys = termion::async_stdin().keys();
+    game.record_paths();
+    let mut state = State {
+        paused: false,
+        speed: 2,
+        finished: false,
+    };
+    loop {
+        draw(screen, game, state)?;
+        let deadline = Instant::now() + Duration::from_millis(DELAYS[state.speed]);
+        let mut step = false;
+        loop {
+            match keys.next() {
+                Some(key) => match key? {
+                    Key::Char('q') | Key::Esc | Key::Ctrl('c') => return Ok(()),
+                    Key::Char(' ') => state.paused = !state.paused,
+                    Key::Char('n') | Key::Right => step = true,
+                    Key::Char('+') => state.speed = (state.speed + 1).min(DELAYS.len() - 1),
+                    Key::Char('-') => state.speed = state.speed.saturating_sub(1),
+                    _ => continue,
+                },
+                None if state.paused || state.finished || Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+                None => step = true,
+            }
+            break;
+        }
+        // the other keys only change what is shown
+        if step && !state.finished {
+            state.finished = !game.round();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `line` without the escape sequences of colours and styles.
+    fn plain(line: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn test_hp_bar() {
+        assert_eq!(hp_bar(200, 200), "██████████");
+        assert_eq!(hp_bar(100, 200), "█████░░░░░");
+        assert_eq!(hp_bar(1, 200), "█░░░░░░░░░");
+        assert_eq!(hp_bar(-2, 200), "░░░░░░░░░░");
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut game =
+            Game::parse("#######\n#.G...#\n#...EG#\n#.#.#G#\n#..G#E#\n#.....#\n#######").unwrap();
+        game.record_paths();
+        game.round();
+        let state = State {
+            paused: true,
+            speed: 0,
+            finished: false,
+        };
+        let lines: Vec<String> = frame(&game, state).iter().map(|l| plain(l)).collect();
+        assert_eq!(lines[0], "Round 1  paused");
+        assert_eq!(lines[1], "#######");
+        assert_eq!(lines[2], "#..G..#   G ██████████ 200");
+        assert_eq!(lines[3], "#...EG#   E ██████████ 197   G ██████████ 197");
+        assert_eq!(lines.last().unwrap(), HELP);
+        // the goblin moved from 2,1 to 3,1 on its way to 4,1 next to the elf
+        assert!(game.paths().contains(&vec![(2, 1), (3, 1), (4, 1)]));
+        assert!(frame(&game, state)[2].contains(&Bg(TARGET_COLOR).to_string()));
+    }
+}
@@ -0,0 +1,484 @@
+//! Assembler for programs with labels, register names and jumps.
+//!
+//! ```text
+//! #ip 4
+//! .reg i r1          ; names register 1
+//! .reg n r2
+//! .tmp r3            ; register for the comparisons of conditional jumps
+//!     seti 10 0 n
+//! loop:
+//!     addi i 1 i
+//!     jgt n i loop   ; jumps to loop if n > i
+//!     halt
+//! ```
+//!
+//! Comments start with `;` or `//`. A register is written as `r2`, `2`, a name given by `.reg` or
+//! `ip` for the one bound to the instruction pointer. An immediate is a number or a label, which
+//! stands for the index of the instruction after it.
+//!
+//! The pseudo-instructions are lowered into instructions writing the `ip` register:
+//!
+//! - `jmp L` jumps to `L`, `halt` leaves the program
+//! - `jt r L` and `jf r L` jump if `r` is 1, or 0, as set by a comparison
+//! - `jeq`, `jne`, `jgt`, `jge`, `jlt` and `jle` with two registers or a register and a number,
+//!   e.g. `jlt i 10 L`, compare into the `.tmp` register and jump if the comparison holds
+//! - `jz r L` and `jnz r L` jump if `r` is 0, or not
+//!
+//! The output is the plain text format of `Program`.
+
+use crate::opcode::{Opcode, Operand, Value};
+use crate::program::{Instruction, ParseError, Program, Tokens};
+use std::collections::HashMap;
+
+/// An immediate operand.
+#[derive(Debug, Clone)]
+enum Imm {
+    Value(Value),
+    /// Index of the label plus `offset`.
+    Label {
+        name: String,
+        line: usize,
+        col: usize,
+        offset: Value,
+    },
+    /// Index of the end of the program minus 1, i.e. a jump target which halts.
+    Halt,
+}
+
+impl Imm {
+    fn minus_one(self) -> Imm {
+        match self {
+            Imm::Value(value) => Imm::Value(value - 1),
+            Imm::Label {
+                name,
+                line,
+                col,
+                offset,
+            } => Imm::Label {
+                name,
+                line,
+                col,
+                offset: offset - 1,
+            },
+            Imm::Halt => Imm::Halt,
+        }
+    }
+}
+
+/// Operand of a comparison.
+enum Arg {
+    Reg(usize),
+    Imm(Imm),
+}
+
+/// An instruction with unresolved labels.
+struct Pending {
+    op: Opcode,
+    a: Imm,
+    b: Imm,
+    c: usize,
+}
+
+fn is_identifier(token: &str) -> bool {
+    let mut chars = token.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+struct Assembler {
+    registers: usize,
+    ip: Option<usize>,
+    tmp: Option<usize>,
+    names: HashMap<String, usize>,
+    labels: HashMap<String, usize>,
+    code: Vec<Pending>,
+}
+
+impl Assembler {
+    fn next<'a>(
+        &self,
+        tokens: &mut Tokens<'a>,
+        what: &str,
+    ) -> Result<(usize, &'a str), ParseError> {
+        tokens.next().ok_or_else(|| tokens.expected(what))
+    }
+
+    /// The register `token` stands for, if any.
+    fn lookup(&self, token: &str) -> Option<usize> {
+        if token == "ip" {
+            return self.ip;
+        }
+        if let Some(&r) = self.names.get(token) {
+            return Some(r);
+        }
+        let digits = token.strip_prefix('r').unwrap_or(token);
+        digits.parse().ok().filter(|&r| r < self.registers)
+    }
+
+    fn register(&self, line: usize, tokens: &mut Tokens) -> Result<usize, ParseError> {
+        let (col, token) = self.next(tokens, "a register")?;
+        self.lookup(token)
+            .ok_or_else(|| ParseError::new(line, col, format!("no register {:?}", token)))
+    }
+
+    fn immediate(line: usize, col: usize, token: &str) -> Result<Imm, ParseError> {
+        if let Ok(value) = token.parse() {
+            Ok(Imm::Value(value))
+        } else if is_identifier(token) {
+            Ok(Imm::Label {
+                name: token.to_string(),
+                line,
+                col,
+                offset: 0,
+            })
+        } else {
+            let message = format!("expected a number or a label, found {:?}", token);
+            Err(ParseError::new(line, col, message))
+        }
+    }
+
+    fn imm(&self, line: usize, tokens: &mut Tokens) -> Result<Imm, ParseError> {
+        let (col, token) = self.next(tokens, "a number or a label")?;
+        Self::immediate(line, col, token)
+    }
+
+    /// A register, or a number or a label for numbers which aren't registers.
+    fn arg(&self, line: usize, tokens: &mut Tokens) -> Result<Arg, ParseError> {
+        let (col, token) = self.next(tokens, "a register or a number")?;
+        let is_number = token.parse::<Value>().is_ok();
+        match self.lookup(token) {
+            Some(r) if !is_number => Ok(Arg::Reg(r)),
+            _ => Self::immediate(line, col, token).map(Arg::Imm),
+        }
+    }
+
+    fn push(&mut self, op: Opcode, a: Imm, b: Imm, c: usize) {
+        self.code.push(Pending { op, a, b, c });
+    }
+
+    fn ip(&self, line: usize, col: usize) -> Result<usize, ParseError> {
+        self.ip
+            .ok_or_else(|| ParseError::new(line, col, "jumps need an #ip line"))
+    }
+
+    fn jump(&mut self, ip: usize, target: Imm) {
+        self.push(Opcode::Seti, target.minus_one(), Imm::Value(0), ip);
+    }
+
+    /// Jumps to `target` if `r` is 1, or 0 if `jump_if` is false.
+    fn branch(&mut self, ip: usize, r: usize, jump_if: bool, target: Imm) {
+        let (r, ip_value) = (Imm::Value(r as Value), Imm::Value(ip as Value));
+        self.push(Opcode::Addr, r, ip_value.clone(), ip);
+        if jump_if {
+            // skip the jump if r was 0
+            self.push(Opcode::Addi, ip_value, Imm::Value(1), ip);
+        }
+        self.jump(ip, target);
+    }
+
+    /// `lhs > rhs` or `lhs == rhs` into the `.tmp` register.
+    fn compare(
+        &mut self,
+        line: usize,
+        col: usize,
+        greater: bool,
+        lhs: Arg,
+        rhs: Arg,
+    ) -> Result<usize, ParseError> {
+        let tmp = self
+            .tmp
+            .ok_or_else(|| ParseError::new(line, col, "comparing jumps need a .tmp register"))?;
+        let reg = |r: usize| Imm::Value(r as Value);
+        let (rr, ri, ir) = if greater {
+            (Opcode::Gtrr, Opcode::Gtri, Opcode::Gtir)
+        } else {
+            (Opcode::Eqrr, Opcode::Eqri, Opcode::Eqir)
+        };
+        let (op, a, b) = match (lhs, rhs) {
+            (Arg::Reg(a), Arg::Reg(b)) => (rr, reg(a), reg(b)),
+            (Arg::Reg(a), Arg::Imm(b)) => (ri, reg(a), b),
+            (Arg::Imm(a), Arg::Reg(b)) => (ir, a, reg(b)),
+            (Arg::Imm(_), Arg::Imm(_)) => {
+                return Err(ParseError::new(line, col, "can't compare two constants"))
+            }
+        };
+        self.push(op, a, b, tmp);
+        Ok(tmp)
+    }
+
+    /// Lowers the pseudo-instruction `name`, false if there is none of that name.
+    fn pseudo(
+        &mut self,
+        line: usize,
+        col: usize,
+        name: &str,
+        tokens: &mut Tokens,
+    ) -> Result<bool, ParseError> {
+        let zero = || Arg::Imm(Imm::Value(0));
+        // (greater, swap, jump if the comparison holds)
+        let comparison = match name {
+            "jmp" | "halt" | "jt" | "jf" => None,
+            "jeq" | "jz" => Some((false, false, true)),
+            "jne" | "jnz" => Some((false, false, false)),
+            "jgt" => Some((true, false, true)),
+            "jle" => Some((true, false, false)),
+            "jlt" => Some((true, true, true)),
+            "jge" => Some((true, true, false)),
+            _ => return Ok(false),
+        };
+        let ip = self.ip(line, col)?;
+        match (name, comparison) {
+            ("jmp", _) => {
+                let target = self.imm(line, tokens)?;
+                self.jump(ip, target);
+            }
+            ("halt", _) => self.jump(ip, Imm::Halt),
+            ("jt", _) | ("jf", _) => {
+                let r = self.register(line, tokens)?;
+                let target = self.imm(line, tokens)?;
+                self.branch(ip, r, name == "jt", target);
+            }
+            (_, Some((greater, swap, jump_if))) => {
+                let lhs = self.arg(line, tokens)?;
+                let rhs = if name == "jz" || name == "jnz" {
+                    zero()
+                } else {
+                    self.arg(line, tokens)?
+                };
+                let target = self.imm(line, tokens)?;
+                let (lhs, rhs) = if swap { (rhs, lhs) } else { (lhs, rhs) };
+                let tmp = self.compare(line, col, greater, lhs, rhs)?;
+                self.branch(ip, tmp, jump_if, target);
+            }
+            (_, None) => unreachable!("all pseudo-instructions are handled"),
+        }
+        Ok(true)
+    }
+
+    fn line(&mut self, line: usize, text: &str) -> Result<(), ParseError> {
+        let code = [";", "//"]
+            .iter()
+            .filter_map(|comment| text.find(comment))
+            .min()
+            .map_or(text, |start| &text[..start]);
+        let mut tokens = Tokens::new(line, code);
+        let (mut col, mut first) = match tokens.next() {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        if let Some(label) = first.strip_suffix(':') {
+            if !is_identifier(label) || self.lookup(label).is_some() {
+                let message = format!("invalid label {:?}", label);
+                return Err(ParseError::new(line, col, message));
+            }
+            if self
+                .labels
+                .insert(label.to_string(), self.code.len())
+                .is_some()
+            {
+                let message = format!("label {:?} is defined twice", label);
+                return Err(ParseError::new(line, col, message));
+            }
+            match tokens.next() {
+                Some(token) => (col, first) = token,
+                None => return Ok(()),
+            }
+        }
+        match first {
+            "#ip" if self.ip.is_none() && self.code.is_empty() => {
+                self.ip = Some(self.register(line, &mut tokens)?);
+            }
+            "#ip" => {
+                let message = "#ip must come once before the instructions";
+                return Err(ParseError::new(line, col, message));
+            }
+            ".reg" => {
+                let (col, name) = self.next(&mut tokens, "a name")?;
+                if !is_identifier(name) || name == "ip" || self.lookup(name).is_some() {
+                    let message = format!("invalid register name {:?}", name);
+                    return Err(ParseError::new(line, col, message));
+                }
+                let r = self.register(line, &mut tokens)?;
+                self.names.insert(name.to_string(), r);
+            }
+            ".tmp" => self.tmp = Some(self.register(line, &mut tokens)?),
+            name if self.pseudo(line, col, name, &mut tokens)? => (),
+            name => {
+                let op: Opcode = name.parse().map_err(|e| ParseError::new(line, col, e))?;
+                let (kind_a, kind_b) = op.operands();
+                let mut operand = |kind| match kind {
+                    Operand::Register => self
+                        .register(line, &mut tokens)
+                        .map(|r| Imm::Value(r as Value)),
+                    Operand::Immediate | Operand::Ignored => self.imm(line, &mut tokens),
+                };
+                let a = operand(kind_a)?;
+                let b = operand(kind_b)?;
+                let c = self.register(line, &mut tokens)?;
+                self.push(op, a, b, c);
+            }
+        }
+        tokens.end()
+    }
+
+    fn resolve(&self, imm: &Imm) -> Result<Value, ParseError> {
+        match imm {
+            Imm::Value(value) => Ok(*value),
+            Imm::Label {
+                name,
+                line,
+                col,
+                offset,
+            } => match self.labels.get(name) {
+                Some(&idx) => Ok(idx as Value + offset),
+                None => Err(ParseError::new(
+                    *line,
+                    *col,
+                    format!("unknown label {:?}", name),
+                )),
+            },
+            Imm::Halt => Ok(self.code.len() as Value - 1),
+        }
+    }
+}
+
+/// Assembles `source` for a device with `registers` registers.
+pub fn assemble(source: &str, registers: usize) -> Result<Program, ParseError> {
+    let mut asm = Assembler {
+        registers,
+        ip: None,
+        tmp: None,
+        names: HashMap::new(),
+        labels: HashMap::new(),
+        code: Vec::new(),
+    };
+    for (i, text) in source.lines().enumerate() {
+        asm.line(i + 1, text)?;
+    }
+    let instructions = asm
+        .code
+        .iter()
+        .map(|pending| {
+            let a = asm.resolve(&pending.a)?;
+            let b = asm.resolve(&pending.b)?;
+            Ok(Instruction::new(pending.op, a, b, pending.c as Value))
+        })
+        .collect::<Result<_, ParseError>>()?;
+    Ok(Program {
+        ip: asm.ip,
+        instructions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::DAY21;
+    use crate::vm::Vm;
+
+    fn run(program: &Program, regs: &[Value]) -> Vec<Value> {
+        let mut vm = Vm::new(program, regs.len()).unwrap();
+        vm.registers_mut().copy_from_slice(regs);
+        vm.run(10_000);
+        assert!(vm.is_halted());
+        vm.registers().to_vec()
+    }
+
+    #[test]
+    fn test_assemble() {
+        let source = "\
+#ip 4
+.reg product r0
+.reg i r1
+.tmp r3
+    seti 0 0 product   // r2 * r5 by adding
+loop:
+    jge i r2 done
+    addr product r5 product
+    addi i 1 i
+    jmp loop
+done: halt
+";
+        let program = assemble(source, 6).unwrap();
+        assert_eq!(
+            program.to_string(),
+            "#ip 4\nseti 0 0 0\ngtrr 2 1 3\naddr 3 4 4\nseti 6 0 4\naddr 0 5 0\naddi 1 1 1\n\
+             seti 0 0 4\nseti 7 0 4\n"
+        );
+        assert_eq!(Program::parse(&program.to_string(), 6), Ok(program.clone()));
+        assert_eq!(run(&program, &[0, 0, 7, 0, 0, 6])[0], 42);
+
+        // plain programs are assembled as they are
+        let plain: Program = DAY21.parse().unwrap();
+        assert_eq!(assemble(DAY21, 6), Ok(plain));
+    }
+
+    type Condition = fn(Value, Value) -> bool;
+
+    #[test]
+    fn test_jumps() {
+        let jumps: [(&str, Condition); 8] = [
+            ("jeq r1 r2", |x, y| x == y),
+            ("jne r1 r2", |x, y| x != y),
+            ("jgt r1 r2", |x, y| x > y),
+            ("jge r1 r2", |x, y| x >= y),
+            ("jlt r1 r2", |x, y| x < y),
+            ("jle r1 1", |x, _| x <= 1),
+            ("jz r1", |x, _| x == 0),
+            ("jnz r1", |x, _| x != 0),
+        ];
+        for (jump, expected) in jumps.iter() {
+            let source = format!(
+                "#ip 5\n.tmp r3\n{} yes\nseti 2 0 0\nhalt\nyes: seti 1 0 0\n",
+                jump
+            );
+            let program = assemble(&source, 6).unwrap();
+            for x in -1..=2 {
+                for y in -1..=2 {
+                    let regs = run(&program, &[0, x, y, 0, 0, 0]);
+                    let jumped = regs[0] == 1;
+                    assert_eq!(jumped, expected(x, y), "{} with {} {}", jump, x, y);
+                }
+            }
+        }
+        let program = assemble("#ip 2\njt r1 yes\nhalt\nyes: seti 1 0 0", 3).unwrap();
+        assert_eq!(run(&program, &[0, 1, 0])[0], 1);
+        assert_eq!(run(&program, &[0, 0, 0])[0], 0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| assemble(source, 6).unwrap_err().to_string();
+        assert_eq!(
+            error("#ip 4\njmp nowhere"),
+            r#"line 2 col 5: unknown label "nowhere""#
+        );
+        assert_eq!(
+            error("#ip 4\nx: seti 0 0 1\nx: halt"),
+            r#"line 3 col 1: label "x" is defined twice"#
+        );
+        assert_eq!(
+            error("seti 0 0 1\njmp 0"),
+            "line 2 col 1: jumps need an #ip line"
+        );
+        assert_eq!(
+            error("#ip 4\njz r1 0"),
+            "line 2 col 1: comparing jumps need a .tmp register"
+        );
+        assert_eq!(
+            error("#ip 4\n.tmp r3\njeq 1 2 0"),
+            "line 3 col 1: can't compare two constants"
+        );
+        assert_eq!(error("addr x 1 2"), r#"line 1 col 6: no register "x""#);
+        assert_eq!(
+            error("seti 0 0 1\n#ip 4"),
+            "line 2 col 1: #ip must come once before the instructions"
+        );
+        assert_eq!(
+            error(".reg r2 r1"),
+            r#"line 1 col 6: invalid register name "r2""#
+        );
+    }
+}
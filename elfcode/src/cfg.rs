@@ -0,0 +1,473 @@
+//! Control flow graph of a program.
+//!
+//! An instruction writing the instruction pointer register is a jump to the written value plus
+//! one. It is a branch if it adds a comparison result computed by the instruction before, like
+//!
+//! ```text
+//! gtrr 1 5 1
+//! addr 1 4 4
+//! ```
+//!
+//! and a computed jump if the target depends on other registers. Blocks consisting of a single
+//! unconditional jump are skipped by the edges of the graph.
+
+use crate::expr::{Atom, BinOp, Cond, Expr};
+use crate::opcode::Value;
+use crate::program::Program;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// How a block is left. Targets are block indices, `Cfg::halt` for leaving the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exit {
+    Goto(usize),
+    /// The comparison of `cond` is the last instruction of the body.
+    Branch {
+        cond: Cond,
+        then: usize,
+        els: usize,
+    },
+    /// Jumps to the instruction `expr + 1`, which is in one of the `targets`.
+    Computed {
+        expr: Expr,
+        targets: Vec<usize>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// Indices of the instructions.
+    pub range: Range<usize>,
+    /// Indices of the instructions except the jump at the end.
+    pub body: Range<usize>,
+    pub exit: Exit,
+}
+
+impl Block {
+    /// Whether the block only jumps somewhere else.
+    pub fn is_trampoline(&self) -> bool {
+        self.body.is_empty() && matches!(self.exit, Exit::Goto(_))
+    }
+}
+
+/// A natural loop, i.e. a header and all blocks which can go back to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub header: usize,
+    pub body: BTreeSet<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+    pub entry: usize,
+}
+
+/// Jump at the end of a block, in instruction indices.
+enum Jump {
+    Goto(usize),
+    Branch { cond: Cond, then: usize, els: usize },
+    Computed { expr: Expr, relative: bool },
+}
+
+fn jump(program: &Program, pc: usize) -> Option<Jump> {
+    let ip = program.ip?;
+    let instr = &program.instructions[pc];
+    if instr.c as usize != ip {
+        return None;
+    }
+    let target = |value: Value| {
+        Some(value + 1)
+            .filter(|&t| t >= 0 && (t as usize) < program.len())
+            .map_or(program.len(), |t| t as usize)
+    };
+    let expr = Expr::of(instr, Some(ip), pc);
+    Some(match expr {
+        Expr::Atom(Atom::Const(value)) => Jump::Goto(target(value)),
+        Expr::Binary(BinOp::Add, Atom::Const(base), Atom::Reg(r))
+        | Expr::Binary(BinOp::Add, Atom::Reg(r), Atom::Const(base))
+            if base == pc as Value =>
+        {
+            let comparison = pc
+                .checked_sub(1)
+                .map(|prev| &program.instructions[prev])
+                .filter(|prev| prev.c as usize == r)
+                .and_then(|prev| Cond::of(Expr::of(prev, Some(ip), pc - 1)));
+            match comparison {
+                Some(cond) => Jump::Branch {
+                    cond,
+                    then: target(base + 1),
+                    els: pc + 1,
+                },
+                None => Jump::Computed {
+                    expr,
+                    relative: true,
+                },
+            }
+        }
+        _ => Jump::Computed {
+            expr,
+            relative: false,
+        },
+    })
+}
+
+impl Cfg {
+    pub fn new(program: &Program) -> Self {
+        let len = program.len();
+        let mut jumps: Vec<Option<Jump>> = (0..len).map(|pc| jump(program, pc)).collect();
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (pc, jump) in jumps.iter().enumerate() {
+            match jump {
+                Some(Jump::Goto(target)) => {
+                    leaders.insert(*target);
+                }
+                Some(Jump::Branch { then, els, .. }) => {
+                    leaders.insert(*then);
+                    leaders.insert(*els);
+                }
+                Some(Jump::Computed { .. }) | None => (),
+            }
+            if jump.is_some() {
+                leaders.insert(pc + 1);
+            }
+        }
+        // a branch reached by a jump doesn't know where its condition comes from
+        for (pc, jump) in jumps.iter_mut().enumerate() {
+            if let Some(Jump::Branch { .. }) = jump {
+                if leaders.contains(&pc) {
+                    let offset = Atom::Reg(program.instructions[pc - 1].c as usize);
+                    *jump = Some(Jump::Computed {
+                        expr: Expr::Binary(BinOp::Add, Atom::Const(pc as Value), offset),
+                        relative: true,
+                    });
+                }
+            }
+        }
+        let starts: Vec<usize> = leaders.into_iter().filter(|&pc| pc < len).collect();
+        let block_of = |pc: usize| starts.binary_search(&pc).unwrap_or(starts.len());
+
+        let mut blocks: Vec<Block> = starts
+            .iter()
+            .enumerate()
+            .map(|(idx, &start)| {
+                let end = starts.get(idx + 1).cloned().unwrap_or(len);
+                let (body, exit) = match jumps[end - 1].take() {
+                    None => (start..end, Exit::Goto(block_of(end))),
+                    Some(jump) => {
+                        let exit = match jump {
+                            Jump::Goto(target) => Exit::Goto(block_of(target)),
+                            Jump::Branch { cond, then, els } => Exit::Branch {
+                                cond,
+                                then: block_of(then),
+                                els: block_of(els),
+                            },
+                            Jump::Computed { expr, relative } => {
+                                let first = if relative { idx + 1 } else { 0 };
+                                let targets = (first..starts.len()).collect();
+                                Exit::Computed { expr, targets }
+                            }
+                        };
+                        (start..end - 1, exit)
+                    }
+                };
+                Block {
+                    range: start..end,
+                    body,
+                    exit,
+                }
+            })
+            .collect();
+
+        let thread = |blocks: &[Block], mut b: usize| {
+            for _ in 0..blocks.len() {
+                match blocks.get(b) {
+                    Some(Block {
+                        body,
+                        exit: Exit::Goto(target),
+                        ..
+                    }) if body.is_empty() => b = *target,
+                    _ => break,
+                }
+            }
+            b
+        };
+        for idx in 0..blocks.len() {
+            let exit = match blocks[idx].exit.clone() {
+                Exit::Goto(target) => Exit::Goto(thread(&blocks, target)),
+                Exit::Branch { cond, then, els } => {
+                    let (then, els) = (thread(&blocks, then), thread(&blocks, els));
+                    if then == els {
+                        Exit::Goto(then)
+                    } else {
+                        Exit::Branch { cond, then, els }
+                    }
+                }
+                exit @ Exit::Computed { .. } => exit,
+            };
+            blocks[idx].exit = exit;
+        }
+        let entry = thread(&blocks, 0);
+        Cfg { blocks, entry }
+    }
+
+    /// The node for leaving the program.
+    pub fn halt(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Number of nodes, i.e. the blocks and `halt`.
+    pub fn node_count(&self) -> usize {
+        self.blocks.len() + 1
+    }
+
+    /// Block starting at the instruction `pc`.
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        self.blocks.iter().position(|block| block.range.start == pc)
+    }
+
+    pub fn successors(&self, node: usize) -> Vec<usize> {
+        match self.blocks.get(node).map(|block| &block.exit) {
+            None => Vec::new(),
+            Some(Exit::Goto(target)) => vec![*target],
+            Some(Exit::Branch { then, els, .. }) => vec![*then, *els],
+            Some(Exit::Computed { targets, .. }) => targets.clone(),
+        }
+    }
+
+    fn graph(&self) -> Vec<Vec<usize>> {
+        (0..self.node_count())
+            .map(|node| self.successors(node))
+            .collect()
+    }
+
+    /// Graph for the dominators with a virtual root as last node, which has edges to the entry
+    /// and the targets of the computed jumps instead of the computed jumps themselves.
+    ///
+    /// This way code only reached by computed jumps has loops, while a computed jump doesn't
+    /// make a loop of everything before it.
+    fn rooted_graph(&self) -> Vec<Vec<usize>> {
+        let mut roots = vec![self.entry];
+        let mut graph: Vec<Vec<usize>> = (0..self.node_count())
+            .map(
+                |node| match self.blocks.get(node).map(|block| &block.exit) {
+                    Some(Exit::Computed { targets, .. }) => {
+                        roots.extend(targets.iter().cloned());
+                        Vec::new()
+                    }
+                    _ => self.successors(node),
+                },
+            )
+            .collect();
+        graph.push(roots);
+        graph
+    }
+
+    /// Immediate dominator of each node; `None` for unreachable nodes and for the entry and the
+    /// targets of computed jumps unless they are dominated by other nodes.
+    pub fn dominators(&self) -> Vec<Option<usize>> {
+        let root = self.node_count();
+        let mut idom = immediate_dominators(root, &self.rooted_graph());
+        idom.pop();
+        for node in &mut idom {
+            *node = node.filter(|&node| node != root);
+        }
+        idom
+    }
+
+    /// Immediate post-dominator of each node, `None` for `halt` and nodes which never halt.
+    pub fn post_dominators(&self) -> Vec<Option<usize>> {
+        let mut reversed = vec![Vec::new(); self.node_count()];
+        for (node, successors) in self.graph().into_iter().enumerate() {
+            for succ in successors {
+                reversed[succ].push(node);
+            }
+        }
+        immediate_dominators(self.halt(), &reversed)
+    }
+
+    /// Natural loops ordered by their headers.
+    pub fn loops(&self) -> Vec<Loop> {
+        let graph = self.rooted_graph();
+        let root = self.node_count();
+        let idom = immediate_dominators(root, &graph);
+        let mut predecessors = vec![Vec::new(); graph.len()];
+        for (node, successors) in graph.iter().enumerate() {
+            for &succ in successors {
+                predecessors[succ].push(node);
+            }
+        }
+        let reachable = |node: usize| node != root && idom[node].is_some();
+
+        let mut loops: Vec<Loop> = Vec::new();
+        for (node, successors) in graph.iter().enumerate() {
+            for &header in successors {
+                if !reachable(node) || !dominates(&idom, header, node) {
+                    continue;
+                }
+                let idx = match loops.iter().position(|l| l.header == header) {
+                    Some(idx) => idx,
+                    None => {
+                        let mut body = BTreeSet::new();
+                        body.insert(header);
+                        loops.push(Loop { header, body });
+                        loops.len() - 1
+                    }
+                };
+                let body = &mut loops[idx].body;
+                let mut stack = vec![node];
+                while let Some(node) = stack.pop() {
+                    if body.insert(node) {
+                        stack.extend(predecessors[node].iter().filter(|&&p| reachable(p)));
+                    }
+                }
+            }
+        }
+        loops.sort_by_key(|l| l.header);
+        loops
+    }
+}
+
+/// Whether `a` dominates `b` according to the immediate dominators `idom`.
+pub fn dominates(idom: &[Option<usize>], a: usize, mut b: usize) -> bool {
+    loop {
+        if a == b {
+            return true;
+        }
+        match idom[b] {
+            Some(next) => b = next,
+            None => return false,
+        }
+    }
+}
+
+/// Cooper, Harvey and Kennedy: "A Simple, Fast Dominance Algorithm".
+fn immediate_dominators(root: usize, successors: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let len = successors.len();
+    let mut postorder = Vec::new();
+    let mut visited = vec![false; len];
+    visited[root] = true;
+    let mut stack = vec![(root, 0)];
+    while let Some((node, i)) = stack.pop() {
+        match successors[node].get(i) {
+            Some(&succ) => {
+                stack.push((node, i + 1));
+                if !visited[succ] {
+                    visited[succ] = true;
+                    stack.push((succ, 0));
+                }
+            }
+            None => postorder.push(node),
+        }
+    }
+    let mut number = vec![0; len];
+    for (i, &node) in postorder.iter().enumerate() {
+        number[node] = i;
+    }
+    let mut predecessors = vec![Vec::new(); len];
+    for &node in &postorder {
+        for &succ in &successors[node] {
+            predecessors[succ].push(node);
+        }
+    }
+
+    let mut idom = vec![None; len];
+    idom[root] = Some(root);
+    let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while number[a] < number[b] {
+                a = idom[a].expect("processed node");
+            }
+            while number[b] < number[a] {
+                b = idom[b].expect("processed node");
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        // reverse postorder without the root, which is the last node in postorder
+        for &node in postorder.iter().rev().skip(1) {
+            let mut new = None;
+            for &pred in predecessors[node].iter().filter(|&&p| idom[p].is_some()) {
+                new = Some(new.map_or(pred, |other| intersect(&idom, pred, other)));
+            }
+            if new != idom[node] {
+                idom[node] = new;
+                changed = true;
+            }
+        }
+    }
+    idom[root] = None;
+    idom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::DAY21;
+
+    #[test]
+    fn test_blocks() {
+        let program: Program = DAY21.parse().unwrap();
+        let cfg = Cfg::new(&program);
+        let block = |pc| cfg.block_at(pc).unwrap();
+        assert_eq!(cfg.entry, 0);
+        assert_eq!(cfg.blocks[block(1)].range, 1..4);
+        assert_eq!(cfg.blocks[block(1)].body, 1..3);
+        // `seti 0 0 4` at 4 is skipped
+        match &cfg.blocks[block(1)].exit {
+            Exit::Branch { cond, then, els } => {
+                assert_eq!(cond.to_string(), "r2 == 72");
+                assert_eq!((*then, *els), (block(5), block(1)));
+            }
+            exit => panic!("unexpected exit {:?}", exit),
+        }
+        assert!(cfg.blocks[block(4)].is_trampoline());
+        assert_eq!(cfg.successors(block(28)), vec![cfg.halt(), block(6)]);
+    }
+
+    #[test]
+    fn test_loops() {
+        let program: Program = DAY21.parse().unwrap();
+        let cfg = Cfg::new(&program);
+        let block = |pc| cfg.block_at(pc).unwrap();
+        let headers: Vec<_> = cfg.loops().iter().map(|l| l.header).collect();
+        assert_eq!(headers, vec![block(1), block(6), block(8), block(18)]);
+        let inner = &cfg.loops()[3];
+        assert_eq!(
+            inner.body,
+            vec![block(18), block(24)]
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+        );
+
+        let idom = cfg.dominators();
+        assert_eq!(idom[block(28)], Some(block(8)));
+        assert!(dominates(&idom, block(6), block(26)));
+        let ipdom = cfg.post_dominators();
+        assert_eq!(ipdom[block(17)], Some(block(18)));
+        assert_eq!(ipdom[block(28)], Some(cfg.halt()));
+    }
+
+    #[test]
+    fn test_computed() {
+        let program: Program = "#ip 1\naddr 1 0 1\nseti 0 0 0\nseti 5 0 0".parse().unwrap();
+        let cfg = Cfg::new(&program);
+        assert_eq!(cfg.blocks.len(), 2);
+        assert_eq!(
+            cfg.blocks[0].exit,
+            Exit::Computed {
+                expr: Expr::Binary(BinOp::Add, Atom::Const(0), Atom::Reg(0)),
+                targets: vec![1],
+            }
+        );
+        // no instruction pointer, no jumps
+        let program: Program = "seti 0 0 0\nseti 5 0 0".parse().unwrap();
+        let cfg = Cfg::new(&program);
+        assert_eq!(cfg.blocks.len(), 1);
+        assert_eq!(cfg.blocks[0].exit, Exit::Goto(cfg.halt()));
+    }
+}
@@ -0,0 +1,578 @@
+//! Debugger for programs on the device, driven by text commands.
+//!
+//! Besides stepping and running to breakpoints, the debugger keeps a ring buffer of the states
+//! before the last instructions, so that steps can be undone with `back`.
+
+use crate::expr::Atom;
+use crate::opcode::Value;
+use crate::vm::{State, Vm};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of past states kept by default.
+pub const DEFAULT_HISTORY: usize = 10_000;
+
+pub const HELP: &str = "\
+commands:
+    s, step [n]           Executes n instructions [default: 1]
+    b, back [n]           Goes back n instructions in the history [default: 1]
+    c, continue [n]       Runs until a breakpoint, or for at most n instructions
+    u, until <ip>         Runs until the instruction pointer is <ip>
+    break <ip>            Stops before executing the instruction <ip>
+    break r<n> <op> <x>   Stops when the condition holds, <op> is one of == != < <= > >=
+                          and <x> a register or a number
+    watch r<n>            Stops when the register changes
+    d, delete <id>        Deletes a breakpoint or watchpoint
+    i, info               Lists breakpoints and watchpoints
+    r, registers          Prints the registers
+    l, list               Prints the instructions around the instruction pointer
+    set r<n> <value>      Sets a register
+    history [n]           Prints the last n states [default: 10]
+    reset                 Starts over, keeping breakpoints
+    h, help               Prints this help
+    q, quit               Exits
+An empty line repeats the last command.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    const ALL: [CmpOp; 6] = [
+        CmpOp::Eq,
+        CmpOp::Ne,
+        CmpOp::Lt,
+        CmpOp::Le,
+        CmpOp::Gt,
+        CmpOp::Ge,
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+
+    pub fn holds(self, a: Value, b: Value) -> bool {
+        match self {
+            CmpOp::Eq => a == b,
+            CmpOp::Ne => a != b,
+            CmpOp::Lt => a < b,
+            CmpOp::Le => a <= b,
+            CmpOp::Gt => a > b,
+            CmpOp::Ge => a >= b,
+        }
+    }
+}
+
+/// `r<reg> <op> <rhs>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub reg: usize,
+    pub op: CmpOp,
+    pub rhs: Atom,
+}
+
+impl Condition {
+    /// Whether the condition holds; registers out of range are 0.
+    pub fn holds(&self, registers: &[Value]) -> bool {
+        let value = |r: usize| registers.get(r).cloned().unwrap_or(0);
+        let rhs = match self.rhs {
+            Atom::Reg(r) => value(r),
+            Atom::Const(c) => c,
+        };
+        self.op.holds(value(self.reg), rhs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops before executing the instruction.
+    At(usize),
+    /// Stops after an instruction made the condition hold.
+    When(Condition),
+    /// Stops after an instruction changed the register.
+    Watch(usize),
+}
+
+/// Why the debugger stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Executed the requested instructions.
+    Done,
+    Halted,
+    /// Hit the breakpoint with the id.
+    Breakpoint(usize),
+    /// The register of the watchpoint with the id changed.
+    Changed {
+        id: usize,
+        reg: usize,
+        old: Value,
+        new: Value,
+    },
+    /// There is no older state in the history.
+    HistoryExhausted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(u64),
+    Back(u64),
+    Continue(Option<u64>),
+    Until(usize),
+    Break(Breakpoint),
+    Delete(usize),
+    Info,
+    Registers,
+    List,
+    Set(usize, Value),
+    History(usize),
+    Reset,
+    Help,
+    Quit,
+}
+
+fn parse_register(token: &str) -> Result<usize, String> {
+    token
+        .strip_prefix('r')
+        .and_then(|r| r.parse().ok())
+        .ok_or_else(|| format!("expected a register like r0, found {:?}", token))
+}
+
+fn parse_number<T: FromStr>(token: Option<&str>, default: Option<T>) -> Result<T, String> {
+    match (token, default) {
+        (Some(token), _) => token
+            .parse()
+            .map_err(|_| format!("expected a number, found {:?}", token)),
+        (None, Some(default)) => Ok(default),
+        (None, None) => Err("expected a number".to_string()),
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let (reg, op, rhs) = match tokens[..] {
+            [reg, op, rhs] => (reg, op, rhs),
+            _ => return Err(format!("expected a condition like r3 > 10, found {:?}", s)),
+        };
+        let reg = parse_register(reg)?;
+        let op = CmpOp::ALL
+            .iter()
+            .cloned()
+            .find(|o| o.symbol() == op)
+            .ok_or_else(|| format!("unknown comparison {:?}", op))?;
+        let rhs = if rhs.starts_with('r') {
+            Atom::Reg(parse_register(rhs)?)
+        } else {
+            Atom::Const(parse_number(Some(rhs), None)?)
+        };
+        Ok(Condition { reg, op, rhs })
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut tokens = line.split_whitespace();
+        let command = tokens.next().unwrap_or("");
+        let rest: Vec<&str> = tokens.collect();
+        let arg = rest.first().cloned();
+        let command = match command {
+            "s" | "step" => Command::Step(parse_number(arg, Some(1))?),
+            "b" | "back" => Command::Back(parse_number(arg, Some(1))?),
+            "c" | "continue" => {
+                Command::Continue(arg.map(|n| parse_number(Some(n), None)).transpose()?)
+            }
+            "u" | "until" => Command::Until(parse_number(arg, None)?),
+            "break" if rest.len() == 1 => Command::Break(Breakpoint::At(parse_number(arg, None)?)),
+            "break" => Command::Break(Breakpoint::When(rest.join(" ").parse()?)),
+            "watch" => Command::Break(Breakpoint::Watch(parse_register(arg.unwrap_or(""))?)),
+            "d" | "delete" => Command::Delete(parse_number(arg, None)?),
+            "i" | "info" => Command::Info,
+            "r" | "registers" => Command::Registers,
+            "l" | "list" => Command::List,
+            "set" => {
+                let reg = parse_register(arg.unwrap_or(""))?;
+                Command::Set(reg, parse_number(rest.get(1).cloned(), None)?)
+            }
+            "history" => Command::History(parse_number(arg, Some(10))?),
+            "reset" => Command::Reset,
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            "" => return Err("expected a command".to_string()),
+            command => return Err(format!("unknown command {:?}, try help", command)),
+        };
+        let expected = match command {
+            Command::Break(Breakpoint::When(_)) => 3,
+            Command::Set(..) => 2,
+            Command::Info
+            | Command::Registers
+            | Command::List
+            | Command::Reset
+            | Command::Help
+            | Command::Quit => 0,
+            _ => 1,
+        };
+        if rest.len() > expected {
+            return Err(format!("unexpected {:?}", rest[expected]));
+        }
+        Ok(command)
+    }
+}
+
+pub struct Debugger<'a> {
+    vm: Vm<'a>,
+    start: State,
+    /// States before the last executed instructions, the newest at the back.
+    history: VecDeque<State>,
+    capacity: usize,
+    breakpoints: Vec<(usize, Breakpoint)>,
+    next_id: usize,
+}
+
+impl<'a> Debugger<'a> {
+    /// Debugger starting at the current state of `vm`, keeping `capacity` past states.
+    pub fn new(vm: Vm<'a>, capacity: usize) -> Self {
+        Self {
+            start: vm.state(),
+            vm,
+            history: VecDeque::new(),
+            capacity,
+            breakpoints: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn vm(&self) -> &Vm<'a> {
+        &self.vm
+    }
+
+    /// Adds a breakpoint and returns its id.
+    pub fn add(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    /// Deletes a breakpoint; false if there is none with the id.
+    pub fn delete(&mut self, id: usize) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|&(other, _)| other != id);
+        self.breakpoints.len() < len
+    }
+
+    pub fn breakpoints(&self) -> &[(usize, Breakpoint)] {
+        &self.breakpoints
+    }
+
+    /// Past states, the oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &State> {
+        self.history.iter()
+    }
+
+    /// Executes one instruction, recording the state before it.
+    fn step_once(&mut self) -> Option<Event> {
+        if self.vm.is_halted() {
+            return Some(Event::Halted);
+        }
+        let before = self.vm.state();
+        self.vm.step();
+        let event = self.stop(&before.registers);
+        self.remember(before);
+        event
+    }
+
+    /// Event after an instruction changed the registers from `old`.
+    fn stop(&self, old: &[Value]) -> Option<Event> {
+        let registers = self.vm.registers();
+        for &(id, breakpoint) in &self.breakpoints {
+            match breakpoint {
+                Breakpoint::Watch(reg) if old.get(reg) != registers.get(reg) => {
+                    return Some(Event::Changed {
+                        id,
+                        reg,
+                        old: old.get(reg).cloned().unwrap_or(0),
+                        new: registers.get(reg).cloned().unwrap_or(0),
+                    });
+                }
+                Breakpoint::When(cond) if cond.holds(registers) => {
+                    return Some(Event::Breakpoint(id));
+                }
+                Breakpoint::At(ip) if self.vm.ip() == Some(ip) => {
+                    return Some(Event::Breakpoint(id));
+                }
+                _ => (),
+            }
+        }
+        Some(Event::Halted).filter(|_| self.vm.is_halted())
+    }
+
+    fn remember(&mut self, state: State) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(state);
+    }
+
+    /// Executes up to `n` instructions, stopping at breakpoints.
+    pub fn step(&mut self, n: u64) -> Event {
+        for _ in 0..n {
+            if let Some(event) = self.step_once() {
+                return event;
+            }
+        }
+        Event::Done
+    }
+
+    /// Runs until a breakpoint or the halt, or for at most `budget` instructions.
+    pub fn resume(&mut self, budget: Option<u64>) -> Event {
+        self.step(budget.unwrap_or(u64::MAX))
+    }
+
+    /// Runs until the instruction pointer is `ip`, a breakpoint or the halt.
+    pub fn until(&mut self, ip: usize) -> Event {
+        loop {
+            if let Some(event) = self.step_once() {
+                return event;
+            }
+            if self.vm.ip() == Some(ip) {
+                return Event::Done;
+            }
+        }
+    }
+
+    /// Undoes the last `n` instructions.
+    pub fn back(&mut self, n: u64) -> Event {
+        for _ in 0..n {
+            match self.history.pop_back() {
+                Some(state) => self.vm.restore(&state),
+                None => return Event::HistoryExhausted,
+            }
+        }
+        Event::Done
+    }
+
+    /// Goes back to the start and forgets the history.
+    pub fn reset(&mut self) {
+        self.vm.restore(&self.start);
+        self.history.clear();
+    }
+
+    /// Executes `command` and returns the output for the user.
+    pub fn execute(&mut self, command: &Command) -> String {
+        let event = match *command {
+            Command::Step(n) => self.step(n),
+            Command::Back(n) => self.back(n),
+            Command::Continue(budget) => self.resume(budget),
+            Command::Until(ip) => self.until(ip),
+            Command::Break(breakpoint) => {
+                let id = self.add(breakpoint);
+                return format!("{} {}", id, breakpoint);
+            }
+            Command::Delete(id) if self.delete(id) => return format!("deleted {}", id),
+            Command::Delete(id) => return format!("no breakpoint {}", id),
+            Command::Info if self.breakpoints.is_empty() => {
+                return "no breakpoints or watchpoints".to_string()
+            }
+            Command::Info => {
+                let lines: Vec<String> = self
+                    .breakpoints
+                    .iter()
+                    .map(|(id, breakpoint)| format!("{} {}", id, breakpoint))
+                    .collect();
+                return lines.join("\n");
+            }
+            Command::Registers => return self.describe(&self.vm.state()),
+            Command::List => return self.list(5),
+            Command::Set(reg, value) => match self.vm.registers_mut().get_mut(reg) {
+                Some(r) => {
+                    *r = value;
+                    return self.describe(&self.vm.state());
+                }
+                None => return format!("no register {}", reg),
+            },
+            Command::History(n) => {
+                let skip = self.history.len().saturating_sub(n);
+                let lines: Vec<String> = self
+                    .history
+                    .iter()
+                    .skip(skip)
+                    .map(|state| self.describe(state))
+                    .collect();
+                return lines.join("\n");
+            }
+            Command::Reset => {
+                self.reset();
+                Event::Done
+            }
+            Command::Help => return HELP.to_string(),
+            Command::Quit => return String::new(),
+        };
+        let state = self.describe(&self.vm.state());
+        match event {
+            Event::Done => state,
+            Event::Halted => format!("halted\n{}", state),
+            Event::Breakpoint(id) => {
+                let breakpoint = self.breakpoints.iter().find(|&&(other, _)| other == id);
+                let breakpoint = breakpoint.map(|(_, b)| b.to_string()).unwrap_or_default();
+                format!("breakpoint {}: {}\n{}", id, breakpoint, state)
+            }
+            Event::Changed { id, reg, old, new } => {
+                format!("watchpoint {}: r{} {} -> {}\n{}", id, reg, old, new, state)
+            }
+            Event::HistoryExhausted => format!("no older state in the history\n{}", state),
+        }
+    }
+
+    /// The state as in the puzzle, e.g. `#12 ip=3 [0, 5, 6, 0, 0, 0] addr 1 2 3`.
+    fn describe(&self, state: &State) -> String {
+        let program = self.vm.program();
+        match state.ip {
+            Some(ip) => format!(
+                "#{} ip={} {:?} {}",
+                state.steps, ip, state.registers, program.instructions[ip]
+            ),
+            None => format!("#{} halted {:?}", state.steps, state.registers),
+        }
+    }
+
+    /// Instructions within `radius` of the instruction pointer.
+    fn list(&self, radius: usize) -> String {
+        let instructions = &self.vm.program().instructions;
+        let ip = self.vm.ip().unwrap_or(instructions.len());
+        let width = instructions.len().saturating_sub(1).to_string().len();
+        let first = ip.saturating_sub(radius);
+        let lines: Vec<String> = instructions
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(ip + radius + 1 - first)
+            .map(|(idx, instr)| {
+                let marker = if idx == ip { "=>" } else { "  " };
+                let breakpoint = self
+                    .breakpoints
+                    .iter()
+                    .any(|&(_, b)| b == Breakpoint::At(idx));
+                let breakpoint = if breakpoint { "*" } else { " " };
+                format!("{}{} {:>w$}  {}", marker, breakpoint, idx, instr, w = width)
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "r{} {} {}", self.reg, self.op.symbol(), self.rhs)
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::At(ip) => write!(f, "break at {}", ip),
+            Breakpoint::When(cond) => write!(f, "break when {}", cond),
+            Breakpoint::Watch(reg) => write!(f, "watch r{}", reg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+    use crate::testdata::DAY21;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("s".parse(), Ok(Command::Step(1)));
+        assert_eq!("continue 100".parse(), Ok(Command::Continue(Some(100))));
+        assert_eq!("break 28".parse(), Ok(Command::Break(Breakpoint::At(28))));
+        assert_eq!(
+            "break r1 >= r5".parse(),
+            Ok(Command::Break(Breakpoint::When(Condition {
+                reg: 1,
+                op: CmpOp::Ge,
+                rhs: Atom::Reg(5),
+            })))
+        );
+        assert_eq!("set r0 -3".parse(), Ok(Command::Set(0, -3)));
+        assert_eq!(
+            "watch 3".parse::<Command>(),
+            Err(r#"expected a register like r0, found "3""#.to_string())
+        );
+        assert_eq!(
+            "step 1 2".parse::<Command>(),
+            Err(r#"unexpected "2""#.to_string())
+        );
+        assert!("break r1 =~ 3".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_debugger() {
+        let program: Program = DAY21.parse().unwrap();
+        let mut debugger = Debugger::new(Vm::new(&program, 6).unwrap(), 3);
+        assert_eq!(debugger.step(2), Event::Done);
+        assert_eq!(debugger.vm().registers(), &[0, 0, 72, 0, 1, 0]);
+
+        let id = debugger.add(Breakpoint::At(28));
+        assert_eq!(debugger.resume(None), Event::Breakpoint(id));
+        assert_eq!(debugger.vm().ip(), Some(28));
+        let halting_value = debugger.vm().registers()[2];
+
+        assert!(debugger.delete(id));
+        let id = debugger.add(Breakpoint::Watch(2));
+        assert_eq!(
+            debugger.resume(None),
+            Event::Changed {
+                id,
+                reg: 2,
+                old: halting_value,
+                new: 16123384,
+            }
+        );
+        assert_eq!(debugger.vm().ip(), Some(8));
+
+        // the history keeps the states before 30, 6 and 7
+        assert_eq!(debugger.back(1), Event::Done);
+        assert_eq!(debugger.vm().ip(), Some(7));
+        assert_eq!(debugger.vm().registers()[2], halting_value);
+        assert_eq!(debugger.back(5), Event::HistoryExhausted);
+        assert_eq!(debugger.vm().ip(), Some(30));
+
+        debugger.delete(id);
+        let id = debugger.add("r3 > 200".parse().map(Breakpoint::When).unwrap());
+        assert_eq!(debugger.resume(Some(1000)), Event::Breakpoint(id));
+        assert!(debugger.vm().registers()[3] > 200);
+        assert_eq!(debugger.until(26), Event::Breakpoint(id));
+        debugger.delete(id);
+        assert_eq!(debugger.until(26), Event::Done);
+        assert_eq!(debugger.vm().ip(), Some(26));
+
+        assert_eq!(
+            debugger.execute(&Command::Reset),
+            "#0 ip=0 [0, 0, 0, 0, 0, 0] seti 123 0 2"
+        );
+        assert_eq!(debugger.history().count(), 0);
+    }
+}
@@ -0,0 +1,628 @@
+//! Decompiler turning a program into structured pseudo-code.
+//!
+//! The instruction pointer register disappears: jumps become loops, ifs, `break` and `continue`,
+//! and a `goto` to a label where the control flow doesn't nest. A comparison whose result is not
+//! read later on, not even after halting, is folded into the condition of the branch after it. The
+//! innermost loop of day 21 becomes
+//!
+//! ```text
+//! 17  r3 = 0
+//!     loop {
+//! 18      r1 = r3 + 1
+//! 19      r1 *= 256
+//! 20      r1 = r1 > r5
+//! 21      if r1 != 0 {
+//!             break
+//!         }
+//! 24      r3 += 1
+//!     }
+//! 26  r5 = r3
+//! ```
+//!
+//! with the index of the instruction of each statement in the left column.
+
+use crate::cfg::{Cfg, Exit};
+use crate::expr::{Atom, BinOp, Cond, Expr};
+use crate::program::Program;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    /// `reg = expr` computed by the instruction `at`.
+    Assign {
+        at: usize,
+        reg: usize,
+        expr: Expr,
+    },
+    /// Branch on the comparison at the instruction `at`.
+    If {
+        at: usize,
+        cond: Cond,
+        then: Vec<Stmt>,
+        els: Vec<Stmt>,
+    },
+    Loop(Vec<Stmt>),
+    Break,
+    Continue,
+    /// Jumps to the label of the instruction.
+    Goto(usize),
+    /// Jumps from the instruction `at` to the instruction `expr + 1`.
+    Computed {
+        at: usize,
+        expr: Expr,
+    },
+    /// Label of the instruction, for `goto`.
+    Label(usize),
+    Halt,
+}
+
+/// A decompiled program; `Display` renders it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pseudocode {
+    pub stmts: Vec<Stmt>,
+    /// Number of instructions of the program.
+    len: usize,
+}
+
+pub fn decompile(program: &Program) -> Pseudocode {
+    let cfg = Cfg::new(program);
+    let mut decompiler = Decompiler {
+        program,
+        cfg: &cfg,
+        live_out: live_out(program, &cfg),
+        post_dominators: cfg.post_dominators(),
+        loops: cfg
+            .loops()
+            .into_iter()
+            .map(|l| (l.header, l.body))
+            .collect(),
+        emitted: vec![false; cfg.blocks.len()],
+        labels: BTreeSet::new(),
+    };
+    let mut stmts = Vec::new();
+    decompiler.region(cfg.entry, cfg.halt(), &mut Vec::new(), &mut stmts);
+
+    // blocks only reached by computed jumps, or not at all
+    let mut computed_targets = BTreeSet::new();
+    for block in &cfg.blocks {
+        if let Exit::Computed { targets, .. } = &block.exit {
+            computed_targets.extend(targets.iter().cloned());
+        }
+    }
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if decompiler.emitted[b] || block.is_trampoline() && !computed_targets.contains(&b) {
+            continue;
+        }
+        if falls_through(&stmts) {
+            stmts.push(Stmt::Halt);
+        }
+        decompiler.labels.insert(block.range.start);
+        decompiler.region(b, cfg.halt(), &mut Vec::new(), &mut stmts);
+    }
+
+    let mut labels = decompiler.labels;
+    gotos(&stmts, &mut labels);
+    remove_labels(&mut stmts, &labels);
+    Pseudocode {
+        stmts,
+        len: program.len(),
+    }
+}
+
+/// Registers which might be read after each block before being written.
+fn live_out(program: &Program, cfg: &Cfg) -> Vec<BTreeSet<usize>> {
+    let data = |r: i64| Some(r as usize).filter(|&r| Some(r) != program.ip);
+    let all: BTreeSet<usize> = program
+        .instructions
+        .iter()
+        .flat_map(|instr| instr.inputs().chain(Some(instr.c)))
+        .filter_map(data)
+        .collect();
+    let (uses, defs): (Vec<_>, Vec<_>) = cfg
+        .blocks
+        .iter()
+        .map(|block| {
+            let mut used = BTreeSet::new();
+            let mut defined = BTreeSet::new();
+            for instr in &program.instructions[block.range.clone()] {
+                for r in instr.inputs().filter_map(data) {
+                    if !defined.contains(&r) {
+                        used.insert(r);
+                    }
+                }
+                defined.extend(data(instr.c));
+            }
+            (used, defined)
+        })
+        .unzip();
+
+    let mut live_in = vec![BTreeSet::new(); cfg.blocks.len()];
+    let mut live_out = vec![BTreeSet::new(); cfg.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in (0..cfg.blocks.len()).rev() {
+            let out: BTreeSet<usize> = match cfg.blocks[b].exit {
+                // the target might be in the middle of a block
+                Exit::Computed { .. } => all.clone(),
+                _ => cfg
+                    .successors(b)
+                    .into_iter()
+                    .flat_map(|s| live_in.get(s).unwrap_or(&all).iter().cloned())
+                    .collect(),
+            };
+            let mut input = uses[b].clone();
+            input.extend(out.difference(&defs[b]).cloned());
+            if input != live_in[b] || out != live_out[b] {
+                live_in[b] = input;
+                live_out[b] = out;
+                changed = true;
+            }
+        }
+    }
+    live_out
+}
+
+/// Whether the control flow might continue after `stmts`; false for empty `stmts`.
+fn falls_through(stmts: &[Stmt]) -> bool {
+    /// Whether `stmts` contain a `break` of the loop around them.
+    fn breaks(stmts: &[Stmt]) -> bool {
+        stmts.iter().any(|stmt| match stmt {
+            Stmt::Break => true,
+            Stmt::If { then, els, .. } => breaks(then) || breaks(els),
+            _ => false,
+        })
+    }
+    match stmts.last() {
+        None => false,
+        Some(Stmt::Goto(_))
+        | Some(Stmt::Computed { .. })
+        | Some(Stmt::Halt)
+        | Some(Stmt::Break)
+        | Some(Stmt::Continue) => false,
+        Some(Stmt::Loop(body)) => breaks(body),
+        Some(Stmt::If { then, els, .. }) => {
+            els.is_empty() || falls_through(then) || falls_through(els)
+        }
+        Some(_) => true,
+    }
+}
+
+/// Adds the targets of all `goto`s to `labels`.
+fn gotos(stmts: &[Stmt], labels: &mut BTreeSet<usize>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Goto(pc) => {
+                labels.insert(*pc);
+            }
+            Stmt::If { then, els, .. } => {
+                gotos(then, labels);
+                gotos(els, labels);
+            }
+            Stmt::Loop(body) => gotos(body, labels),
+            _ => (),
+        }
+    }
+}
+
+fn remove_labels(stmts: &mut Vec<Stmt>, labels: &BTreeSet<usize>) {
+    stmts.retain(|stmt| match stmt {
+        Stmt::Label(pc) => labels.contains(pc),
+        _ => true,
+    });
+    for stmt in stmts {
+        match stmt {
+            Stmt::If { then, els, .. } => {
+                remove_labels(then, labels);
+                remove_labels(els, labels);
+            }
+            Stmt::Loop(body) => remove_labels(body, labels),
+            _ => (),
+        }
+    }
+}
+
+/// Loop being emitted.
+struct Scope {
+    header: usize,
+    /// Block after the loop, `Cfg::halt` if it is only left by halting.
+    follow: usize,
+}
+
+struct Decompiler<'a> {
+    program: &'a Program,
+    cfg: &'a Cfg,
+    live_out: Vec<BTreeSet<usize>>,
+    post_dominators: Vec<Option<usize>>,
+    /// Bodies of the loops by their headers.
+    loops: HashMap<usize, BTreeSet<usize>>,
+    emitted: Vec<bool>,
+    /// Instructions jumped to by computed jumps or starting unreachable code.
+    labels: BTreeSet<usize>,
+}
+
+impl<'a> Decompiler<'a> {
+    /// Emits the blocks from `b` until the control flow reaches `stop`.
+    fn region(&mut self, mut b: usize, stop: usize, scopes: &mut Vec<Scope>, out: &mut Vec<Stmt>) {
+        while b != stop {
+            if let Some(stmt) = self.jump(b, scopes) {
+                out.push(stmt);
+                return;
+            }
+            match self.node(b, stop, scopes, out) {
+                Some(next) => b = next,
+                None => return,
+            }
+        }
+    }
+
+    /// Statement for continuing with `b` if it can't be emitted here.
+    fn jump(&self, b: usize, scopes: &[Scope]) -> Option<Stmt> {
+        if b == self.cfg.halt() {
+            return Some(Stmt::Halt);
+        }
+        let goto = Stmt::Goto(self.cfg.blocks[b].range.start);
+        if let Some(scope) = scopes.last() {
+            if b == scope.header {
+                return Some(Stmt::Continue);
+            } else if b == scope.follow {
+                return Some(Stmt::Break);
+            } else if !self.loops[&scope.header].contains(&b) {
+                return Some(goto);
+            }
+        }
+        Some(goto).filter(|_| self.emitted[b])
+    }
+
+    /// Whether the control flow can join at `b` after an if.
+    fn can_join(&self, b: usize, stop: usize, scopes: &[Scope]) -> bool {
+        b == stop
+            || b != self.cfg.halt()
+                && !self.emitted[b]
+                && scopes
+                    .last()
+                    .is_none_or(|scope| self.loops[&scope.header].contains(&b))
+    }
+
+    /// Emits `b`, or the loop starting at `b`, and returns where the control flow continues.
+    fn node(
+        &mut self,
+        b: usize,
+        stop: usize,
+        scopes: &mut Vec<Scope>,
+        out: &mut Vec<Stmt>,
+    ) -> Option<usize> {
+        let follow = match self.loops.get(&b) {
+            Some(body) => body
+                .iter()
+                .filter(|&&node| !matches!(self.cfg.blocks[node].exit, Exit::Computed { .. }))
+                .flat_map(|&node| self.cfg.successors(node))
+                .filter(|succ| !body.contains(succ))
+                .min()
+                .unwrap_or_else(|| self.cfg.halt()),
+            None => return self.block(b, stop, scopes, out),
+        };
+        scopes.push(Scope { header: b, follow });
+        let mut body = Vec::new();
+        if let Some(next) = self.block(b, b, scopes, &mut body) {
+            self.region(next, b, scopes, &mut body);
+        }
+        scopes.pop();
+        out.push(Stmt::Loop(body));
+        Some(follow).filter(|&follow| follow != self.cfg.halt())
+    }
+
+    fn block(
+        &mut self,
+        b: usize,
+        stop: usize,
+        scopes: &mut Vec<Scope>,
+        out: &mut Vec<Stmt>,
+    ) -> Option<usize> {
+        self.emitted[b] = true;
+        let block = &self.cfg.blocks[b];
+        out.push(Stmt::Label(block.range.start));
+        let instructions = &self.program.instructions;
+        let folded = match block.exit {
+            Exit::Branch { .. } => Some(block.body.end - 1)
+                .filter(|&pc| !self.live_out[b].contains(&(instructions[pc].c as usize))),
+            _ => None,
+        };
+        for pc in block.body.clone().filter(|&pc| Some(pc) != folded) {
+            let instr = &instructions[pc];
+            out.push(Stmt::Assign {
+                at: pc,
+                reg: instr.c as usize,
+                expr: Expr::of(instr, self.program.ip, pc),
+            });
+        }
+        match block.exit.clone() {
+            Exit::Goto(target) => Some(target),
+            Exit::Computed { expr, targets } => {
+                let blocks = &self.cfg.blocks;
+                self.labels
+                    .extend(targets.iter().map(|&t| blocks[t].range.start));
+                out.push(Stmt::Computed {
+                    at: block.range.end - 1,
+                    expr,
+                });
+                None
+            }
+            Exit::Branch { cond, then, els } => {
+                let comparison = block.body.end - 1;
+                let reg = instructions[comparison].c as usize;
+                let (at, cond) = if folded.is_some() {
+                    (comparison, cond)
+                } else if [cond.lhs, cond.rhs].contains(&Atom::Reg(reg)) {
+                    // the comparison overwrote one of its operands
+                    let cond = Cond {
+                        op: BinOp::Eq,
+                        lhs: Atom::Reg(reg),
+                        rhs: Atom::Const(0),
+                        negated: true,
+                    };
+                    (block.body.end, cond)
+                } else {
+                    (block.body.end, cond)
+                };
+                self.branch(b, at, cond, (then, els), stop, scopes, out)
+            }
+        }
+    }
+
+    /// The statement of an arm which leaves the control flow right away, empty if it ends it.
+    fn arm(&self, target: usize, stop: usize, scopes: &[Scope]) -> Option<Vec<Stmt>> {
+        if target == stop {
+            Some(Vec::new())
+        } else {
+            self.jump(target, scopes).map(|stmt| vec![stmt])
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn branch(
+        &mut self,
+        b: usize,
+        at: usize,
+        cond: Cond,
+        (then, els): (usize, usize),
+        stop: usize,
+        scopes: &mut Vec<Scope>,
+        out: &mut Vec<Stmt>,
+    ) -> Option<usize> {
+        let mut one_armed = |cond, arm: Option<Vec<Stmt>>| match arm {
+            Some(arm) if !arm.is_empty() => {
+                out.push(Stmt::If {
+                    at,
+                    cond,
+                    then: arm,
+                    els: Vec::new(),
+                });
+                true
+            }
+            _ => false,
+        };
+        if one_armed(cond, self.arm(then, stop, scopes)) {
+            return Some(els);
+        }
+        if one_armed(cond.negate(), self.arm(els, stop, scopes)) {
+            return Some(then);
+        }
+
+        let follow = if then == stop || els == stop {
+            Some(stop)
+        } else {
+            self.post_dominators[b].filter(|&f| self.can_join(f, stop, scopes))
+        };
+        let end = follow.unwrap_or(stop);
+        let mut then_stmts = Vec::new();
+        self.region(then, end, scopes, &mut then_stmts);
+        let mut els_stmts = Vec::new();
+        self.region(els, end, scopes, &mut els_stmts);
+        out.push(if then_stmts.iter().all(|s| matches!(s, Stmt::Label(_))) {
+            Stmt::If {
+                at,
+                cond: cond.negate(),
+                then: els_stmts,
+                els: Vec::new(),
+            }
+        } else {
+            Stmt::If {
+                at,
+                cond,
+                then: then_stmts,
+                els: els_stmts,
+            }
+        });
+        follow
+    }
+}
+
+/// `reg = expr`, with an operator assignment if `reg` is an operand.
+fn assignment(reg: usize, expr: &Expr) -> String {
+    let r = Atom::Reg(reg);
+    match *expr {
+        Expr::Binary(op, a, b) if !op.is_comparison() && a == r => {
+            format!("{} {}= {}", r, op.symbol(), b)
+        }
+        Expr::Binary(op, a, b) if !op.is_comparison() && op.is_commutative() && b == r => {
+            format!("{} {}= {}", r, op.symbol(), a)
+        }
+        _ => format!("{} = {}", r, expr),
+    }
+}
+
+/// `goto` to the instruction `expr + 1`.
+fn computed_goto(expr: &Expr) -> String {
+    match *expr {
+        Expr::Binary(BinOp::Add, Atom::Const(base), offset)
+        | Expr::Binary(BinOp::Add, offset, Atom::Const(base)) => {
+            format!("goto L({} + {})", base + 1, offset)
+        }
+        _ => format!("goto L({} + 1)", expr),
+    }
+}
+
+fn write_line(
+    f: &mut fmt::Formatter,
+    width: usize,
+    at: Option<usize>,
+    depth: usize,
+    text: &str,
+) -> fmt::Result {
+    match at {
+        Some(at) => write!(f, "{:>width$}  ", at, width = width)?,
+        None => write!(f, "{:width$}  ", "", width = width)?,
+    }
+    writeln!(f, "{:indent$}{}", "", text, indent = 4 * depth)
+}
+
+fn write_stmts(f: &mut fmt::Formatter, width: usize, depth: usize, stmts: &[Stmt]) -> fmt::Result {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Assign { at, reg, expr } => {
+                write_line(f, width, Some(*at), depth, &assignment(*reg, expr))?
+            }
+            Stmt::If {
+                at,
+                cond,
+                then,
+                els,
+            } => {
+                write_line(f, width, Some(*at), depth, &format!("if {} {{", cond))?;
+                write_stmts(f, width, depth + 1, then)?;
+                if !els.is_empty() {
+                    write_line(f, width, None, depth, "} else {")?;
+                    write_stmts(f, width, depth + 1, els)?;
+                }
+                write_line(f, width, None, depth, "}")?;
+            }
+            Stmt::Loop(body) => {
+                write_line(f, width, None, depth, "loop {")?;
+                write_stmts(f, width, depth + 1, body)?;
+                write_line(f, width, None, depth, "}")?;
+            }
+            Stmt::Break => write_line(f, width, None, depth, "break")?,
+            Stmt::Continue => write_line(f, width, None, depth, "continue")?,
+            Stmt::Goto(pc) => write_line(f, width, None, depth, &format!("goto L{}", pc))?,
+            Stmt::Computed { at, expr } => {
+                write_line(f, width, Some(*at), depth, &computed_goto(expr))?
+            }
+            Stmt::Label(pc) => write_line(f, width, None, depth, &format!("L{}:", pc))?,
+            Stmt::Halt => write_line(f, width, None, depth, "halt")?,
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Pseudocode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.len.saturating_sub(1).to_string().len();
+        write_stmts(f, width, 0, &self.stmts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::DAY21;
+
+    fn decompiled(program: &str) -> String {
+        let program: Program = program.parse().unwrap();
+        decompile(&program).to_string()
+    }
+
+    #[test]
+    fn test_day21() {
+        let expected = " 0  r2 = 123
+    loop {
+ 1      r2 &= 456
+ 2      r2 = r2 == 72
+ 3      if r2 != 0 {
+            break
+        }
+    }
+ 5  r2 = 0
+    loop {
+ 6      r5 = r2 | 65536
+ 7      r2 = 16123384
+        loop {
+ 8          r3 = r5 & 255
+ 9          r2 += r3
+10          r2 &= 16777215
+11          r2 *= 65899
+12          r2 &= 16777215
+13          if 256 > r5 {
+                break
+            }
+17          r3 = 0
+            loop {
+18              r1 = r3 + 1
+19              r1 *= 256
+20              r1 = r1 > r5
+21              if r1 != 0 {
+                    break
+                }
+24              r3 += 1
+            }
+26          r5 = r3
+        }
+28      r3 = r2 == r0
+29      if r2 == r0 {
+            halt
+        }
+    }
+";
+        assert_eq!(decompiled(DAY21), expected);
+    }
+
+    #[test]
+    fn test_if_else() {
+        let program = "\
+#ip 5
+eqri 0 3 1
+addr 1 5 5
+addi 5 2 5
+seti 7 0 2
+seti 5 0 5
+seti 8 0 2
+seti 9 0 3";
+        let expected = "0  r1 = r0 == 3
+1  if r0 == 3 {
+3      r2 = 7
+   } else {
+5      r2 = 8
+   }
+6  r3 = 9
+";
+        assert_eq!(decompiled(program), expected);
+    }
+
+    #[test]
+    fn test_computed() {
+        // the example of day 19 jumps to the instruction in r1
+        let program = "\
+#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5";
+        let expected = "   L0:
+0  r1 = 5
+1  r2 = 6
+   L4:
+4  goto L(r1 + 1)
+   L3:
+3  r3 = r1 + r2
+   goto L4
+   L5:
+5  r4 = 8
+6  r5 = 9
+";
+        assert_eq!(decompiled(program), expected);
+    }
+}
@@ -0,0 +1,190 @@
+//! Expressions computed by instructions, as used by the control flow graph and the decompiler.
+//!
+//! Reads of the instruction pointer register are replaced by the index of the instruction, so an
+//! expression only refers to the registers holding data.
+
+use crate::opcode::{Opcode, Operand, Value};
+use crate::program::Instruction;
+use std::fmt;
+
+/// A register or a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Atom {
+    Reg(usize),
+    Const(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Mul,
+    And,
+    Or,
+    Gt,
+    Eq,
+}
+
+impl BinOp {
+    /// The operation of `op`, `None` for `setr` and `seti`.
+    pub fn of(op: Opcode) -> Option<BinOp> {
+        match op {
+            Opcode::Addr | Opcode::Addi => Some(BinOp::Add),
+            Opcode::Mulr | Opcode::Muli => Some(BinOp::Mul),
+            Opcode::Banr | Opcode::Bani => Some(BinOp::And),
+            Opcode::Borr | Opcode::Bori => Some(BinOp::Or),
+            Opcode::Setr | Opcode::Seti => None,
+            Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => Some(BinOp::Gt),
+            Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => Some(BinOp::Eq),
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Mul => "*",
+            BinOp::And => "&",
+            BinOp::Or => "|",
+            BinOp::Gt => ">",
+            BinOp::Eq => "==",
+        }
+    }
+
+    pub fn is_comparison(self) -> bool {
+        self == BinOp::Gt || self == BinOp::Eq
+    }
+
+    pub fn is_commutative(self) -> bool {
+        self != BinOp::Gt
+    }
+
+    pub fn eval(self, a: Value, b: Value) -> Value {
+        match self {
+            BinOp::Add => a + b,
+            BinOp::Mul => a * b,
+            BinOp::And => a & b,
+            BinOp::Or => a | b,
+            BinOp::Gt => (a > b) as Value,
+            BinOp::Eq => (a == b) as Value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Atom(Atom),
+    Binary(BinOp, Atom, Atom),
+}
+
+impl Expr {
+    /// Value written by `instr` at the index `pc`, with the register `ip` read as `pc`.
+    ///
+    /// Operations on constants only are folded into a constant.
+    pub fn of(instr: &Instruction, ip: Option<usize>, pc: usize) -> Expr {
+        let atom = |kind, operand: Value| match kind {
+            Operand::Register if ip == Some(operand as usize) => Atom::Const(pc as Value),
+            Operand::Register => Atom::Reg(operand as usize),
+            Operand::Immediate | Operand::Ignored => Atom::Const(operand),
+        };
+        let (kind_a, kind_b) = instr.op.operands();
+        let a = atom(kind_a, instr.a);
+        match (BinOp::of(instr.op), a, atom(kind_b, instr.b)) {
+            (None, _, _) => Expr::Atom(a),
+            (Some(op), Atom::Const(a), Atom::Const(b)) => Expr::Atom(Atom::Const(op.eval(a, b))),
+            (Some(op), a, b) => Expr::Binary(op, a, b),
+        }
+    }
+
+    /// Registers read by the expression.
+    pub fn registers(&self) -> impl Iterator<Item = usize> {
+        let (a, b) = match *self {
+            Expr::Atom(a) => (a, None),
+            Expr::Binary(_, a, b) => (a, Some(b)),
+        };
+        Some(a).into_iter().chain(b).filter_map(|atom| match atom {
+            Atom::Reg(r) => Some(r),
+            Atom::Const(_) => None,
+        })
+    }
+}
+
+/// Condition of a branch: a comparison, possibly negated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cond {
+    pub op: BinOp,
+    pub lhs: Atom,
+    pub rhs: Atom,
+    pub negated: bool,
+}
+
+impl Cond {
+    /// The condition that `expr` is true, if it is a comparison.
+    pub fn of(expr: Expr) -> Option<Cond> {
+        match expr {
+            Expr::Binary(op, lhs, rhs) if op.is_comparison() => Some(Cond {
+                op,
+                lhs,
+                rhs,
+                negated: false,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn negate(self) -> Cond {
+        Cond {
+            negated: !self.negated,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Atom::Reg(r) => write!(f, "r{}", r),
+            Atom::Const(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Atom(a) => write!(f, "{}", a),
+            Expr::Binary(op, a, b) => write!(f, "{} {} {}", a, op.symbol(), b),
+        }
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match (self.op, self.negated) {
+            (BinOp::Gt, true) => "<=",
+            (BinOp::Eq, true) => "!=",
+            (op, _) => op.symbol(),
+        };
+        write!(f, "{} {} {}", self.lhs, symbol, self.rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_of() {
+        let expr = |op, a, b| Expr::of(&Instruction::new(op, a, b, 0), Some(4), 7);
+        assert_eq!(expr(Opcode::Addr, 4, 3).to_string(), "7 + r3");
+        assert_eq!(expr(Opcode::Mulr, 4, 4), Expr::Atom(Atom::Const(49)));
+        assert_eq!(expr(Opcode::Seti, 4, 9), Expr::Atom(Atom::Const(4)));
+        assert_eq!(
+            expr(Opcode::Gtir, 256, 5).registers().collect::<Vec<_>>(),
+            vec![5]
+        );
+
+        let cond = Cond::of(expr(Opcode::Gtrr, 1, 5)).unwrap();
+        assert_eq!(cond.to_string(), "r1 > r5");
+        assert_eq!(cond.negate().to_string(), "r1 <= r5");
+        assert_eq!(Cond::of(expr(Opcode::Addi, 1, 5)), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! Values of register 0 which halt a program, as in day 21.
+//!
+//! The program of day 21 only reads register 0 in a single equality test, which ends the program
+//! if it holds:
+//!
+//! ```text
+//! eqrr 2 0 3
+//! addr 3 4 4
+//! ```
+//!
+//! Looking at the other operand each time the test runs gives the values which halt the program,
+//! in the order the program tries them. They repeat at some point, since the program can only
+//! be in finitely many states then.
+
+use crate::expr::{Atom, BinOp, Expr};
+use crate::opcode::Value;
+use crate::optimize::optimize;
+use crate::program::Program;
+use crate::vm::{InvalidRegister, Vm};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+/// Why the halting values of a program can't be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HaltingError {
+    /// No instruction reads register 0.
+    NoCheck,
+    /// Several instructions read register 0.
+    Checks(Vec<usize>),
+    /// The instruction reading register 0 doesn't test for equality.
+    NoEquality(usize),
+    /// The instruction writes register 0.
+    Writes(usize),
+    Register(InvalidRegister),
+}
+
+impl fmt::Display for HaltingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HaltingError::NoCheck => write!(f, "no instruction reads register 0"),
+            HaltingError::Checks(pcs) => {
+                write!(f, "instructions {:?} read register 0, expected one", pcs)
+            }
+            HaltingError::NoEquality(pc) => write!(
+                f,
+                "instruction {} reads register 0 but doesn't test for equality",
+                pc
+            ),
+            HaltingError::Writes(pc) => write!(f, "instruction {} writes register 0", pc),
+            HaltingError::Register(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for HaltingError {}
+
+impl From<InvalidRegister> for HaltingError {
+    fn from(e: InvalidRegister) -> Self {
+        HaltingError::Register(e)
+    }
+}
+
+/// The equality test of register 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Check {
+    /// Index of the instruction.
+    pub pc: usize,
+    /// What register 0 is compared with.
+    pub other: Atom,
+}
+
+/// The only instruction reading register 0, which must test it for equality.
+pub fn find_check(program: &Program) -> Result<Check, HaltingError> {
+    let mut readers = Vec::new();
+    for (pc, instr) in program.instructions.iter().enumerate() {
+        if instr.c == 0 {
+            return Err(HaltingError::Writes(pc));
+        }
+        if program.ip != Some(0) && instr.inputs().any(|r| r == 0) {
+            readers.push(pc);
+        }
+    }
+    let pc = match readers[..] {
+        [] => return Err(HaltingError::NoCheck),
+        [pc] => pc,
+        _ => return Err(HaltingError::Checks(readers)),
+    };
+    let other = match Expr::of(&program.instructions[pc], program.ip, pc) {
+        Expr::Binary(BinOp::Eq, Atom::Reg(0), other)
+        | Expr::Binary(BinOp::Eq, other, Atom::Reg(0))
+            if other != Atom::Reg(0) =>
+        {
+            other
+        }
+        _ => return Err(HaltingError::NoEquality(pc)),
+    };
+    Ok(Check { pc, other })
+}
+
+/// Values of register 0 which halt `program`, in the order the program tries them, up to the
+/// first repetition of the program's state at the check.
+///
+/// The first value halts the program after the fewest instructions, the last one after the most.
+/// The values are empty if the program halts without reaching the check, and this doesn't
+/// return if the program runs forever without reaching it.
+pub fn halting_values(program: &Program, registers: usize) -> Result<Vec<Value>, HaltingError> {
+    let check = find_check(program)?;
+    let mut optimized = optimize(program, registers);
+    optimized.unfuse(check.pc);
+    let mut vm = Vm::optimized(&optimized)?;
+
+    let mut states = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut values = Vec::new();
+    loop {
+        while vm.ip() != Some(check.pc) {
+            if !vm.step() {
+                return Ok(values);
+            }
+        }
+        let value = match check.other {
+            Atom::Reg(r) => vm.registers()[r],
+            Atom::Const(value) => value,
+        };
+        // register 0 is only read by the check, so it can differ from the value to go on
+        vm.registers_mut()[0] = 0;
+        if !states.insert(vm.registers().to_vec()) {
+            return Ok(values);
+        }
+        if seen.insert(value) {
+            values.push(value);
+        }
+        vm.registers_mut()[0] = !value;
+        vm.step();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{DAY19, DAY21};
+    use crate::vm::Exit;
+
+    #[test]
+    fn test_find_check() {
+        let program: Program = DAY21.parse().unwrap();
+        assert_eq!(
+            find_check(&program),
+            Ok(Check {
+                pc: 28,
+                other: Atom::Reg(2)
+            })
+        );
+        let program: Program = DAY19.parse().unwrap();
+        assert_eq!(
+            find_check(&program).unwrap_err().to_string(),
+            "instruction 7 writes register 0"
+        );
+        let program: Program = "#ip 1\ngtrr 0 2 3\naddr 3 1 1".parse().unwrap();
+        assert_eq!(find_check(&program), Err(HaltingError::NoEquality(0)));
+        let program: Program = "eqri 0 7 3\neqrr 2 0 3".parse().unwrap();
+        assert_eq!(find_check(&program), Err(HaltingError::Checks(vec![0, 1])));
+    }
+
+    #[test]
+    fn test_halting_values() {
+        let program: Program = DAY21.parse().unwrap();
+        let values = halting_values(&program, 6).unwrap();
+        assert_eq!(values.len(), 10263);
+        let optimized = optimize(&program, 6);
+        for &value in &[values[0], values[values.len() - 1]] {
+            let mut vm = Vm::optimized(&optimized).unwrap();
+            vm.registers_mut()[0] = value;
+            assert_eq!(vm.run(10_000_000), Exit::Halted);
+        }
+
+        // counts r2 down from 5 and halts if it's r0
+        let program: Program = "#ip 4\nseti 5 0 2\neqrr 0 2 3\naddr 3 4 4\nseti 4 0 4\n\
+                                seti 99 0 4\naddi 2 -1 2\ngtri 2 0 3\naddr 3 4 4\n\
+                                seti 99 0 4\nseti 0 0 4"
+            .parse()
+            .unwrap();
+        assert_eq!(halting_values(&program, 6), Ok(vec![5, 4, 3, 2, 1]));
+    }
+}
@@ -0,0 +1,274 @@
+//! Inferring which number stands for which opcode from samples, as in day 16.
+//!
+//! Each sample narrows the opcodes its number can stand for down to the ones which explain it.
+//! Numbers stand for different opcodes, so a mapping is a matching in the bipartite graph of
+//! numbers and their possible opcodes. After each sample a matching has to exist, otherwise the
+//! sample contradicts the ones before it. The mapping is known if there is exactly one matching.
+
+use crate::opcode::{Opcode, Value};
+use crate::program::Instruction;
+use std::error::Error;
+use std::fmt;
+
+/// Number of mappings listed by `InferError::Ambiguous`.
+pub const MAX_MAPPINGS: usize = 20;
+
+/// An instruction with an opcode number and the registers before and after executing it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub before: Vec<Value>,
+    pub number: usize,
+    pub a: Value,
+    pub b: Value,
+    pub c: Value,
+    pub after: Vec<Value>,
+}
+
+impl Sample {
+    /// Whether the instruction does what the sample shows if its number stands for `op`.
+    pub fn behaves_like(&self, op: Opcode) -> bool {
+        let instr = Instruction::new(op, self.a, self.b, self.c);
+        if instr.invalid_register(self.before.len()).is_some() {
+            return false;
+        }
+        let mut regs = self.before.clone();
+        instr.execute(&mut regs);
+        regs == self.after
+    }
+
+    /// The opcodes out of `opcodes` which behave like the sample.
+    pub fn possible<'a>(&'a self, opcodes: &'a [Opcode]) -> impl Iterator<Item = Opcode> + 'a {
+        opcodes.iter().cloned().filter(move |&op| self.behaves_like(op))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InferError {
+    /// There are more numbers than opcodes.
+    TooFewOpcodes,
+    /// The number of the sample with this index is out of range.
+    UnknownNumber(usize),
+    /// No mapping fits the sample with this index and the ones before it.
+    Contradiction(usize),
+    /// Several mappings fit all samples; `complete` is false if there are more than listed.
+    Ambiguous {
+        mappings: Vec<Vec<Opcode>>,
+        complete: bool,
+    },
+}
+
+impl fmt::Display for InferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InferError::TooFewOpcodes => write!(f, "there are more numbers than opcodes"),
+            InferError::UnknownNumber(sample) => {
+                write!(f, "sample {}: the opcode number is out of range", sample)
+            }
+            InferError::Contradiction(sample) => {
+                write!(f, "sample {}: no mapping fits it and the samples before", sample)
+            }
+            InferError::Ambiguous { mappings, complete } => {
+                let more = if *complete { "" } else { "more than " };
+                write!(f, "{}{} mappings fit the samples:", more, mappings.len())?;
+                for mapping in mappings {
+                    write!(f, "\n   ")?;
+                    for (number, op) in mapping.iter().enumerate() {
+                        write!(f, " {}={}", number, op)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for InferError {}
+
+/// Sets of indices into the opcodes.
+type Set = u32;
+
+fn members(set: Set) -> impl Iterator<Item = usize> {
+    (0..Set::BITS as usize).filter(move |&i| set & (1 << i) != 0)
+}
+
+/// Tries to find an opcode for `number` which isn't in `seen`, moving other numbers to other
+/// opcodes if necessary (Kuhn's algorithm).
+fn augment(number: usize, possible: &[Set], owner: &mut [Option<usize>], seen: &mut Set) -> bool {
+    for op in members(possible[number] & !*seen) {
+        *seen |= 1 << op;
+        let free = match owner[op] {
+            None => true,
+            Some(other) => augment(other, possible, owner, seen),
+        };
+        if free {
+            owner[op] = Some(number);
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether each number can get a different one of its possible opcodes.
+fn has_matching(possible: &[Set]) -> bool {
+    let mut owner = vec![None; Set::BITS as usize];
+    (0..possible.len()).all(|number| augment(number, possible, &mut owner, &mut 0))
+}
+
+/// Adds up to `limit` matchings extending `assigned` to `found`, trying the numbers with the
+/// fewest possible opcodes first.
+fn matchings(
+    possible: &[Set],
+    assigned: &mut Vec<Option<usize>>,
+    used: Set,
+    limit: usize,
+    found: &mut Vec<Vec<usize>>,
+) {
+    let next = (0..possible.len())
+        .filter(|&number| assigned[number].is_none())
+        .min_by_key(|&number| (possible[number] & !used).count_ones());
+    let number = match next {
+        Some(number) => number,
+        None => {
+            found.push(assigned.iter().map(|op| op.expect("assigned")).collect());
+            return;
+        }
+    };
+    for op in members(possible[number] & !used) {
+        if found.len() >= limit {
+            return;
+        }
+        assigned[number] = Some(op);
+        matchings(possible, assigned, used | 1 << op, limit, found);
+    }
+    assigned[number] = None;
+}
+
+/// The opcode of each of the numbers `0..numbers` according to `samples`, out of `opcodes`.
+///
+/// `opcodes` are the distinct opcodes of the device, e.g. `Opcode::ALL`; it may have more opcodes
+/// than numbers.
+pub fn infer(
+    samples: &[Sample],
+    numbers: usize,
+    opcodes: &[Opcode],
+) -> Result<Vec<Opcode>, InferError> {
+    if numbers > opcodes.len() {
+        return Err(InferError::TooFewOpcodes);
+    }
+    let all: Set = (1 << opcodes.len()) - 1;
+    let mut possible = vec![all; numbers];
+    for (idx, sample) in samples.iter().enumerate() {
+        let set = possible
+            .get_mut(sample.number)
+            .ok_or(InferError::UnknownNumber(idx))?;
+        let narrowed = members(*set)
+            .filter(|&op| sample.behaves_like(opcodes[op]))
+            .fold(0, |set, op| set | 1 << op);
+        if narrowed != *set {
+            *set = narrowed;
+            if !has_matching(&possible) {
+                return Err(InferError::Contradiction(idx));
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    matchings(
+        &possible,
+        &mut vec![None; numbers],
+        0,
+        MAX_MAPPINGS + 1,
+        &mut found,
+    );
+    let mut mappings: Vec<Vec<Opcode>> = found
+        .into_iter()
+        .map(|mapping| mapping.into_iter().map(|op| opcodes[op]).collect())
+        .collect();
+    if mappings.len() == 1 {
+        return Ok(mappings.remove(0));
+    }
+    let complete = mappings.len() <= MAX_MAPPINGS;
+    mappings.truncate(MAX_MAPPINGS);
+    Err(InferError::Ambiguous { mappings, complete })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(before: &[Value], instr: [Value; 4], after: &[Value]) -> Sample {
+        Sample {
+            before: before.to_vec(),
+            number: instr[0] as usize,
+            a: instr[1],
+            b: instr[2],
+            c: instr[3],
+            after: after.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_possible() {
+        // the example of day 16
+        let example = sample(&[3, 2, 1, 1], [9, 2, 1, 2], &[3, 2, 2, 1]);
+        let possible: Vec<Opcode> = example.possible(&Opcode::ALL).collect();
+        assert_eq!(possible, vec![Opcode::Addi, Opcode::Mulr, Opcode::Seti]);
+        assert!(!sample(&[0, 0], [0, 5, 0, 1], &[0, 0]).behaves_like(Opcode::Addr));
+    }
+
+    #[test]
+    fn test_infer() {
+        let opcodes = [Opcode::Addr, Opcode::Mulr, Opcode::Seti];
+        let samples = vec![
+            sample(&[2, 2, 0], [0, 0, 1, 2], &[2, 2, 4]),
+            sample(&[2, 3, 0], [1, 0, 1, 2], &[2, 3, 6]),
+            sample(&[2, 3, 0], [2, 7, 1, 2], &[2, 3, 7]),
+        ];
+        assert_eq!(
+            infer(&samples[1..], 3, &opcodes),
+            Ok(vec![Opcode::Addr, Opcode::Mulr, Opcode::Seti])
+        );
+        assert_eq!(
+            infer(&samples[1..], 2, &opcodes),
+            Err(InferError::UnknownNumber(1))
+        );
+
+        // 2 + 2 == 2 * 2, so number 0 could be addr or mulr
+        let ambiguous = infer(&samples[..1], 2, &opcodes[..2]).unwrap_err();
+        assert_eq!(
+            ambiguous,
+            InferError::Ambiguous {
+                mappings: vec![
+                    vec![Opcode::Addr, Opcode::Mulr],
+                    vec![Opcode::Mulr, Opcode::Addr]
+                ],
+                complete: true
+            }
+        );
+        assert_eq!(
+            ambiguous.to_string(),
+            "2 mappings fit the samples:\n    0=addr 1=mulr\n    0=mulr 1=addr"
+        );
+        match infer(&[], 16, &Opcode::ALL) {
+            Err(InferError::Ambiguous { mappings, complete }) => {
+                assert_eq!(mappings.len(), MAX_MAPPINGS);
+                assert!(!complete);
+            }
+            result => panic!("{:?}", result),
+        }
+
+        // numbers 0 and 1 would both have to be mulr
+        let contradiction = vec![
+            samples[1].clone(),
+            sample(&[2, 3, 0], [0, 0, 1, 2], &[2, 3, 6]),
+        ];
+        assert_eq!(
+            infer(&contradiction, 3, &opcodes),
+            Err(InferError::Contradiction(1))
+        );
+        assert_eq!(
+            infer(&samples, 4, &opcodes),
+            Err(InferError::TooFewOpcodes)
+        );
+    }
+}
@@ -0,0 +1,30 @@
+//! The wrist device of days 16, 19 and 21 and its programs ("ElfCode").
+//!
+//! The device has a configurable number of registers (4 in day 16, 6 in days 19 and 21) and 16
+//! opcodes, which read registers or immediate values and write one register. A program may bind
+//! the instruction pointer to a register with `#ip N`, which is the only way to jump.
+
+pub mod asm;
+pub mod cfg;
+pub mod debugger;
+pub mod decompile;
+pub mod expr;
+pub mod halting;
+pub mod infer;
+pub mod opcode;
+pub mod optimize;
+pub mod profile;
+pub mod program;
+pub mod samples;
+pub mod symbolic;
+#[cfg(test)]
+mod testdata;
+pub mod vm;
+
+pub use crate::asm::assemble;
+pub use crate::decompile::{decompile, Pseudocode};
+pub use crate::opcode::{Opcode, Operand, Value};
+pub use crate::optimize::{optimize, Optimized};
+pub use crate::profile::Profile;
+pub use crate::program::{Instruction, ParseError, Program, DEFAULT_REGISTERS};
+pub use crate::vm::{Exit, InvalidRegister, State, Vm};
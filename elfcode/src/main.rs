@@ -0,0 +1,331 @@
+use elfcode::debugger::{Command, Debugger, DEFAULT_HISTORY};
+use elfcode::halting::halting_values;
+use elfcode::infer::infer;
+use elfcode::samples::{self, generate, minimize, random_mapping, Input};
+use elfcode::symbolic::{setup, Inputs};
+use elfcode::{
+    assemble, decompile, optimize, Exit, Opcode, Profile, Program, Value, Vm, DEFAULT_REGISTERS,
+};
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::process;
+
+const USAGE: &str = "\
+usage: elfcode <command> [options] <program>
+       elfcode generate [options]
+       elfcode validate|minimize <input>
+
+Reads the program from the file <program>, or from stdin if it is - and the command is not debug.
+A puzzle description with the program in a ``` block works, too.
+
+commands:
+    run          Runs the program and prints the registers
+    decompile    Prints the program as structured pseudo-code
+    debug        Debugs the program interactively, see help in the debugger
+    halting      Prints the values of register 0 which halt the program, as in day 21
+    setup        Runs the program symbolically up to its first loop for all values of register 0 >= 0
+    profile      Runs the program and prints its instructions by executions and its hot loops
+    assemble     Prints the plain program of an assembly source with labels and jumps
+    generate     Prints a day 16 input for a random opcode mapping, the mapping goes to stderr
+    validate     Prints the opcode mapping determined by the samples of a day 16 input
+    minimize     Prints a day 16 input with only a few samples which determine the mapping
+
+options:
+    -r, --registers <n>      Number of registers [default: 6]
+    -s, --set <r>=<value>    Sets register <r> before running; repeatable
+    -b, --budget <n>         Stops after <n> instructions
+    -O, --optimize           Runs loops with a closed form natively, each counts as one instruction
+    --dot <file>             Writes the profiled control flow graph in Graphviz format
+    --history <n>            Number of states kept for going back [default: 10000]
+    --seed <n>               Seed of the generated input [default: 0]
+    --samples <n>            Number of generated samples [default: 800]
+    --length <n>             Length of the generated test program [default: 100]
+";
+
+#[derive(Debug)]
+struct Options {
+    command: String,
+    program: String,
+    registers: usize,
+    set: Vec<(usize, Value)>,
+    budget: Option<u64>,
+    optimize: bool,
+    history: usize,
+    dot: Option<String>,
+    seed: u64,
+    samples: usize,
+    length: usize,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, Box<dyn Error>> {
+    let command = args.next().ok_or("missing command")?;
+    let mut options = Options {
+        command,
+        program: String::new(),
+        registers: DEFAULT_REGISTERS,
+        set: Vec::new(),
+        budget: None,
+        optimize: false,
+        history: DEFAULT_HISTORY,
+        dot: None,
+        seed: 0,
+        samples: 800,
+        length: 100,
+    };
+    let mut program = None;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value of {}", arg))
+        };
+        match arg.as_str() {
+            "-r" | "--registers" => options.registers = value()?.parse()?,
+            "-b" | "--budget" => options.budget = Some(value()?.parse()?),
+            "-O" | "--optimize" => options.optimize = true,
+            "--history" => options.history = value()?.parse()?,
+            "--dot" => options.dot = Some(value()?),
+            "--seed" => options.seed = value()?.parse()?,
+            "--samples" => options.samples = value()?.parse()?,
+            "--length" => options.length = value()?.parse()?,
+            "-s" | "--set" => {
+                let value = value()?;
+                let mut parts = value.splitn(2, '=');
+                let register = parts.next().unwrap_or("").trim_start_matches('r');
+                let v = parts
+                    .next()
+                    .ok_or_else(|| format!("expected r=value: {}", value))?;
+                options.set.push((register.parse()?, v.parse()?));
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option {}", arg).into())
+            }
+            _ if program.is_none() => program = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg).into()),
+        }
+    }
+    options.program = match program {
+        Some(program) => program,
+        None if options.command == "generate" => String::new(),
+        None => return Err("missing program".into()),
+    };
+    Ok(options)
+}
+
+/// The first ``` block of a puzzle description which starts with `#ip`, with its first line.
+fn code_block(input: &str) -> Option<(usize, String)> {
+    let lines: Vec<&str> = input.lines().collect();
+    let start = 1 + lines
+        .windows(2)
+        .position(|w| w[0].trim() == "```" && w[1].starts_with("#ip"))?;
+    let len = lines[start..]
+        .iter()
+        .position(|line| line.trim() == "```")
+        .unwrap_or(lines.len() - start);
+    Some((start + 1, lines[start..start + len].join("\n")))
+}
+
+fn read_input(path: &str) -> Result<String, Box<dyn Error>> {
+    let mut input = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut input)?;
+    } else {
+        input = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(input)
+}
+
+fn read_program(path: &str, registers: usize) -> Result<Program, Box<dyn Error>> {
+    let input = read_input(path)?;
+    let result = match Program::parse(&input, registers) {
+        Err(e) => match code_block(&input) {
+            Some((line, block)) => Program::parse(&block, registers).map_err(|mut e| {
+                e.line += line - 1;
+                e
+            }),
+            None => Err(e),
+        },
+        program => program,
+    };
+    result.map_err(|e| format!("{}: {}", path, e).into())
+}
+
+/// Sets the registers given by the options.
+fn set_registers(options: &Options, vm: &mut Vm) -> Result<(), Box<dyn Error>> {
+    for &(r, value) in &options.set {
+        *vm.registers_mut()
+            .get_mut(r)
+            .ok_or_else(|| format!("no register {}", r))? = value;
+    }
+    Ok(())
+}
+
+fn run(options: &Options, program: &Program) -> Result<(), Box<dyn Error>> {
+    let optimized;
+    let mut vm = if options.optimize {
+        optimized = optimize(program, options.registers);
+        Vm::optimized(&optimized)?
+    } else {
+        Vm::new(program, options.registers)?
+    };
+    set_registers(options, &mut vm)?;
+    let budget = options.budget.unwrap_or(u64::MAX);
+    let exit = if options.command == "profile" {
+        let (profile, exit) = Profile::run(&mut vm, budget);
+        println!("{}", profile.listing(program));
+        if let Some(path) = &options.dot {
+            fs::write(path, profile.dot(program)).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        exit
+    } else {
+        vm.run(budget)
+    };
+    match exit {
+        Exit::Halted => println!("halted after {} instructions", vm.steps()),
+        Exit::OutOfBudget => println!(
+            "stopped at ip {} after {} instructions",
+            vm.ip().unwrap_or_default(),
+            vm.steps()
+        ),
+    }
+    println!("registers: {:?}", vm.registers());
+    Ok(())
+}
+
+fn debug(options: &Options, program: &Program) -> Result<(), Box<dyn Error>> {
+    if options.program == "-" {
+        return Err("the debugger reads commands from stdin, pass the program as a file".into());
+    }
+    let mut vm = Vm::new(program, options.registers)?;
+    set_registers(options, &mut vm)?;
+    let mut debugger = Debugger::new(vm, options.history);
+    println!("{}", debugger.execute(&Command::Registers));
+    let mut last = None;
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(elfcode) ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+        let command = match (line.trim(), &last) {
+            ("", Some(last)) => Command::clone(last),
+            ("", None) => continue,
+            (line, _) => match line.parse() {
+                Ok(command) => command,
+                Err(e) => {
+                    println!("error: {}", e);
+                    continue;
+                }
+            },
+        };
+        if command == Command::Quit {
+            return Ok(());
+        }
+        println!("{}", debugger.execute(&command));
+        last = Some(command);
+    }
+}
+
+/// The commands for the inputs of day 16.
+fn day16(options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.command == "generate" {
+        let mapping = random_mapping(options.seed);
+        let input = generate(&mapping, options.samples, options.length, options.seed);
+        print!("{}", input);
+        eprintln!("mapping: {}", mapping_string(&mapping));
+        eprintln!("registers: {:?}", input.run(&mapping));
+        return Ok(());
+    }
+    let mut input: Input = read_input(&options.program)?
+        .parse()
+        .map_err(|e| format!("{}: {}", options.program, e))?;
+    let (numbers, opcodes) = (Opcode::ALL.len(), &Opcode::ALL);
+    if options.command == "minimize" {
+        let count = input.samples.len();
+        input.samples = minimize(&input.samples, numbers, opcodes)?;
+        print!("{}", input);
+        eprintln!("{} of {} samples", input.samples.len(), count);
+        return Ok(());
+    }
+    let mapping = infer(&input.samples, numbers, opcodes)?;
+    println!("mapping: {}", mapping_string(&mapping));
+    if input.program.iter().all(|instr| {
+        instr.number < numbers
+            && instr
+                .with(mapping[instr.number])
+                .invalid_register(samples::REGISTERS)
+                .is_none()
+    }) {
+        println!("registers: {:?}", input.run(&mapping));
+    } else {
+        println!("the test program has invalid instructions");
+    }
+    Ok(())
+}
+
+fn mapping_string(mapping: &[Opcode]) -> String {
+    let numbered: Vec<String> = mapping
+        .iter()
+        .enumerate()
+        .map(|(number, op)| format!("{}={}", number, op))
+        .collect();
+    numbered.join(" ")
+}
+
+fn try_main() -> Result<(), Box<dyn Error>> {
+    // only mistakes in the arguments get the usage
+    let options =
+        parse_args(std::env::args().skip(1)).map_err(|e| format!("{}\n\n{}", e, USAGE))?;
+    match options.command.as_str() {
+        "generate" | "validate" | "minimize" => return day16(&options),
+        _ => (),
+    }
+    if options.command == "assemble" {
+        let source = read_input(&options.program)?;
+        let program = assemble(&source, options.registers)
+            .map_err(|e| format!("{}: {}", options.program, e))?;
+        print!("{}", program);
+        return Ok(());
+    }
+    let program = read_program(&options.program, options.registers)?;
+    match options.command.as_str() {
+        "run" | "profile" => run(&options, &program),
+        "debug" => debug(&options, &program),
+        "halting" => {
+            let values = halting_values(&program, options.registers)?;
+            match (values.first(), values.last()) {
+                (Some(first), Some(last)) => println!(
+                    "{} values, fewest instructions: {}, most instructions: {}",
+                    values.len(),
+                    first,
+                    last
+                ),
+                _ => println!("the program halts without checking register 0"),
+            }
+            Ok(())
+        }
+        "setup" => {
+            let inputs = Inputs::range(0, Value::MAX);
+            let budget = options.budget.unwrap_or(10_000);
+            for entry in setup(&program, options.registers, inputs, budget)? {
+                println!("{}", entry);
+            }
+            Ok(())
+        }
+        "decompile" => {
+            print!("{}", decompile(&program));
+            Ok(())
+        }
+        command => Err(format!("unknown command {}\n\n{}", command, USAGE).into()),
+    }
+}
+
+fn main() {
+    if let Err(e) = try_main() {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
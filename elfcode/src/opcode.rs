@@ -0,0 +1,162 @@
+//! The 16 opcodes of the device.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Value of a register or an immediate operand.
+pub type Value = i64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Opcode {
+    Addr,
+    Addi,
+    Mulr,
+    Muli,
+    Banr,
+    Bani,
+    Borr,
+    Bori,
+    Setr,
+    Seti,
+    Gtir,
+    Gtri,
+    Gtrr,
+    Eqir,
+    Eqri,
+    Eqrr,
+}
+
+/// How an instruction uses its operands `a` and `b`; `c` is always the output register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register,
+    Immediate,
+    Ignored,
+}
+
+impl Opcode {
+    /// All opcodes in the order of the puzzle description.
+    pub const ALL: [Opcode; 16] = [
+        Opcode::Addr,
+        Opcode::Addi,
+        Opcode::Mulr,
+        Opcode::Muli,
+        Opcode::Banr,
+        Opcode::Bani,
+        Opcode::Borr,
+        Opcode::Bori,
+        Opcode::Setr,
+        Opcode::Seti,
+        Opcode::Gtir,
+        Opcode::Gtri,
+        Opcode::Gtrr,
+        Opcode::Eqir,
+        Opcode::Eqri,
+        Opcode::Eqrr,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Opcode::Addr => "addr",
+            Opcode::Addi => "addi",
+            Opcode::Mulr => "mulr",
+            Opcode::Muli => "muli",
+            Opcode::Banr => "banr",
+            Opcode::Bani => "bani",
+            Opcode::Borr => "borr",
+            Opcode::Bori => "bori",
+            Opcode::Setr => "setr",
+            Opcode::Seti => "seti",
+            Opcode::Gtir => "gtir",
+            Opcode::Gtri => "gtri",
+            Opcode::Gtrr => "gtrr",
+            Opcode::Eqir => "eqir",
+            Opcode::Eqri => "eqri",
+            Opcode::Eqrr => "eqrr",
+        }
+    }
+
+    /// Kinds of the operands `a` and `b`.
+    pub fn operands(self) -> (Operand, Operand) {
+        use self::Operand::*;
+        match self {
+            Opcode::Addr | Opcode::Mulr | Opcode::Banr | Opcode::Borr => (Register, Register),
+            Opcode::Addi | Opcode::Muli | Opcode::Bani | Opcode::Bori => (Register, Immediate),
+            Opcode::Setr => (Register, Ignored),
+            Opcode::Seti => (Immediate, Ignored),
+            Opcode::Gtir | Opcode::Eqir => (Immediate, Register),
+            Opcode::Gtri | Opcode::Eqri => (Register, Immediate),
+            Opcode::Gtrr | Opcode::Eqrr => (Register, Register),
+        }
+    }
+
+    /// Result of the opcode for the values of `a` and `b`, i.e. after reading registers.
+    ///
+    /// Additions and multiplications wrap around on overflow like the device's registers.
+    pub fn eval(self, a: Value, b: Value) -> Value {
+        match self {
+            Opcode::Addr | Opcode::Addi => a.wrapping_add(b),
+            Opcode::Mulr | Opcode::Muli => a.wrapping_mul(b),
+            Opcode::Banr | Opcode::Bani => a & b,
+            Opcode::Borr | Opcode::Bori => a | b,
+            Opcode::Setr | Opcode::Seti => a,
+            Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => (a > b) as Value,
+            Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => (a == b) as Value,
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownOpcode(pub String);
+
+impl fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownOpcode {}
+
+impl FromStr for Opcode {
+    type Err = UnknownOpcode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Opcode::ALL
+            .iter()
+            .cloned()
+            .find(|op| op.name() == s)
+            .ok_or_else(|| UnknownOpcode(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        for &op in Opcode::ALL.iter() {
+            assert_eq!(op.name().parse(), Ok(op));
+        }
+        assert_eq!(
+            "adx".parse::<Opcode>().unwrap_err().to_string(),
+            r#"unknown opcode "adx""#
+        );
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(Opcode::Mulr.eval(6, 7), 42);
+        assert_eq!(Opcode::Bani.eval(123, 456), 72);
+        assert_eq!(Opcode::Gtri.eval(3, 2), 1);
+        assert_eq!(Opcode::Eqir.eval(3, 2), 0);
+        assert_eq!(Opcode::Addi.eval(Value::MAX, 1), Value::MIN);
+        assert_eq!(Opcode::Mulr.eval(Value::MAX, 2), -2);
+    }
+}
@@ -0,0 +1,831 @@
+//! Fusing loops into native operations.
+//!
+//! The programs of days 19 and 21 spend almost all of their time in a few loops, like
+//!
+//! ```text
+//! do {
+//!     if r1 * r5 == r2 { r0 += r1 }
+//!     r5 += 1
+//! } while r5 <= r2
+//! ```
+//!
+//! One iteration of a loop is evaluated symbolically, i.e. the registers at its end are terms of
+//! the registers at its start. A loop can be fused if one register, the counter, goes up or down
+//! by a constant and the loop is left when a linear term of the counter gets greater than or
+//! equal to a term of the registers which don't change. The other registers must accumulate sums
+//! with a closed form: constants, linear terms of the counter or divisor tests. Fused loops are
+//! evaluated symbolically in turn, so loops around them can be fused, too.
+//!
+//! A fused loop runs as a single instruction. It falls back to the instructions if the closed
+//! form doesn't apply, e.g. on overflows or if the loop never ends.
+
+use crate::cfg::{self, Cfg, Loop};
+use crate::expr::{Atom, BinOp, Expr};
+use crate::opcode::Value;
+use crate::program::Program;
+use std::collections::BTreeSet;
+
+/// Value of a register in terms of the registers at the start of a loop iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Reg(usize),
+    Const(Value),
+    Binary(BinOp, Box<Term>, Box<Term>),
+    Sub(Box<Term>, Box<Term>),
+    Max(Box<Term>, Box<Term>),
+    /// The second term if the first is not 0, else the third.
+    Select(Box<Term>, Box<Term>, Box<Term>),
+    /// `value` if `value * q == target` for a `q` in `from..=to`, else 0.
+    Divisor {
+        value: Box<Term>,
+        target: Box<Term>,
+        from: Box<Term>,
+        to: Box<Term>,
+    },
+}
+
+/// `op.eval` which fails on overflows.
+fn checked(op: BinOp, a: Value, b: Value) -> Option<Value> {
+    match op {
+        BinOp::Add => a.checked_add(b),
+        BinOp::Mul => a.checked_mul(b),
+        op => Some(op.eval(a, b)),
+    }
+}
+
+impl Term {
+    fn atom(atom: Atom, state: &[Term]) -> Term {
+        match atom {
+            Atom::Reg(r) => state[r].clone(),
+            Atom::Const(value) => Term::Const(value),
+        }
+    }
+
+    fn binary(op: BinOp, a: Term, b: Term) -> Term {
+        match (op, a, b) {
+            (op, Term::Const(a), Term::Const(b)) if checked(op, a, b).is_some() => {
+                Term::Const(op.eval(a, b))
+            }
+            (BinOp::Add, a, Term::Const(0)) | (BinOp::Add, Term::Const(0), a) => a,
+            (BinOp::Mul, a, Term::Const(1)) | (BinOp::Mul, Term::Const(1), a) => a,
+            (op, Term::Const(a), b) if op.is_commutative() => {
+                Term::Binary(op, Box::new(b), Box::new(Term::Const(a)))
+            }
+            (op, a, b) => Term::Binary(op, Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn add(a: Term, b: Term) -> Term {
+        Term::binary(BinOp::Add, a, b)
+    }
+
+    fn sub(a: Term, b: Term) -> Term {
+        match (a, b) {
+            (Term::Const(a), Term::Const(b)) if a.checked_sub(b).is_some() => Term::Const(a - b),
+            (a, Term::Const(0)) => a,
+            (a, b) => Term::Sub(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn max(a: Term, b: Term) -> Term {
+        match (a, b) {
+            (Term::Const(a), Term::Const(b)) => Term::Const(a.max(b)),
+            (a, b) => Term::Max(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn select(cond: Term, then: Term, els: Term) -> Term {
+        match cond {
+            _ if then == els => then,
+            Term::Const(0) => els,
+            Term::Const(_) => then,
+            cond => Term::Select(Box::new(cond), Box::new(then), Box::new(els)),
+        }
+    }
+
+    /// The term with each `Reg(r)` replaced by `state[r]`.
+    fn subst(&self, state: &[Term]) -> Term {
+        let subst = |term: &Term| term.subst(state);
+        match self {
+            Term::Reg(r) => state[*r].clone(),
+            Term::Const(value) => Term::Const(*value),
+            Term::Binary(op, a, b) => Term::binary(*op, subst(a), subst(b)),
+            Term::Sub(a, b) => Term::sub(subst(a), subst(b)),
+            Term::Max(a, b) => Term::max(subst(a), subst(b)),
+            Term::Select(cond, then, els) => Term::select(subst(cond), subst(then), subst(els)),
+            Term::Divisor {
+                value,
+                target,
+                from,
+                to,
+            } => Term::Divisor {
+                value: Box::new(subst(value)),
+                target: Box::new(subst(target)),
+                from: Box::new(subst(from)),
+                to: Box::new(subst(to)),
+            },
+        }
+    }
+
+    /// Value for the registers `regs`, `None` on overflows.
+    fn eval(&self, regs: &[Value]) -> Option<Value> {
+        let eval = |term: &Term| term.eval(regs);
+        match self {
+            Term::Reg(r) => Some(regs[*r]),
+            Term::Const(value) => Some(*value),
+            Term::Binary(op, a, b) => checked(*op, eval(a)?, eval(b)?),
+            Term::Sub(a, b) => eval(a)?.checked_sub(eval(b)?),
+            Term::Max(a, b) => Some(eval(a)?.max(eval(b)?)),
+            Term::Select(cond, then, els) if eval(cond)? != 0 => eval(then),
+            Term::Select(_, _, els) => eval(els),
+            Term::Divisor {
+                value,
+                target,
+                from,
+                to,
+            } => {
+                let (value, target) = (eval(value)?, eval(target)?);
+                if value == 0 || target % value != 0 {
+                    return Some(0);
+                }
+                let q = target / value;
+                Some(if eval(from)? <= q && q <= eval(to)? {
+                    value
+                } else {
+                    0
+                })
+            }
+        }
+    }
+
+    fn registers(&self, regs: &mut BTreeSet<usize>) {
+        match self {
+            Term::Reg(r) => {
+                regs.insert(*r);
+            }
+            Term::Const(_) => (),
+            Term::Binary(_, a, b) | Term::Sub(a, b) | Term::Max(a, b) => {
+                a.registers(regs);
+                b.registers(regs);
+            }
+            Term::Select(a, b, c) => {
+                for term in &[a, b, c] {
+                    term.registers(regs);
+                }
+            }
+            Term::Divisor {
+                value,
+                target,
+                from,
+                to,
+            } => {
+                for term in &[value, target, from, to] {
+                    term.registers(regs);
+                }
+            }
+        }
+    }
+
+    /// Whether the term only reads registers for which `allowed` is true.
+    fn reads_only(&self, allowed: impl Fn(usize) -> bool) -> bool {
+        let mut regs = BTreeSet::new();
+        self.registers(&mut regs);
+        regs.into_iter().all(allowed)
+    }
+
+    fn reads(&self, r: usize) -> bool {
+        !self.reads_only(|reg| reg != r)
+    }
+
+    /// Whether the term is `a * counter + b` with `a` and `b` independent of the counter.
+    fn is_linear(&self, counter: usize) -> bool {
+        match self {
+            Term::Binary(BinOp::Add, a, b) | Term::Sub(a, b) => {
+                a.is_linear(counter) && b.is_linear(counter)
+            }
+            Term::Binary(BinOp::Mul, a, b) => {
+                (a.is_linear(counter) && !b.reads(counter))
+                    || (!a.reads(counter) && b.is_linear(counter))
+            }
+            term => *term == Term::Reg(counter) || !term.reads(counter),
+        }
+    }
+}
+
+/// Sum of a term over the iterations of a fused loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Sum {
+    /// The same term in each iteration.
+    Constant(Term),
+    /// A linear term of the counter.
+    Linear(Term),
+    /// `value` in each iteration where `lhs == rhs`, with `lhs` linear in the counter.
+    Hit { lhs: Term, rhs: Term, value: Term },
+    /// The counter if it is a divisor of `target` with a quotient in `from..=to`.
+    Divisors { target: Term, from: Term, to: Term },
+}
+
+/// Sum of the divisors of `target` in `lo..=hi` with a quotient in `from..=to`.
+fn divisor_sum(target: Value, from: Value, to: Value, lo: Value, hi: Value) -> Option<Value> {
+    if target < 1 || lo < 1 {
+        return None;
+    }
+    let mut sum: Value = 0;
+    let mut i = 1;
+    while i * i <= target {
+        if target % i == 0 {
+            let pair = [i, target / i];
+            let divisors = if pair[0] == pair[1] {
+                &pair[..1]
+            } else {
+                &pair[..]
+            };
+            for &d in divisors {
+                let q = target / d;
+                if lo <= d && d <= hi && from <= q && q <= to {
+                    sum = sum.checked_add(d)?;
+                }
+            }
+        }
+        i += 1;
+    }
+    Some(sum)
+}
+
+/// One iteration of a loop: if `until` holds at the exit test, the loop is left with the
+/// registers `pre`, otherwise it goes on with the registers `step`.
+struct Iteration {
+    pre: Vec<Term>,
+    step: Vec<Term>,
+    until: Term,
+    /// Instruction after the loop.
+    exit: usize,
+    body: BTreeSet<usize>,
+}
+
+/// Applies the instructions `pcs` to `state`.
+fn execute(program: &Program, pcs: std::ops::Range<usize>, state: &mut [Term]) {
+    for pc in pcs {
+        let instr = &program.instructions[pc];
+        let term = match Expr::of(instr, program.ip, pc) {
+            Expr::Atom(a) => Term::atom(a, state),
+            Expr::Binary(op, a, b) => Term::binary(op, Term::atom(a, state), Term::atom(b, state)),
+        };
+        state[instr.c as usize] = term;
+    }
+}
+
+/// Evaluates an iteration of `lp`, which must be a path from the header back to it with a single
+/// exit and `if` without `else` on the way.
+fn iteration(
+    program: &Program,
+    cfg: &Cfg,
+    lp: &Loop,
+    fused: &[Option<Fused>],
+    registers: usize,
+) -> Option<Iteration> {
+    let mut state: Vec<Term> = (0..registers).map(Term::Reg).collect();
+    let mut leave = None;
+    let mut visited = BTreeSet::new();
+    let mut body = BTreeSet::new();
+    let mut node = lp.header;
+    while node != lp.header || visited.is_empty() {
+        if !lp.body.contains(&node) || !visited.insert(node) {
+            return None;
+        }
+        let block = &cfg.blocks[node];
+        if let Some(inner) = fused[block.range.start].as_ref() {
+            if node != lp.header {
+                body.extend(inner.body.iter().cloned());
+                state = inner.summary(&state)?;
+                node = cfg.block_at(inner.exit)?;
+                continue;
+            }
+        }
+        body.extend(block.range.clone());
+        execute(program, block.body.clone(), &mut state);
+        match block.exit {
+            cfg::Exit::Goto(target) => node = target,
+            cfg::Exit::Branch { then, els, .. } => {
+                let jump = block.range.end - 1;
+                let cond = state[program.instructions[jump - 1].c as usize].clone();
+                match (lp.body.contains(&then), lp.body.contains(&els)) {
+                    (false, true) if leave.is_none() => {
+                        leave = Some((cond, state.clone(), jump + 2));
+                        node = els;
+                    }
+                    (true, false) if leave.is_none() => {
+                        let negated = Term::binary(BinOp::Eq, cond, Term::Const(0));
+                        leave = Some((negated, state.clone(), jump + 1));
+                        node = then;
+                    }
+                    (true, true) => {
+                        let mut is_arm = |arm: usize, join: usize| {
+                            let block = &cfg.blocks[arm];
+                            arm != lp.header
+                                && block.exit == cfg::Exit::Goto(join)
+                                && fused[block.range.start].is_none()
+                                && visited.insert(arm)
+                        };
+                        let (arm, join) = if is_arm(then, els) {
+                            (then, els)
+                        } else if is_arm(els, then) {
+                            (els, then)
+                        } else {
+                            return None;
+                        };
+                        body.extend(cfg.blocks[arm].range.clone());
+                        let mut taken = state.clone();
+                        execute(program, cfg.blocks[arm].body.clone(), &mut taken);
+                        for (old, new) in state.iter_mut().zip(taken) {
+                            *old = if arm == then {
+                                Term::select(cond.clone(), new, old.clone())
+                            } else {
+                                Term::select(cond.clone(), old.clone(), new)
+                            };
+                        }
+                        node = join;
+                    }
+                    _ => return None,
+                }
+            }
+            cfg::Exit::Computed { .. } => return None,
+        }
+    }
+    let (until, pre, exit) = leave?;
+    Some(Iteration {
+        pre,
+        step: state,
+        until,
+        exit: Some(exit).filter(|&exit| exit < program.len())?,
+        body,
+    })
+}
+
+/// A loop replaced by a native operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fused {
+    /// First instruction of the loop.
+    pub start: usize,
+    /// Instruction after the loop.
+    pub exit: usize,
+    /// Instructions of the loop, except for jumps to other jumps.
+    pub body: BTreeSet<usize>,
+    counter: usize,
+    stride: Value,
+    /// The loop is left if `lhs > rhs`, or `lhs == rhs` for `Eq`.
+    until: (BinOp, Term, Term),
+    sums: Vec<(usize, Sum)>,
+    /// Registers set in each iteration, in terms of the counter and the unchanged registers.
+    temps: Vec<(usize, Term)>,
+    pre: Vec<Term>,
+    step: Vec<Term>,
+}
+
+impl Fused {
+    fn new(start: usize, iteration: Iteration) -> Option<Fused> {
+        let Iteration {
+            pre,
+            step,
+            until,
+            exit,
+            body,
+        } = iteration;
+        let (op, lhs, rhs) = match until {
+            Term::Binary(op, lhs, rhs) if op.is_comparison() => (op, *lhs, *rhs),
+            _ => return None,
+        };
+        // !(a > b) is b + 1 > a, while a != b can't be solved
+        let until = match (op, lhs, rhs) {
+            (BinOp::Eq, Term::Binary(BinOp::Gt, a, b), Term::Const(0)) => {
+                (BinOp::Gt, Term::add(*b, Term::Const(1)), *a)
+            }
+            (BinOp::Eq, Term::Binary(BinOp::Eq, _, _), Term::Const(0)) => return None,
+            until => until,
+        };
+
+        let invariant: Vec<bool> = (0..step.len()).map(|r| step[r] == Term::Reg(r)).collect();
+        let (counter, stride) = (0..step.len()).find_map(|r| match &step[r] {
+            Term::Binary(BinOp::Add, a, b)
+                if **a == Term::Reg(r) && (until.1.reads(r) || until.2.reads(r)) =>
+            {
+                match **b {
+                    Term::Const(stride) if stride != 0 => Some((r, stride)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })?;
+        let known = |r: usize| invariant[r] || r == counter;
+        if [&until.1, &until.2]
+            .iter()
+            .any(|term| !term.reads_only(known) || !term.is_linear(counter))
+        {
+            return None;
+        }
+
+        let mut sums = Vec::new();
+        let mut temps = Vec::new();
+        for (r, term) in step.iter().enumerate() {
+            if invariant[r] || r == counter {
+                continue;
+            }
+            if term.reads_only(known) {
+                temps.push((r, term.clone()));
+                continue;
+            }
+            let acc = Term::Reg(r);
+            let added = |term: &Term| match term {
+                Term::Binary(BinOp::Add, a, b) if **a == acc => Some((**b).clone()),
+                Term::Binary(BinOp::Add, a, b) if **b == acc => Some((**a).clone()),
+                _ => None,
+            };
+            let increment = match term {
+                Term::Select(cond, then, els) if **els == acc => {
+                    Term::select((**cond).clone(), added(then)?, Term::Const(0))
+                }
+                Term::Select(cond, then, els) if **then == acc => {
+                    Term::select((**cond).clone(), Term::Const(0), added(els)?)
+                }
+                term => added(term)?,
+            };
+            if !increment.reads_only(known) {
+                return None;
+            }
+            sums.push((r, Sum::new(increment, counter, stride, &known)?));
+        }
+        Some(Fused {
+            start,
+            exit,
+            body,
+            counter,
+            stride,
+            until,
+            sums,
+            temps,
+            pre,
+            step,
+        })
+    }
+
+    /// Registers at the start of iteration `k`, except for the sums and temporary registers.
+    fn at(&self, regs: &[Value], k: Value) -> Option<Vec<Value>> {
+        let mut regs = regs.to_vec();
+        let counter = &mut regs[self.counter];
+        *counter = counter.checked_add(k.checked_mul(self.stride)?)?;
+        Some(regs)
+    }
+
+    /// Values of `term` at the start of the first two iterations.
+    fn first_two(&self, term: &Term, regs: &[Value]) -> Option<(Value, Value)> {
+        Some((
+            term.eval(&self.at(regs, 0)?)?,
+            term.eval(&self.at(regs, 1)?)?,
+        ))
+    }
+
+    /// Number of iterations before the one in which the loop is left.
+    fn iterations(&self, regs: &[Value]) -> Option<Value> {
+        let (op, lhs, rhs) = &self.until;
+        let (l0, l1) = self.first_two(lhs, regs)?;
+        let (r0, r1) = self.first_two(rhs, regs)?;
+        // lhs - rhs is linear in the iterations
+        let diff = l0.checked_sub(r0)?;
+        let slope = l1.checked_sub(r1)?.checked_sub(diff)?;
+        match op {
+            BinOp::Gt if diff > 0 => Some(0),
+            BinOp::Gt if slope > 0 => Some(-diff / slope + 1),
+            BinOp::Eq if diff == 0 => Some(0),
+            BinOp::Eq if slope != 0 && -diff % slope == 0 && -diff / slope > 0 => {
+                Some(-diff / slope)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&self, sum: &Sum, regs: &[Value], n: Value) -> Option<Value> {
+        match sum {
+            Sum::Constant(term) => term.eval(regs)?.checked_mul(n),
+            Sum::Linear(term) => {
+                let (first, second) = self.first_two(term, regs)?;
+                let series = n.checked_mul(n - 1)? / 2;
+                let slope = second.checked_sub(first)?;
+                n.checked_mul(first)?
+                    .checked_add(slope.checked_mul(series)?)
+            }
+            Sum::Hit { lhs, rhs, value } => {
+                let (l0, l1) = self.first_two(lhs, regs)?;
+                let diff = l0.checked_sub(rhs.eval(regs)?)?;
+                let slope = l1.checked_sub(l0)?;
+                let hits = match slope {
+                    0 if diff == 0 => n,
+                    0 => 0,
+                    _ if -diff % slope == 0 && (0..n).contains(&(-diff / slope)) => 1,
+                    _ => 0,
+                };
+                value.eval(regs)?.checked_mul(hits)
+            }
+            Sum::Divisors { target, from, to } => {
+                let lo = regs[self.counter];
+                divisor_sum(
+                    target.eval(regs)?,
+                    from.eval(regs)?,
+                    to.eval(regs)?,
+                    lo,
+                    lo.checked_add(n - 1)?,
+                )
+            }
+        }
+    }
+
+    /// Registers when leaving the loop which starts with the registers `regs`.
+    fn leave(&self, regs: &[Value]) -> Option<Vec<Value>> {
+        let k = self.iterations(regs)?;
+        let mut last = self.at(regs, k)?;
+        for (r, sum) in &self.sums {
+            last[*r] = regs[*r].checked_add(self.sum(sum, regs, k)?)?;
+        }
+        if k > 0 {
+            let before = self.at(regs, k - 1)?;
+            for (r, term) in &self.temps {
+                last[*r] = term.eval(&before)?;
+            }
+        }
+        self.pre.iter().map(|term| term.eval(&last)).collect()
+    }
+
+    /// Runs the loop on `regs`; false if it has to run instruction by instruction.
+    pub fn apply(&self, regs: &mut [Value]) -> bool {
+        match self.leave(regs) {
+            Some(out) => {
+                regs.copy_from_slice(&out);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Registers at the start of iteration `n`, in terms of the registers at the start of the
+    /// loop.
+    ///
+    /// The temporary registers need `n > 0`, or `positive` false to keep them for `n == 0`.
+    fn closed(&self, n: &Term, positive: bool) -> Option<Vec<Term>> {
+        let c = self.counter;
+        let mut state: Vec<Term> = (0..self.step.len()).map(Term::Reg).collect();
+        let last = Term::add(Term::Reg(c), Term::sub(n.clone(), Term::Const(1)));
+        state[c] = Term::add(Term::Reg(c), n.clone());
+        for (r, sum) in &self.sums {
+            let total = match sum {
+                Sum::Constant(term) => Term::binary(BinOp::Mul, n.clone(), term.clone()),
+                Sum::Hit { lhs, rhs, value } => match lhs {
+                    Term::Binary(BinOp::Mul, a, b)
+                        if (**a == *value && **b == Term::Reg(c))
+                            || (**b == *value && **a == Term::Reg(c)) =>
+                    {
+                        Term::Divisor {
+                            value: Box::new(value.clone()),
+                            target: Box::new(rhs.clone()),
+                            from: Box::new(Term::Reg(c)),
+                            to: Box::new(last.clone()),
+                        }
+                    }
+                    _ => return None,
+                },
+                Sum::Linear(_) | Sum::Divisors { .. } => return None,
+            };
+            state[*r] = Term::add(Term::Reg(*r), total);
+        }
+        let mut before: Vec<Term> = (0..self.step.len()).map(Term::Reg).collect();
+        before[c] = last;
+        for (r, term) in &self.temps {
+            let value = term.subst(&before);
+            state[*r] = if positive {
+                value
+            } else {
+                Term::select(n.clone(), value, Term::Reg(*r))
+            };
+        }
+        Some(state)
+    }
+
+    /// Registers when leaving the loop in terms of `state`, the registers when entering it.
+    ///
+    /// Only loops counting up by one until `counter + e > bound` have a closed form.
+    fn summary(&self, state: &[Term]) -> Option<Vec<Term>> {
+        let c = self.counter;
+        let e = match &self.until {
+            (BinOp::Gt, Term::Reg(r), _) if *r == c => 0,
+            (BinOp::Gt, Term::Binary(BinOp::Add, a, b), _) if **a == Term::Reg(c) => match **b {
+                Term::Const(e) => e,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let bound = &self.until.2;
+        if self.stride != 1 || bound.reads(c) {
+            return None;
+        }
+        let k = Term::max(
+            Term::sub(Term::add(bound.clone(), Term::Const(1 - e)), Term::Reg(c)),
+            Term::Const(0),
+        );
+        let last = self.closed(&k, false)?;
+        let after = self.closed(&Term::add(k, Term::Const(1)), true)?;
+        let leave = (0..self.step.len()).map(|r| {
+            // registers set before the exit test are set once more in the last iteration
+            let term = if self.pre[r] == self.step[r] {
+                after[r].clone()
+            } else {
+                self.pre[r].subst(&last)
+            };
+            term.subst(state)
+        });
+        Some(leave.collect())
+    }
+}
+
+impl Sum {
+    fn new(
+        term: Term,
+        counter: usize,
+        stride: Value,
+        known: &impl Fn(usize) -> bool,
+    ) -> Option<Sum> {
+        let invariant = |term: &Term| term.reads_only(|r| r != counter && known(r));
+        Some(match term {
+            term if invariant(&term) => Sum::Constant(term),
+            term if term.is_linear(counter) => Sum::Linear(term),
+            Term::Select(cond, value, els) if *els == Term::Const(0) && invariant(&value) => {
+                match *cond {
+                    Term::Binary(BinOp::Eq, a, b)
+                        if a.is_linear(counter) && b.is_linear(counter) =>
+                    {
+                        let (lhs, rhs) = if invariant(&b) { (*a, *b) } else { (*b, *a) };
+                        if !invariant(&rhs) {
+                            return None;
+                        }
+                        Sum::Hit {
+                            lhs,
+                            rhs,
+                            value: *value,
+                        }
+                    }
+                    _ => return None,
+                }
+            }
+            Term::Divisor {
+                value,
+                target,
+                from,
+                to,
+            } if *value == Term::Reg(counter)
+                && stride == 1
+                && [&target, &from, &to].iter().all(|term| invariant(term)) =>
+            {
+                Sum::Divisors {
+                    target: *target,
+                    from: *from,
+                    to: *to,
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// A program with fused loops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Optimized {
+    pub program: Program,
+    pub registers: usize,
+    /// Fused loop by its first instruction.
+    pub(crate) fused: Vec<Option<Fused>>,
+}
+
+impl Optimized {
+    /// Fused loops by their first instruction.
+    pub fn fused(&self) -> impl Iterator<Item = &Fused> {
+        self.fused.iter().flatten()
+    }
+
+    /// Runs the loops containing the instruction `pc` instruction by instruction, e.g. to watch
+    /// it.
+    pub fn unfuse(&mut self, pc: usize) {
+        for slot in &mut self.fused {
+            if slot.as_ref().is_some_and(|fused| fused.body.contains(&pc)) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// Fuses the loops of `program` on a device with `registers` registers, inner loops first.
+pub fn optimize(program: &Program, registers: usize) -> Optimized {
+    let mut fused = vec![None; program.len()];
+    let valid = program.ip.is_some_and(|ip| ip < registers)
+        && program
+            .instructions
+            .iter()
+            .all(|instr| instr.invalid_register(registers).is_none());
+    if valid {
+        let cfg = Cfg::new(program);
+        let mut loops = cfg.loops();
+        loops.sort_by_key(|lp| lp.body.len());
+        for lp in loops {
+            let start = cfg.blocks[lp.header].range.start;
+            fused[start] = iteration(program, &cfg, &lp, &fused, registers)
+                .and_then(|iteration| Fused::new(start, iteration));
+        }
+    }
+    Optimized {
+        program: program.clone(),
+        registers,
+        fused,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{DAY19, DAY21};
+    use crate::vm::{Exit, Vm};
+
+    /// Runs `program` with and without fused loops, compares the results and returns whether
+    /// a fused loop saved instructions.
+    fn check(program: &Program, regs: &[Value], budget: u64) -> bool {
+        let optimized = optimize(program, regs.len());
+        let mut plain = Vm::new(program, regs.len()).unwrap();
+        let mut fast = Vm::optimized(&optimized).unwrap();
+        plain.registers_mut().copy_from_slice(regs);
+        fast.registers_mut().copy_from_slice(regs);
+        let exit = plain.run(budget);
+        assert_eq!(fast.run(budget), exit, "{:?}", regs);
+        if exit == Exit::Halted {
+            assert_eq!(fast.registers(), plain.registers(), "{:?}", regs);
+        }
+        exit == Exit::Halted && fast.steps() < plain.steps()
+    }
+
+    #[test]
+    fn test_day19() {
+        let program: Program = DAY19.parse().unwrap();
+        let optimized = optimize(&program, 6);
+        let starts: Vec<usize> = optimized.fused().map(|f| f.start).collect();
+        assert_eq!(starts, vec![2, 3]);
+        assert!(check(&program, &[0; 6], 10_000_000));
+
+        let mut outer = optimized.clone();
+        outer.unfuse(13);
+        assert_eq!(outer.fused().map(|f| f.start).collect::<Vec<_>>(), vec![3]);
+        outer.unfuse(7);
+        assert_eq!(outer.fused().count(), 0);
+
+        let mut vm = Vm::optimized(&optimized).unwrap();
+        vm.registers_mut()[0] = 1;
+        assert_eq!(vm.run(100), Exit::Halted);
+        assert_eq!(vm.registers()[0], 22302144);
+    }
+
+    #[test]
+    fn test_day21() {
+        let program: Program = DAY21.parse().unwrap();
+        let optimized = optimize(&program, 6);
+        let fused: Vec<(usize, usize)> = optimized.fused().map(|f| (f.start, f.exit)).collect();
+        assert_eq!(fused, vec![(18, 23)]);
+        assert!(check(&program, &[12980435, 0, 0, 0, 0, 0], 100_000));
+        assert!(!check(&program, &[1, 0, 0, 0, 0, 0], 100_000));
+    }
+
+    #[test]
+    fn test_idioms() {
+        let programs = [
+            // r0 += r1 while r2 counts up to r3
+            "#ip 4\naddr 0 1 0\naddi 2 1 2\ngtrr 2 3 5\naddr 4 5 4\nseti -1 0 4\naddi 0 0 0",
+            // r0 += r2, r1 += 3 while r2 counts up to r3, tested first
+            "#ip 4\neqrr 2 3 5\naddr 4 5 4\nseti 3 0 4\nseti 8 0 4\naddr 0 2 0\naddi 1 3 1\n\
+             addi 2 1 2\nseti -1 0 4\naddi 0 0 0\naddi 0 0 0",
+            // r0 += r1 while r2 counts down to r3
+            "#ip 4\naddr 0 1 0\naddi 2 -1 2\ngtrr 2 3 5\naddr 4 5 4\nseti 5 0 4\n\
+             seti -1 0 4\naddi 0 0 0",
+            // r0 = r1 / 3 when counting up from 0
+            "#ip 4\naddi 0 1 2\nmuli 2 3 2\ngtrr 2 1 2\naddr 2 4 4\naddi 4 1 4\nseti 7 0 4\n\
+             addi 0 1 0\nseti -1 0 4\nseti 0 0 5",
+            // r0 += r1 if r1 * r2 == r3 for r2 up to r3
+            "#ip 4\nmulr 1 2 5\neqrr 5 3 5\naddr 5 4 4\naddi 4 1 4\naddr 1 0 0\naddi 2 1 2\n\
+             gtrr 2 3 5\naddr 4 5 4\nseti -1 0 4\naddi 0 0 0",
+        ];
+        for (idx, text) in programs.iter().enumerate() {
+            let program: Program = text.parse().unwrap();
+            assert_eq!(optimize(&program, 6).fused().count(), 1, "program {}", idx);
+            let mut fused = 0;
+            for &r1 in &[-2, 0, 3, 7] {
+                for r2 in -3..=4 {
+                    for r3 in -3..=12 {
+                        fused += check(&program, &[5, r1, r2, r3, 0, 0], 1000) as usize;
+                    }
+                }
+            }
+            assert!(fused > 0, "program {}", idx);
+        }
+    }
+}
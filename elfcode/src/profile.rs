@@ -0,0 +1,293 @@
+//! Profiling runs which count how often each instruction is executed and which jumps are taken.
+//!
+//! A jump is taken through the instruction pointer register, so the profile records the
+//! instruction after each instruction writing it, and each time the next instruction isn't the
+//! following one. A jump to the same or an earlier instruction is a back-edge of a loop, which is
+//! how the programs of the puzzles loop.
+
+use crate::cfg::Cfg;
+use crate::program::Program;
+use crate::vm::{Exit, Vm};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// Number of executions of each instruction.
+    pub counts: Vec<u64>,
+    /// Number of jumps by the instruction and the next one, which is the length of the program
+    /// for halting.
+    pub jumps: BTreeMap<(usize, usize), u64>,
+}
+
+/// A loop found by its back-edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotLoop {
+    /// The jump back to `start`.
+    pub end: usize,
+    pub start: usize,
+    /// How often the jump back is taken.
+    pub iterations: u64,
+    /// Executions of the instructions from `start` to `end`.
+    pub executions: u64,
+}
+
+fn writes_ip(program: &Program, pc: usize) -> bool {
+    program
+        .ip
+        .is_some_and(|ip| program.instructions[pc].c as usize == ip)
+}
+
+impl Profile {
+    pub fn new(program: &Program) -> Self {
+        Self {
+            counts: vec![0; program.len()],
+            jumps: BTreeMap::new(),
+        }
+    }
+
+    /// Profiles `vm` until its program halts or `budget` instructions were executed.
+    ///
+    /// A fused loop of an optimized `Vm` counts as one execution of its first instruction.
+    pub fn run(vm: &mut Vm, budget: u64) -> (Self, Exit) {
+        let mut profile = Self::new(vm.program());
+        for _ in 0..budget {
+            if !profile.step(vm) {
+                return (profile, Exit::Halted);
+            }
+        }
+        let exit = if vm.is_halted() {
+            Exit::Halted
+        } else {
+            Exit::OutOfBudget
+        };
+        (profile, exit)
+    }
+
+    /// Executes one instruction of `vm` and records it; false if the program already halted.
+    pub fn step(&mut self, vm: &mut Vm) -> bool {
+        let pc = match vm.ip() {
+            Some(pc) => pc,
+            None => return false,
+        };
+        vm.step();
+        self.counts[pc] += 1;
+        let next = vm.ip().unwrap_or_else(|| vm.program().len());
+        if next != pc + 1 || writes_ip(vm.program(), pc) {
+            *self.jumps.entry((pc, next)).or_insert(0) += 1;
+        }
+        true
+    }
+
+    /// Number of executed instructions.
+    pub fn steps(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Loops whose back-edges were taken, the most iterations first.
+    pub fn hot_loops(&self) -> Vec<HotLoop> {
+        let mut loops: Vec<HotLoop> = self
+            .jumps
+            .iter()
+            .filter(|&(&(from, to), _)| to <= from)
+            .map(|(&(end, start), &iterations)| HotLoop {
+                end,
+                start,
+                iterations,
+                executions: self.counts[start..=end].iter().sum(),
+            })
+            .collect();
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.iterations), l.start, l.end));
+        loops
+    }
+
+    /// The instructions of `program` sorted by executions with their jumps, then the hot loops.
+    pub fn listing(&self, program: &Program) -> String {
+        let steps = self.steps().max(1);
+        let share = |count: u64| 100.0 * count as f64 / steps as f64;
+        let width = self.counts.iter().max().map_or(1, |c| c.to_string().len());
+        let pc_width = program.len().saturating_sub(1).to_string().len();
+        let mut order: Vec<usize> = (0..program.len()).collect();
+        order.sort_by_key(|&pc| (std::cmp::Reverse(self.counts[pc]), pc));
+
+        let mut out = String::new();
+        for pc in order {
+            let count = self.counts[pc];
+            let instr = &program.instructions[pc];
+            write!(
+                out,
+                "{:>w$} {:>5.1}% {:>pw$}: {}",
+                count,
+                share(count),
+                pc,
+                instr,
+                w = width,
+                pw = pc_width
+            )
+            .unwrap();
+            let jumps = self.jumps.range((pc, 0)..(pc + 1, 0));
+            for (i, (&(_, to), &taken)) in jumps.enumerate() {
+                let separator = if i == 0 { "  " } else { ", " };
+                let target = if to == program.len() {
+                    "halt".to_string()
+                } else {
+                    to.to_string()
+                };
+                let back = if to <= pc { " back" } else { "" };
+                write!(out, "{}-> {} x{}{}", separator, target, taken, back).unwrap();
+            }
+            out.push('\n');
+        }
+        let loops = self.hot_loops();
+        if !loops.is_empty() {
+            out.push_str("\nhot loops:\n");
+        }
+        for l in loops {
+            writeln!(
+                out,
+                "{:>pw$}..={:<pw$} {} iterations, {} instructions ({:.1}%)",
+                l.start,
+                l.end,
+                l.iterations,
+                l.executions,
+                share(l.executions),
+                pw = pc_width
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// Graphviz graph of the blocks of `program` with the executed edges labelled by their
+    /// counts; back-edges are red.
+    pub fn dot(&self, program: &Program) -> String {
+        let cfg = Cfg::new(program);
+        let block_of = |pc: usize| {
+            cfg.blocks
+                .iter()
+                .position(|block| block.range.contains(&pc))
+                .unwrap_or_else(|| cfg.halt())
+        };
+        let mut edges: BTreeMap<(usize, usize, bool), u64> = BTreeMap::new();
+        for (&(from, to), &count) in &self.jumps {
+            *edges
+                .entry((block_of(from), block_of(to), to <= from))
+                .or_insert(0) += count;
+        }
+        for block in &cfg.blocks {
+            let last = block.range.end - 1;
+            let falls_through = !writes_ip(program, last) && self.counts[last] > 0;
+            if falls_through {
+                let edge = (block_of(last), block_of(last + 1), false);
+                // jumps out of an instruction which doesn't write the ip are recorded already
+                let jumped: u64 = self
+                    .jumps
+                    .range((last, 0)..(last + 1, 0))
+                    .map(|j| j.1)
+                    .sum();
+                *edges.entry(edge).or_insert(0) += self.counts[last] - jumped;
+            }
+        }
+        let max = edges.values().cloned().max().unwrap_or(1) as f64;
+
+        let mut out = String::from("digraph profile {\n    node [shape=box fontname=monospace];\n");
+        for (idx, block) in cfg.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for pc in block.range.clone() {
+                let instr = &program.instructions[pc];
+                write!(label, "{}: {}  x{}\\l", pc, instr, self.counts[pc]).unwrap();
+            }
+            writeln!(out, "    b{} [label=\"{}\"];", idx, label).unwrap();
+        }
+        writeln!(out, "    b{} [label=\"halt\" shape=oval];", cfg.halt()).unwrap();
+        for ((from, to, back), count) in edges {
+            if count == 0 {
+                continue;
+            }
+            let width = 1.0 + 4.0 * count as f64 / max;
+            let color = if back { " color=red" } else { "" };
+            writeln!(
+                out,
+                "    b{} -> b{} [label=\"{}\" penwidth={:.1}{}];",
+                from, to, count, width, color
+            )
+            .unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::testdata::DAY21;
+
+    #[test]
+    fn test_profile() {
+        let source = "\
+#ip 3
+.tmp r2
+    seti 3 0 1
+loop:
+    addi 0 1 0
+    addi 1 -1 1
+    jnz r1 loop
+";
+        let program = assemble(source, 4).unwrap();
+        let mut vm = Vm::new(&program, 4).unwrap();
+        let (profile, exit) = Profile::run(&mut vm, 1000);
+        assert_eq!(exit, Exit::Halted);
+        assert_eq!(vm.registers()[0], 3);
+        assert_eq!(profile.counts, vec![1, 3, 3, 3, 3, 2]);
+        assert_eq!(profile.steps(), 15);
+        assert_eq!(
+            profile.hot_loops(),
+            vec![HotLoop {
+                end: 5,
+                start: 1,
+                iterations: 2,
+                executions: 14
+            }]
+        );
+        assert_eq!(
+            profile.listing(&program),
+            "\
+3  20.0% 1: addi 0 1 0
+3  20.0% 2: addi 1 -1 1
+3  20.0% 3: eqri 1 0 2
+3  20.0% 4: addr 2 3 3  -> 5 x2, -> halt x1
+2  13.3% 5: seti 0 0 3  -> 1 x2 back
+1   6.7% 0: seti 3 0 1
+
+hot loops:
+1..=5 2 iterations, 14 instructions (93.3%)
+"
+        );
+        let dot = profile.dot(&program);
+        assert!(
+            dot.contains("b0 -> b1 [label=\"1\" penwidth=3.0];"),
+            "{}",
+            dot
+        );
+        assert!(
+            dot.contains("-> b1 [label=\"2\" penwidth=5.0 color=red];"),
+            "{}",
+            dot
+        );
+        assert!(dot.contains("[label=\"halt\" shape=oval];"), "{}", dot);
+    }
+
+    #[test]
+    fn test_day21() {
+        let program: Program = DAY21.parse().unwrap();
+        let mut vm = Vm::new(&program, 6).unwrap();
+        let (profile, exit) = Profile::run(&mut vm, 100_000);
+        assert_eq!(exit, Exit::OutOfBudget);
+        assert_eq!(profile.steps(), 100_000);
+        // the division by 256 is the inner loop
+        let hottest = &profile.hot_loops()[0];
+        assert_eq!((hottest.start, hottest.end), (18, 25));
+    }
+}
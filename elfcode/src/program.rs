@@ -0,0 +1,318 @@
+//! Instructions and programs in the text format of the puzzles.
+//!
+//! ```text
+//! #ip 0
+//! seti 5 0 1
+//! addi 0 1 0
+//! ```
+//!
+//! The `#ip` line binds the instruction pointer to a register and is optional; programs without
+//! it can't jump.
+
+use crate::opcode::{Opcode, Operand, Value};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of registers of the device in days 19 and 21.
+pub const DEFAULT_REGISTERS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    pub op: Opcode,
+    pub a: Value,
+    pub b: Value,
+    pub c: Value,
+}
+
+impl Instruction {
+    pub fn new(op: Opcode, a: Value, b: Value, c: Value) -> Self {
+        Self { op, a, b, c }
+    }
+
+    /// Registers read by the instruction.
+    pub fn inputs(&self) -> impl Iterator<Item = Value> {
+        let (kind_a, kind_b) = self.op.operands();
+        let a = Some(self.a).filter(|_| kind_a == Operand::Register);
+        let b = Some(self.b).filter(|_| kind_b == Operand::Register);
+        a.into_iter().chain(b)
+    }
+
+    /// Register written by the instruction.
+    pub fn output(&self) -> Value {
+        self.c
+    }
+
+    /// First register out of `0..registers`, if any.
+    pub fn invalid_register(&self, registers: usize) -> Option<Value> {
+        self.inputs()
+            .chain(Some(self.c))
+            .find(|&r| r < 0 || r as usize >= registers)
+    }
+
+    /// Executes the instruction on `regs`.
+    ///
+    /// Panics if a register operand is out of range, which `invalid_register` checks in advance.
+    pub fn execute(&self, regs: &mut [Value]) {
+        let (kind_a, kind_b) = self.op.operands();
+        let value = |kind, operand| match kind {
+            Operand::Register => regs[operand as usize],
+            Operand::Immediate => operand,
+            Operand::Ignored => 0,
+        };
+        let result = self.op.eval(value(kind_a, self.a), value(kind_b, self.b));
+        regs[self.c as usize] = result;
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.op, self.a, self.b, self.c)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Program {
+    /// Register bound to the instruction pointer.
+    pub ip: Option<usize>,
+    pub instructions: Vec<Instruction>,
+}
+
+impl Program {
+    /// Parses a program for a device with `registers` registers.
+    pub fn parse(input: &str, registers: usize) -> Result<Self, ParseError> {
+        let mut program = Program::default();
+        for (i, line) in input.lines().enumerate() {
+            let mut tokens = Tokens::new(i + 1, line);
+            let (col, first) = match tokens.next() {
+                Some(token) => token,
+                None => return Err(tokens.expected("an instruction")),
+            };
+            if first == "#ip" {
+                if i > 0 {
+                    return Err(ParseError::new(i + 1, col, "#ip must be the first line"));
+                }
+                program.ip = Some(tokens.register(registers)? as usize);
+            } else {
+                let op = first
+                    .parse()
+                    .map_err(|e: crate::opcode::UnknownOpcode| ParseError::new(i + 1, col, e))?;
+                let (kind_a, kind_b) = Opcode::operands(op);
+                let a = tokens.operand(kind_a, registers)?;
+                let b = tokens.operand(kind_b, registers)?;
+                let c = tokens.register(registers)?;
+                program.instructions.push(Instruction { op, a, b, c });
+            }
+            tokens.end()?;
+        }
+        Ok(program)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+}
+
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Program::parse(input, DEFAULT_REGISTERS)
+    }
+}
+
+/// Renders the program in the format it is parsed from.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ip) = self.ip {
+            writeln!(f, "#ip {}", ip)?;
+        }
+        for instr in &self.instructions {
+            writeln!(f, "{}", instr)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error located by line and column, both starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(line: usize, col: usize, message: impl ToString) -> Self {
+        Self {
+            line,
+            col,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} col {}: {}", self.line, self.col, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Whitespace separated tokens of a line with their columns.
+pub(crate) struct Tokens<'a> {
+    line: usize,
+    /// Column after the last token.
+    end: usize,
+    rest: std::iter::Peekable<std::str::CharIndices<'a>>,
+    text: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    pub(crate) fn new(line: usize, text: &'a str) -> Self {
+        Self {
+            line,
+            end: 1,
+            rest: text.char_indices().peekable(),
+            text,
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Option<(usize, &'a str)> {
+        while let Some(&(_, c)) = self.rest.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.rest.next();
+            self.end += 1;
+        }
+        let (start, _) = *self.rest.peek()?;
+        let col = self.end;
+        let mut stop = self.text.len();
+        while let Some(&(i, c)) = self.rest.peek() {
+            if c.is_whitespace() {
+                stop = i;
+                break;
+            }
+            self.rest.next();
+            self.end += 1;
+        }
+        Some((col, &self.text[start..stop]))
+    }
+
+    pub(crate) fn expected(&self, what: &str) -> ParseError {
+        ParseError::new(self.line, self.end, format!("expected {}", what))
+    }
+
+    pub(crate) fn number(&mut self) -> Result<(usize, Value), ParseError> {
+        match self.next() {
+            Some((col, token)) => token.parse().map(|n| (col, n)).map_err(|_| {
+                ParseError::new(
+                    self.line,
+                    col,
+                    format!("expected a number, found {:?}", token),
+                )
+            }),
+            None => Err(self.expected("a number")),
+        }
+    }
+
+    fn register(&mut self, registers: usize) -> Result<Value, ParseError> {
+        let (col, r) = self.number()?;
+        if r < 0 || r as usize >= registers {
+            return Err(ParseError::new(
+                self.line,
+                col,
+                format!("no register {}", r),
+            ));
+        }
+        Ok(r)
+    }
+
+    fn operand(&mut self, kind: Operand, registers: usize) -> Result<Value, ParseError> {
+        match kind {
+            Operand::Register => self.register(registers),
+            Operand::Immediate | Operand::Ignored => self.number().map(|(_, n)| n),
+        }
+    }
+
+    pub(crate) fn end(&mut self) -> Result<(), ParseError> {
+        match self.next() {
+            None => Ok(()),
+            Some((col, token)) => Err(ParseError::new(
+                self.line,
+                col,
+                format!("expected end of line, found {:?}", token),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: &str = "#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5
+";
+
+    #[test]
+    fn test_parse() {
+        let program: Program = PROGRAM.parse().unwrap();
+        assert_eq!(program.ip, Some(0));
+        assert_eq!(program.len(), 7);
+        assert_eq!(
+            program.instructions[2],
+            Instruction::new(Opcode::Addi, 0, 1, 0)
+        );
+        assert_eq!(program.to_string(), PROGRAM);
+
+        let error = |input: &str| input.parse::<Program>().unwrap_err().to_string();
+        assert_eq!(
+            error("#ip 0\nseti 5 0 1\nadx 1 2 3"),
+            r#"line 3 col 1: unknown opcode "adx""#
+        );
+        assert_eq!(error("#ip 6"), "line 1 col 5: no register 6");
+        assert_eq!(
+            error("seti 5 0 1\n#ip 0"),
+            "line 2 col 1: #ip must be the first line"
+        );
+        assert_eq!(
+            error("addr 1  x 3"),
+            r#"line 1 col 9: expected a number, found "x""#
+        );
+        assert_eq!(error("addr 1 2"), "line 1 col 9: expected a number");
+        assert_eq!(
+            error("addr 1 2 3 4"),
+            r#"line 1 col 12: expected end of line, found "4""#
+        );
+        // immediates may be out of the register range
+        assert!(Program::parse("seti 7 0 3", 4).is_ok());
+        assert!(Program::parse("setr 7 0 3", 4).is_err());
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut regs = [3, 2, 1, 1];
+        Instruction::new(Opcode::Mulr, 2, 1, 2).execute(&mut regs);
+        assert_eq!(regs, [3, 2, 2, 1]);
+        Instruction::new(Opcode::Seti, 2, 1, 2).execute(&mut regs);
+        assert_eq!(regs, [3, 2, 2, 1]);
+
+        let instr = Instruction::new(Opcode::Gtir, 9, 4, 0);
+        assert_eq!(instr.inputs().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(instr.invalid_register(4), Some(4));
+        assert_eq!(instr.invalid_register(6), None);
+    }
+}
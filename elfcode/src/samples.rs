@@ -0,0 +1,372 @@
+//! Inputs of day 16: samples of instructions with the registers before and after executing them,
+//! then a test program with opcode numbers.
+//!
+//! `generate` makes inputs for a secret mapping of numbers to opcodes and `minimize` picks a few
+//! samples which still determine the mapping, e.g. to test resolvers of the mapping with.
+
+use crate::infer::{infer, InferError, Sample};
+use crate::opcode::{Opcode, Value};
+use crate::program::{Instruction, ParseError, Tokens};
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of registers of the device in day 16.
+pub const REGISTERS: usize = 4;
+
+/// An instruction with an opcode number instead of an opcode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Numbered {
+    pub number: usize,
+    pub a: Value,
+    pub b: Value,
+    pub c: Value,
+}
+
+impl Numbered {
+    /// The instruction if the number stands for `op`.
+    pub fn with(&self, op: Opcode) -> Instruction {
+        Instruction::new(op, self.a, self.b, self.c)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Input {
+    pub samples: Vec<Sample>,
+    pub program: Vec<Numbered>,
+}
+
+impl Input {
+    /// The registers after running the test program with `mapping`, all 0 before.
+    ///
+    /// Panics if a number isn't mapped or an operand isn't a register.
+    pub fn run(&self, mapping: &[Opcode]) -> Vec<Value> {
+        let mut regs = vec![0; REGISTERS];
+        for instr in &self.program {
+            instr.with(mapping[instr.number]).execute(&mut regs);
+        }
+        regs
+    }
+}
+
+/// The samples which don't behave like the opcode `mapping` gives their number.
+pub fn mismatches(samples: &[Sample], mapping: &[Opcode]) -> Vec<usize> {
+    (0..samples.len())
+        .filter(|&idx| {
+            let sample = &samples[idx];
+            !mapping
+                .get(sample.number)
+                .is_some_and(|&op| sample.behaves_like(op))
+        })
+        .collect()
+}
+
+/// Generator of pseudo-random numbers (xorshift64*), so that an input can be made again from its
+/// seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // the state must not be 0
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// A random mapping of the numbers 0 to 15 to the opcodes.
+pub fn random_mapping(seed: u64) -> Vec<Opcode> {
+    let mut mapping = Opcode::ALL.to_vec();
+    Rng::new(seed).shuffle(&mut mapping);
+    mapping
+}
+
+/// An input with `samples` random samples and a random test program of `len` instructions for
+/// `mapping`, with the registers and operands from 0 to 3 like in the puzzle.
+pub fn generate(mapping: &[Opcode], samples: usize, len: usize, seed: u64) -> Input {
+    let mut rng = Rng::new(seed);
+    let instruction = |rng: &mut Rng| Numbered {
+        number: rng.below(mapping.len()),
+        a: rng.below(REGISTERS) as Value,
+        b: rng.below(REGISTERS) as Value,
+        c: rng.below(REGISTERS) as Value,
+    };
+    let samples = (0..samples)
+        .map(|_| {
+            let before: Vec<Value> = (0..REGISTERS).map(|_| rng.below(4) as Value).collect();
+            let instr = instruction(&mut rng);
+            let mut after = before.clone();
+            instr.with(mapping[instr.number]).execute(&mut after);
+            Sample {
+                before,
+                number: instr.number,
+                a: instr.a,
+                b: instr.b,
+                c: instr.c,
+                after,
+            }
+        })
+        .collect();
+    let program = (0..len).map(|_| instruction(&mut rng)).collect();
+    Input { samples, program }
+}
+
+/// Few of the `samples` which determine the mapping of the numbers `0..numbers` to `opcodes`,
+/// or why all of them don't.
+///
+/// Picks the sample ruling out the most remaining opcodes until the mapping is determined, then
+/// drops the picked samples which aren't needed. No sample can be left out of the result, but
+/// there might be a smaller set.
+pub fn minimize(
+    samples: &[Sample],
+    numbers: usize,
+    opcodes: &[Opcode],
+) -> Result<Vec<Sample>, InferError> {
+    infer(samples, numbers, opcodes)?;
+    let all: u32 = (1 << opcodes.len()) - 1;
+    let ruled_out: Vec<u32> = samples
+        .iter()
+        .map(|sample| {
+            (0..opcodes.len())
+                .filter(|&op| !sample.behaves_like(opcodes[op]))
+                .fold(0, |set, op| set | 1 << op)
+        })
+        .collect();
+    let pick = |picked: &[usize]| -> Vec<Sample> {
+        let mut picked = picked.to_vec();
+        picked.sort_unstable();
+        picked.iter().map(|&idx| samples[idx].clone()).collect()
+    };
+
+    let mut possible = vec![all; numbers];
+    let mut picked = Vec::new();
+    while infer(&pick(&picked), numbers, opcodes).is_err() {
+        // some sample rules out more, since all samples determine the mapping
+        let best = (0..samples.len())
+            .max_by_key(|&idx| {
+                let number = samples[idx].number;
+                let gain = (ruled_out[idx] & possible[number]).count_ones();
+                (gain, std::cmp::Reverse(idx))
+            })
+            .expect("samples");
+        possible[samples[best].number] &= !ruled_out[best];
+        picked.push(best);
+    }
+    for idx in picked.clone().into_iter().rev() {
+        let without: Vec<usize> = picked.iter().cloned().filter(|&i| i != idx).collect();
+        if infer(&pick(&without), numbers, opcodes).is_ok() {
+            picked = without;
+        }
+    }
+    Ok(pick(&picked))
+}
+
+fn registers(line: usize, text: &str, tag: &str) -> Result<Vec<Value>, ParseError> {
+    let rest = text
+        .strip_prefix(tag)
+        .ok_or_else(|| ParseError::new(line, 1, format!("expected {:?}", tag)))?;
+    let start = tag.len() + rest.len() - rest.trim_start().len();
+    let list = rest
+        .trim()
+        .strip_prefix('[')
+        .and_then(|list| list.strip_suffix(']'))
+        .ok_or_else(|| ParseError::new(line, start + 1, "expected [registers]"))?;
+    list.split(',')
+        .map(|value| {
+            value.trim().parse().map_err(|_| {
+                let message = format!("expected a number, found {:?}", value.trim());
+                ParseError::new(line, start + 1, message)
+            })
+        })
+        .collect()
+}
+
+fn numbered(line: usize, text: &str) -> Result<Numbered, ParseError> {
+    let mut tokens = Tokens::new(line, text);
+    let (col, number) = tokens.number()?;
+    if number < 0 {
+        return Err(ParseError::new(line, col, "expected an opcode number"));
+    }
+    let instr = Numbered {
+        number: number as usize,
+        a: tokens.number()?.1,
+        b: tokens.number()?.1,
+        c: tokens.number()?.1,
+    };
+    tokens.end()?;
+    Ok(instr)
+}
+
+impl FromStr for Input {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut input = Input::default();
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, text)| (i + 1, text.trim_end()))
+            .filter(|(_, text)| !text.is_empty());
+        while let Some((line, text)) = lines.next() {
+            if !text.starts_with("Before:") {
+                input.program.push(numbered(line, text)?);
+                continue;
+            }
+            let mut next = |what: &str| {
+                lines.next().ok_or_else(|| {
+                    ParseError::new(line, text.len() + 1, format!("{} missing", what))
+                })
+            };
+            let before = registers(line, text, "Before:")?;
+            let (instr_line, instr_text) = next("instruction")?;
+            let instr = numbered(instr_line, instr_text)?;
+            let (after_line, after_text) = next("After:")?;
+            let after = registers(after_line, after_text, "After:")?;
+            if after.len() != before.len() {
+                let message = format!("expected {} registers", before.len());
+                return Err(ParseError::new(after_line, 1, message));
+            }
+            input.samples.push(Sample {
+                before,
+                number: instr.number,
+                a: instr.a,
+                b: instr.b,
+                c: instr.c,
+                after,
+            });
+        }
+        Ok(input)
+    }
+}
+
+fn write_registers(f: &mut fmt::Formatter, tag: &str, regs: &[Value]) -> fmt::Result {
+    write!(f, "{:<8}[", tag)?;
+    for (i, value) in regs.iter().enumerate() {
+        let separator = if i == 0 { "" } else { ", " };
+        write!(f, "{}{}", separator, value)?;
+    }
+    writeln!(f, "]")
+}
+
+impl fmt::Display for Numbered {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.number, self.a, self.b, self.c)
+    }
+}
+
+/// The format of the puzzle.
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for sample in &self.samples {
+            write_registers(f, "Before:", &sample.before)?;
+            writeln!(
+                f,
+                "{} {} {} {}",
+                sample.number, sample.a, sample.b, sample.c
+            )?;
+            write_registers(f, "After:", &sample.after)?;
+            writeln!(f)?;
+        }
+        if !self.samples.is_empty() {
+            writeln!(f, "\n")?;
+        }
+        for instr in &self.program {
+            writeln!(f, "{}", instr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let text = "\
+Before: [3, 2, 1, 1]
+9 2 1 2
+After:  [3, 2, 2, 1]
+
+
+
+7 3 2 0
+";
+        let input: Input = text.parse().unwrap();
+        assert_eq!(input.samples.len(), 1);
+        assert_eq!(input.samples[0].after, vec![3, 2, 2, 1]);
+        assert_eq!(
+            input.program,
+            vec![Numbered {
+                number: 7,
+                a: 3,
+                b: 2,
+                c: 0
+            }]
+        );
+        assert_eq!(input.to_string(), text);
+
+        let error = |text: &str| text.parse::<Input>().unwrap_err().to_string();
+        assert_eq!(
+            error("Before: [1, 2]\n0 0 0 0"),
+            "line 1 col 15: After: missing"
+        );
+        assert_eq!(
+            error("Before: [1, x]\n0 0 0 0\nAfter: [1, 2]"),
+            "line 1 col 9: expected a number, found \"x\""
+        );
+        assert_eq!(error("-1 0 0 0"), "line 1 col 1: expected an opcode number");
+    }
+
+    #[test]
+    fn test_generate() {
+        let mapping = random_mapping(7);
+        assert_eq!(mapping.len(), 16);
+        assert_ne!(mapping, Opcode::ALL.to_vec());
+        let input = generate(&mapping, 800, 100, 7);
+        assert_eq!(input, generate(&mapping, 800, 100, 7));
+        assert_eq!(input.to_string().parse(), Ok(input.clone()));
+        assert_eq!(mismatches(&input.samples, &mapping), Vec::<usize>::new());
+        assert_eq!(infer(&input.samples, 16, &Opcode::ALL), Ok(mapping.clone()));
+
+        let mut wrong = mapping.clone();
+        wrong.swap(0, 1);
+        assert!(!mismatches(&input.samples, &wrong).is_empty());
+    }
+
+    #[test]
+    fn test_minimize() {
+        for seed in 0..5 {
+            let mapping = random_mapping(seed);
+            let input = generate(&mapping, 800, 0, seed);
+            let few = minimize(&input.samples, 16, &Opcode::ALL).unwrap();
+            assert!(few.len() < 40, "{} samples", few.len());
+            assert_eq!(infer(&few, 16, &Opcode::ALL), Ok(mapping));
+            // each sample is needed
+            for idx in 0..few.len() {
+                let mut fewer = few.clone();
+                fewer.remove(idx);
+                assert!(infer(&fewer, 16, &Opcode::ALL).is_err());
+            }
+        }
+        let ambiguous = generate(&random_mapping(0), 1, 0, 0);
+        assert!(matches!(
+            minimize(&ambiguous.samples, 16, &Opcode::ALL),
+            Err(InferError::Ambiguous { .. })
+        ));
+    }
+}
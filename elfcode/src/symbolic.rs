@@ -0,0 +1,555 @@
+//! Symbolic execution of the setup of a program, with register 0 as the input.
+//!
+//! The programs of days 19 and 21 compute some constants before they enter their main loop, in
+//! day 19 depending on the initial value of register 0. Running this setup symbolically gives the
+//! registers on entering the main loop for all values of register 0 at once, e.g. the number
+//! whose divisors day 19 adds up.
+//!
+//! A jump which depends on register 0 forks the execution. A comparison like `r0 > 3` splits the
+//! values of register 0 into the ones making it true and the others, while a jump by register 0
+//! itself, like `addr 4 0 4`, forks for every value which jumps into the program. Register 0 may
+//! only be added to and multiplied with constants where it decides a jump, which is all the
+//! setups do.
+
+use crate::cfg::Cfg;
+use crate::expr::{Atom, BinOp, Expr};
+use crate::opcode::Value;
+use crate::program::Program;
+use crate::vm::{InvalidRegister, Vm};
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::fmt;
+
+/// A value computed from the initial value of register 0.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Sym {
+    Const(Value),
+    /// The initial value of register 0.
+    Input,
+    Binary(BinOp, Box<Sym>, Box<Sym>),
+}
+
+impl Sym {
+    fn binary(op: BinOp, a: Sym, b: Sym) -> Sym {
+        match (op, a, b) {
+            // wrapping like the device in release builds, paths with odd inputs might overflow
+            (BinOp::Add, Sym::Const(a), Sym::Const(b)) => Sym::Const(a.wrapping_add(b)),
+            (BinOp::Mul, Sym::Const(a), Sym::Const(b)) => Sym::Const(a.wrapping_mul(b)),
+            (op, Sym::Const(a), Sym::Const(b)) => Sym::Const(op.eval(a, b)),
+            (BinOp::Add, a, Sym::Const(0)) | (BinOp::Add, Sym::Const(0), a) => a,
+            (BinOp::Mul, a, Sym::Const(1)) | (BinOp::Mul, Sym::Const(1), a) => a,
+            (BinOp::Mul, _, Sym::Const(0)) | (BinOp::Mul, Sym::Const(0), _) => Sym::Const(0),
+            (op, a, b) => Sym::Binary(op, Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn eval(&self, input: Value) -> Value {
+        match self {
+            Sym::Const(value) => *value,
+            Sym::Input => input,
+            Sym::Binary(op, a, b) => op.eval(a.eval(input), b.eval(input)),
+        }
+    }
+
+    /// `(a, b)` if the value is `a * input + b`.
+    fn affine(&self) -> Option<(i128, i128)> {
+        match self {
+            Sym::Const(value) => Some((0, *value as i128)),
+            Sym::Input => Some((1, 0)),
+            Sym::Binary(BinOp::Add, a, b) => {
+                let ((a1, b1), (a2, b2)) = (a.affine()?, b.affine()?);
+                Some((a1.checked_add(a2)?, b1.checked_add(b2)?))
+            }
+            Sym::Binary(BinOp::Mul, a, b) => match (a.affine()?, b.affine()?) {
+                ((0, k), (a, b)) | ((a, b), (0, k)) => Some((a.checked_mul(k)?, b.checked_mul(k)?)),
+                _ => None,
+            },
+            Sym::Binary(..) => None,
+        }
+    }
+
+    /// The inputs of `inputs` which make the comparison `op(a, b)` true and the ones which make
+    /// it false, if the operands are affine.
+    fn split(op: BinOp, a: &Sym, b: &Sym, inputs: &Inputs) -> Option<(Inputs, Inputs)> {
+        let ((a1, b1), (a2, b2)) = (a.affine()?, b.affine()?);
+        // a > b if k * input + d > 0, a == b if it is 0
+        let (k, d) = (a1.checked_sub(a2)?, b1.checked_sub(b2)?);
+        Some(match op {
+            _ if k == 0 => {
+                let holds = if op == BinOp::Gt { d > 0 } else { d == 0 };
+                if holds {
+                    (inputs.clone(), Inputs::empty())
+                } else {
+                    (Inputs::empty(), inputs.clone())
+                }
+            }
+            BinOp::Gt if k > 0 => {
+                let min = (-d).div_euclid(k) + 1;
+                (
+                    inputs.restrict(min, i128::MAX),
+                    inputs.restrict(i128::MIN, min - 1),
+                )
+            }
+            BinOp::Gt => {
+                // -k * input < d
+                let max = -(-d).div_euclid(-k) - 1;
+                (
+                    inputs.restrict(i128::MIN, max),
+                    inputs.restrict(max + 1, i128::MAX),
+                )
+            }
+            BinOp::Eq if d % k == 0 => {
+                let root = -d / k;
+                (inputs.restrict(root, root), inputs.exclude(root))
+            }
+            BinOp::Eq => (Inputs::empty(), inputs.clone()),
+            _ => return None,
+        })
+    }
+
+    /// The value for the inputs of `inputs`, with the comparisons they decide folded.
+    pub fn simplify(&self, inputs: &Inputs) -> Sym {
+        match self {
+            Sym::Const(_) => self.clone(),
+            Sym::Input => inputs.single().map_or(Sym::Input, Sym::Const),
+            Sym::Binary(op, a, b) => match Sym::binary(*op, a.simplify(inputs), b.simplify(inputs))
+            {
+                Sym::Binary(op, a, b) if op.is_comparison() => {
+                    match Sym::split(op, &a, &b, inputs) {
+                        Some((_, no)) if no.is_empty() => Sym::Const(1),
+                        Some((yes, _)) if yes.is_empty() => Sym::Const(0),
+                        _ => Sym::Binary(op, a, b),
+                    }
+                }
+                sym => sym,
+            },
+        }
+    }
+
+    /// The first comparison depending on the input.
+    fn comparison(&self) -> Option<(BinOp, &Sym, &Sym)> {
+        match self {
+            Sym::Binary(op, a, b) if op.is_comparison() => Some((*op, a, b)),
+            Sym::Binary(_, a, b) => a.comparison().or_else(|| b.comparison()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Sym {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sym::Const(value) => write!(f, "{}", value),
+            Sym::Input => write!(f, "r0"),
+            Sym::Binary(op, a, b) => write!(f, "({} {} {})", a, op.symbol(), b),
+        }
+    }
+}
+
+/// A set of initial values of register 0: a range without some values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inputs {
+    min: i128,
+    max: i128,
+    excluded: BTreeSet<i128>,
+}
+
+impl Inputs {
+    /// All values of register 0.
+    pub fn all() -> Self {
+        Self {
+            min: Value::MIN as i128,
+            max: Value::MAX as i128,
+            excluded: BTreeSet::new(),
+        }
+    }
+
+    /// The values from `min` to `max`.
+    pub fn range(min: Value, max: Value) -> Self {
+        Self {
+            min: min as i128,
+            max: max as i128,
+            excluded: BTreeSet::new(),
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            min: 1,
+            max: 0,
+            excluded: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    pub fn contains(&self, value: Value) -> bool {
+        let value = value as i128;
+        self.min <= value && value <= self.max && !self.excluded.contains(&value)
+    }
+
+    /// The value if there is only one.
+    pub fn single(&self) -> Option<Value> {
+        Some(self.min as Value).filter(|_| self.min == self.max)
+    }
+
+    fn normalized(mut self) -> Self {
+        while self.min <= self.max && self.excluded.remove(&self.min) {
+            self.min += 1;
+        }
+        while self.min <= self.max && self.excluded.remove(&self.max) {
+            self.max -= 1;
+        }
+        let (min, max) = (self.min, self.max);
+        self.excluded.retain(|&value| min < value && value < max);
+        self
+    }
+
+    fn restrict(&self, min: i128, max: i128) -> Self {
+        let mut inputs = self.clone();
+        inputs.min = inputs.min.max(min);
+        inputs.max = inputs.max.min(max);
+        inputs.normalized()
+    }
+
+    fn exclude(&self, value: i128) -> Self {
+        let mut inputs = self.clone();
+        inputs.excluded.insert(value);
+        inputs.normalized()
+    }
+}
+
+impl fmt::Display for Inputs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (all_min, all_max) = (Value::MIN as i128, Value::MAX as i128);
+        match (self.min, self.max) {
+            _ if self.is_empty() => write!(f, "no r0")?,
+            (min, max) if min == max => write!(f, "r0 = {}", min)?,
+            (min, max) if min == all_min && max == all_max => write!(f, "any r0")?,
+            (min, max) if min == all_min => write!(f, "r0 <= {}", max)?,
+            (min, max) if max == all_max => write!(f, "r0 >= {}", min)?,
+            (min, max) => write!(f, "{} <= r0 <= {}", min, max)?,
+        }
+        for value in &self.excluded {
+            write!(f, ", r0 != {}", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a path through the setup ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    /// Enters the loop whose first instruction has this index.
+    Loop(usize),
+    Halted,
+    /// Still runs after the budget at this instruction.
+    OutOfBudget(usize),
+}
+
+/// A path through the setup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The initial values of register 0 which take this path.
+    pub inputs: Inputs,
+    pub end: End,
+    /// The registers at the end; the one bound to the instruction pointer holds the index of the
+    /// instruction.
+    pub registers: Vec<Sym>,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.end {
+            End::Loop(pc) => write!(f, "{}: enters the loop at {} with", self.inputs, pc)?,
+            End::Halted => write!(f, "{}: halts with", self.inputs)?,
+            End::OutOfBudget(pc) => write!(f, "{}: still runs at {} with", self.inputs, pc)?,
+        }
+        for (r, value) in self.registers.iter().enumerate() {
+            let separator = if r == 0 { " " } else { ", " };
+            write!(f, "{}r{} = {}", separator, r, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why the setup of a program can't be executed symbolically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    /// The instruction with this index jumps by a value which can't be split by register 0.
+    Jump(usize, Sym),
+    Register(InvalidRegister),
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Jump(pc, sym) => write!(
+                f,
+                "instruction {} jumps by {}, which can't be split by the values of r0",
+                pc, sym
+            ),
+            SymbolicError::Register(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+impl From<InvalidRegister> for SymbolicError {
+    fn from(e: InvalidRegister) -> Self {
+        SymbolicError::Register(e)
+    }
+}
+
+/// The inputs of `inputs` with the instructions they jump to from `pc` by writing `target` to
+/// the instruction pointer, `None` for halting.
+fn jumps(
+    program: &Program,
+    pc: usize,
+    target: &Sym,
+    inputs: Inputs,
+) -> Result<Vec<(Inputs, Option<usize>)>, SymbolicError> {
+    let next =
+        |value: i128| Some(value + 1).filter(|&next| 0 <= next && next < program.len() as i128);
+    let target = target.simplify(&inputs);
+    if let Sym::Const(value) = target {
+        return Ok(vec![(
+            inputs,
+            next(value as i128).map(|next| next as usize),
+        )]);
+    }
+    if let Some((op, a, b)) = target.comparison() {
+        if let Some((yes, no)) = Sym::split(op, a, b, &inputs) {
+            let mut result = jumps(program, pc, &target, yes)?;
+            result.extend(jumps(program, pc, &target, no)?);
+            return Ok(result);
+        }
+    }
+    let (k, d) = match target.affine() {
+        Some(affine) => affine,
+        None => return Err(SymbolicError::Jump(pc, target)),
+    };
+    let mut result = Vec::new();
+    let mut rest = inputs.clone();
+    for next in 0..program.len() as i128 {
+        // k * input + d + 1 == next
+        let rhs = next - 1 - d;
+        if rhs % k != 0 || !inputs.contains(rhs as Value) {
+            continue;
+        }
+        result.push((inputs.restrict(rhs / k, rhs / k), Some(next as usize)));
+        rest = rest.exclude(rhs / k);
+    }
+    if !rest.is_empty() {
+        result.push((rest, None));
+    }
+    Ok(result
+        .into_iter()
+        .filter(|(inputs, _)| !inputs.is_empty())
+        .collect())
+}
+
+/// Executes `program` symbolically until it enters a loop, for the initial values `inputs` of
+/// register 0 and the other registers 0.
+///
+/// Each path runs for at most `budget` instructions.
+pub fn setup(
+    program: &Program,
+    registers: usize,
+    inputs: Inputs,
+    budget: u64,
+) -> Result<Vec<Entry>, SymbolicError> {
+    Vm::new(program, registers)?;
+    let cfg = Cfg::new(program);
+    let headers: HashSet<usize> = cfg
+        .loops()
+        .iter()
+        .filter_map(|l| cfg.blocks.get(l.header))
+        .map(|block| block.range.start)
+        .collect();
+
+    let mut start = vec![Sym::Const(0); registers];
+    start[0] = Sym::Input;
+    let mut paths = vec![(inputs, start, Some(0).filter(|_| !program.is_empty()), 0)];
+    let mut entries = Vec::new();
+    while let Some((inputs, mut regs, pc, steps)) = paths.pop() {
+        let pc = match pc {
+            Some(pc) => pc,
+            None => {
+                entries.push(Entry {
+                    inputs,
+                    end: End::Halted,
+                    registers: regs,
+                });
+                continue;
+            }
+        };
+        if let Some(ip) = program.ip {
+            regs[ip] = Sym::Const(pc as Value);
+        }
+        if headers.contains(&pc) || steps == budget {
+            let end = if headers.contains(&pc) {
+                End::Loop(pc)
+            } else {
+                End::OutOfBudget(pc)
+            };
+            entries.push(Entry {
+                inputs,
+                end,
+                registers: regs,
+            });
+            continue;
+        }
+        let instr = &program.instructions[pc];
+        let value = match Expr::of(instr, program.ip, pc) {
+            Expr::Atom(a) => atom(a, &regs),
+            Expr::Binary(op, a, b) => Sym::binary(op, atom(a, &regs), atom(b, &regs)),
+        };
+        let c = instr.c as usize;
+        regs[c] = value.simplify(&inputs);
+        if program.ip == Some(c) {
+            // the register keeps the value if the jump leaves the program
+            for (inputs, next) in jumps(program, pc, &value, inputs)?.into_iter().rev() {
+                let regs = regs.iter().map(|sym| sym.simplify(&inputs)).collect();
+                paths.push((inputs, regs, next, steps + 1));
+            }
+        } else {
+            let next = Some(pc + 1).filter(|&next| next < program.len());
+            paths.push((inputs, regs, next, steps + 1));
+        }
+    }
+    Ok(entries)
+}
+
+fn atom(atom: Atom, regs: &[Sym]) -> Sym {
+    match atom {
+        Atom::Reg(r) => regs[r].clone(),
+        Atom::Const(value) => Sym::Const(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{DAY19, DAY21};
+
+    #[test]
+    fn test_day19() {
+        let program: Program = DAY19.parse().unwrap();
+        let entries = setup(&program, 6, Inputs::range(0, Value::MAX), 1000).unwrap();
+        let lines: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(
+            lines[0],
+            "r0 = 0: enters the loop at 2 with r0 = 0, r1 = 1, r2 = 976, r3 = 140, r4 = 2, r5 = 0"
+        );
+        // the number whose divisors part 2 adds up
+        assert_eq!(
+            lines[1],
+            "r0 = 1: enters the loop at 2 \
+             with r0 = 0, r1 = 1, r2 = 10551376, r3 = 10550400, r4 = 2, r5 = 0"
+        );
+        assert_eq!(
+            lines[10],
+            "r0 >= 10: halts with r0 = r0, r1 = 0, r2 = 976, r3 = 140, r4 = (25 + r0), r5 = 0"
+        );
+
+        // the registers at the start of the loop are the ones a concrete run has
+        for entry in &entries {
+            let (pc, input) = match (entry.end, entry.inputs.single()) {
+                (End::Loop(pc), Some(input)) => (pc, input),
+                _ => continue,
+            };
+            let mut vm = Vm::new(&program, 6).unwrap();
+            vm.registers_mut()[0] = input;
+            while vm.ip() != Some(pc) {
+                vm.step();
+            }
+            let regs: Vec<Sym> = vm.registers().iter().map(|&v| Sym::Const(v)).collect();
+            assert_eq!(regs[..4], entry.registers[..4]);
+        }
+
+        // and the registers on halting, too
+        let halted: Vec<&Entry> = entries.iter().filter(|e| e.end == End::Halted).collect();
+        for &input in &[10, 1000] {
+            let entry = halted.iter().find(|e| e.inputs.contains(input)).unwrap();
+            let mut vm = Vm::new(&program, 6).unwrap();
+            vm.registers_mut()[0] = input;
+            vm.run_to_halt();
+            let regs: Vec<Value> = entry.registers.iter().map(|r| r.eval(input)).collect();
+            assert_eq!(vm.registers(), &regs[..]);
+        }
+        let program: Program = "#ip 0\nseti -5 0 0".parse().unwrap();
+        let entries = setup(&program, 1, Inputs::all(), 10).unwrap();
+        assert_eq!(entries[0].to_string(), "any r0: halts with r0 = -5");
+    }
+
+    #[test]
+    fn test_day21() {
+        let program: Program = DAY21.parse().unwrap();
+        let entries = setup(&program, 6, Inputs::all(), 1000).unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["any r0: enters the loop at 1 with r0 = r0, r1 = 0, r2 = 123, r3 = 0, r4 = 1, r5 = 0"]
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let inputs = |source: &str| {
+            let program: Program = source.parse().unwrap();
+            setup(&program, 4, Inputs::all(), 100)
+                .unwrap()
+                .into_iter()
+                .map(|e| format!("{}: r1 = {}", e.inputs, e.registers[1]))
+                .collect::<Vec<_>>()
+        };
+        // r1 = r0 > 5 ? 2 : r0 * 3 + 1, then r0 == 4 or not
+        let source = "\
+#ip 3
+gtri 0 5 2
+addr 2 3 3
+seti 4 0 3
+seti 2 0 1
+seti 9 0 3
+muli 0 3 1
+addi 1 1 1
+eqri 0 4 2
+addr 2 3 3
+addi 1 0 1";
+        assert_eq!(
+            inputs(source),
+            vec![
+                "r0 >= 6: r1 = 2",
+                "r0 = 4: r1 = 13",
+                "r0 <= 5, r0 != 4: r1 = ((r0 * 3) + 1)"
+            ]
+        );
+        // -2 * r0 > 7 if r0 <= -4
+        let source = "#ip 3\nmuli 0 -2 1\ngtri 1 7 2\naddr 2 3 3\nseti 9 0 3\nseti 1 0 1";
+        assert_eq!(
+            inputs(source),
+            vec!["r0 <= -4: r1 = 1", "r0 >= -3: r1 = (r0 * -2)"]
+        );
+
+        let program: Program = "#ip 3\nmulr 0 0 1\naddr 1 3 3".parse().unwrap();
+        assert_eq!(
+            setup(&program, 4, Inputs::all(), 100),
+            Err(SymbolicError::Jump(
+                1,
+                Sym::Binary(
+                    BinOp::Add,
+                    Box::new(Sym::Binary(
+                        BinOp::Mul,
+                        Box::new(Sym::Input),
+                        Box::new(Sym::Input)
+                    )),
+                    Box::new(Sym::Const(1))
+                )
+            ))
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Programs used by the tests.
+
+/// The halting check of day 21.
+pub const DAY21: &str = "\
+#ip 4
+seti 123 0 2
+bani 2 456 2
+eqri 2 72 2
+addr 2 4 4
+seti 0 0 4
+seti 0 1 2
+bori 2 65536 5
+seti 16123384 4 2
+bani 5 255 3
+addr 2 3 2
+bani 2 16777215 2
+muli 2 65899 2
+bani 2 16777215 2
+gtir 256 5 3
+addr 3 4 4
+addi 4 1 4
+seti 27 6 4
+seti 0 3 3
+addi 3 1 1
+muli 1 256 1
+gtrr 1 5 1
+addr 1 4 4
+addi 4 1 4
+seti 25 6 4
+addi 3 1 3
+seti 17 3 4
+setr 3 8 5
+seti 7 2 4
+eqrr 2 0 3
+addr 3 4 4
+seti 5 3 4";
+
+/// The divisor sum of day 19.
+pub const DAY19: &str = "\
+#ip 4
+addi 4 16 4
+seti 1 8 1
+seti 1 3 5
+mulr 1 5 3
+eqrr 3 2 3
+addr 3 4 4
+addi 4 1 4
+addr 1 0 0
+addi 5 1 5
+gtrr 5 2 3
+addr 4 3 4
+seti 2 2 4
+addi 1 1 1
+gtrr 1 2 3
+addr 3 4 4
+seti 1 4 4
+mulr 4 4 4
+addi 2 2 2
+mulr 2 2 2
+mulr 4 2 2
+muli 2 11 2
+addi 3 6 3
+mulr 3 4 3
+addi 3 8 3
+addr 2 3 2
+addr 4 0 4
+seti 0 1 4
+setr 4 4 3
+mulr 3 4 3
+addr 4 3 3
+mulr 4 3 3
+muli 3 14 3
+mulr 3 4 3
+addr 2 3 2
+seti 0 4 0
+seti 0 7 4";
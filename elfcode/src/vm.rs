@@ -0,0 +1,253 @@
+//! Execution of programs on the device.
+
+use crate::opcode::Value;
+use crate::optimize::{Fused, Optimized};
+use crate::program::{Instruction, Program};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// Why `Vm::run` returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// The instruction pointer left the program.
+    Halted,
+    /// The instruction budget is used up.
+    OutOfBudget,
+}
+
+/// A register operand of the program does not exist on the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidRegister {
+    /// Index of the instruction, `None` for the register bound to the instruction pointer.
+    pub instruction: Option<usize>,
+    pub register: Value,
+}
+
+impl fmt::Display for InvalidRegister {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.instruction {
+            Some(idx) => write!(f, "instruction {}: no register {}", idx, self.register),
+            None => write!(f, "#ip: no register {}", self.register),
+        }
+    }
+}
+
+impl Error for InvalidRegister {}
+
+/// Snapshot of a `Vm`, e.g. to go back in time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct State {
+    pub registers: Vec<Value>,
+    pub ip: Option<usize>,
+    pub steps: u64,
+}
+
+/// The device running a program.
+#[derive(Debug, Clone)]
+pub struct Vm<'a> {
+    program: &'a Program,
+    registers: Vec<Value>,
+    /// `None` if the instruction pointer left the program.
+    ip: Option<usize>,
+    steps: u64,
+    /// Fused loops by their first instruction, empty if not optimized.
+    fused: &'a [Option<Fused>],
+}
+
+impl<'a> Vm<'a> {
+    /// Device with `registers` registers, all 0, at the start of `program`.
+    pub fn new(program: &'a Program, registers: usize) -> Result<Self, InvalidRegister> {
+        if let Some(ip) = program.ip.filter(|&ip| ip >= registers) {
+            let register = ip as Value;
+            return Err(InvalidRegister {
+                instruction: None,
+                register,
+            });
+        }
+        for (idx, instr) in program.instructions.iter().enumerate() {
+            if let Some(register) = instr.invalid_register(registers) {
+                return Err(InvalidRegister {
+                    instruction: Some(idx),
+                    register,
+                });
+            }
+        }
+        Ok(Self {
+            program,
+            registers: vec![0; registers],
+            ip: Some(0).filter(|_| !program.is_empty()),
+            steps: 0,
+            fused: &[],
+        })
+    }
+
+    /// Device running an optimized program, where each fused loop counts as one instruction.
+    pub fn optimized(optimized: &'a Optimized) -> Result<Self, InvalidRegister> {
+        let mut vm = Self::new(&optimized.program, optimized.registers)?;
+        vm.fused = &optimized.fused;
+        Ok(vm)
+    }
+
+    pub fn program(&self) -> &'a Program {
+        self.program
+    }
+
+    pub fn registers(&self) -> &[Value] {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut [Value] {
+        &mut self.registers
+    }
+
+    /// Index of the next instruction, `None` if the program halted.
+    pub fn ip(&self) -> Option<usize> {
+        self.ip
+    }
+
+    /// Jumps to the instruction `ip`; out of the program halts.
+    pub fn set_ip(&mut self, ip: usize) {
+        self.ip = Some(ip).filter(|&ip| ip < self.program.len());
+    }
+
+    /// Number of executed instructions.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.ip.is_none()
+    }
+
+    /// Next instruction to execute.
+    pub fn current(&self) -> Option<&'a Instruction> {
+        self.ip.map(|ip| &self.program.instructions[ip])
+    }
+
+    pub fn state(&self) -> State {
+        State {
+            registers: self.registers.clone(),
+            ip: self.ip,
+            steps: self.steps,
+        }
+    }
+
+    /// Goes back to `state` of this `Vm`.
+    ///
+    /// Panics if `state` has a different number of registers.
+    pub fn restore(&mut self, state: &State) {
+        self.registers.copy_from_slice(&state.registers);
+        self.ip = state.ip;
+        self.steps = state.steps;
+    }
+
+    /// Executes one instruction; false if the program already halted.
+    pub fn step(&mut self) -> bool {
+        let (ip, instr) = match (self.ip, self.current()) {
+            (Some(ip), Some(instr)) => (ip, instr),
+            _ => return false,
+        };
+        if let Some(Some(fused)) = self.fused.get(ip) {
+            if fused.apply(&mut self.registers) {
+                self.ip = Some(fused.exit);
+                self.steps += 1;
+                return true;
+            }
+        }
+        match self.program.ip {
+            Some(ip_reg) => {
+                self.registers[ip_reg] = ip as Value;
+                instr.execute(&mut self.registers);
+                let next = self.registers[ip_reg] + 1;
+                self.ip = usize::try_from(next)
+                    .ok()
+                    .filter(|&ip| ip < self.program.len());
+            }
+            None => {
+                instr.execute(&mut self.registers);
+                self.ip = Some(ip + 1).filter(|&ip| ip < self.program.len());
+            }
+        }
+        self.steps += 1;
+        true
+    }
+
+    /// Runs until the program halts or `budget` instructions were executed.
+    pub fn run(&mut self, budget: u64) -> Exit {
+        for _ in 0..budget {
+            if !self.step() {
+                return Exit::Halted;
+            }
+        }
+        if self.is_halted() {
+            Exit::Halted
+        } else {
+            Exit::OutOfBudget
+        }
+    }
+
+    /// Runs until the program halts, which might never happen.
+    pub fn run_to_halt(&mut self) {
+        while self.step() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcode::Opcode;
+
+    const PROGRAM: &str = "#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5";
+
+    #[test]
+    fn test_run() {
+        let program: Program = PROGRAM.parse().unwrap();
+        let mut vm = Vm::new(&program, 6).unwrap();
+        assert_eq!(vm.current().map(|instr| instr.op), Some(Opcode::Seti));
+        vm.run_to_halt();
+        assert_eq!(vm.registers(), &[6, 5, 6, 0, 0, 9]);
+        assert_eq!(vm.steps(), 5);
+        assert!(vm.is_halted());
+        assert!(!vm.step());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let program: Program = "seti 9223372036854775807 0 0\naddi 0 1 1\nmulr 0 0 2"
+            .parse()
+            .unwrap();
+        let mut vm = Vm::new(&program, 3).unwrap();
+        vm.run_to_halt();
+        assert_eq!(vm.registers(), &[Value::MAX, Value::MIN, 1]);
+    }
+
+    #[test]
+    fn test_budget() {
+        let program: Program = "#ip 1\nseti -1 0 1".parse().unwrap();
+        let mut vm = Vm::new(&program, 2).unwrap();
+        let start = vm.state();
+        assert_eq!(vm.run(1000), Exit::OutOfBudget);
+        assert_eq!(vm.steps(), 1000);
+        vm.restore(&start);
+        assert_eq!(vm.state(), start);
+        vm.set_ip(1);
+        assert_eq!(vm.run(1000), Exit::Halted);
+
+        let program: Program = "seti 1 0 0\naddi 0 1 0".parse().unwrap();
+        let mut vm = Vm::new(&program, 1).unwrap();
+        assert_eq!(vm.run(2), Exit::Halted);
+        assert_eq!(vm.registers(), &[2]);
+        assert_eq!(
+            Vm::new(&program, 0).unwrap_err().to_string(),
+            "instruction 0: no register 0"
+        );
+    }
+}
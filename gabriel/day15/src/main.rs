@@ -11,17 +11,21 @@ use env_logger;
 fn main() {
     env_logger::init();
 
-    let mut file = File::open("input4.txt").expect("file not found");
+    let mut file = File::open("input.txt").expect("file not found");
     let mut contents = String::new();
     file.read_to_string(&mut contents)
         .expect("could not read file");
-    let mut board = Board::from_str(&contents).expect("could not parse board.");
+    let board = Board::from_str(&contents).expect("could not parse board.");
 
     println!("{}", board);
-    loop {
-        if let Some(battle_outcome) = board.next_turn() {
-            println!("{}", battle_outcome);
-            return;
-        }
+    let mut battle = board.clone();
+    println!("Outcome of the battle: {}", battle.battle());
+    if let Some(round) = battle.first_elf_death() {
+        println!("First elf died in round {}", round);
     }
+    let (elf_power, battle_outcome) = board.min_elf_power();
+    println!(
+        "Outcome of the battle without elf losses: {} (elf attack power {})",
+        battle_outcome, elf_power
+    );
 }
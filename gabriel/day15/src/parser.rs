@@ -1,6 +1,7 @@
 use std::cmp::{Ord, Ordering, PartialOrd};
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::fmt;
@@ -23,7 +24,7 @@ impl fmt::Display for Tile {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum Class {
     Goblin,
     Elf,
@@ -65,6 +66,8 @@ pub struct Board {
     tiles: Vec<Tile>,
     units: Vec<Unit>,
     turn: usize,
+    attack_powers: HashMap<Class, isize>,
+    first_elf_death: Option<usize>,
 }
 
 impl FromStr for Board {
@@ -118,6 +121,8 @@ impl FromStr for Board {
             height: height,
             width: width,
             turn: 0,
+            attack_powers: HashMap::new(),
+            first_elf_death: None,
         })
     }
 }
@@ -135,7 +140,7 @@ impl Unit {
             .iter()
             .enumerate()
             .filter_map(|(i, &u)| {
-                if adjacent_tiles.contains(&(u.x, u.y)) && u.class != self.class {
+                if adjacent_tiles.contains(&(u.x, u.y)) && u.class != self.class && u.hp > 0 {
                     return Some((i, u.clone()));
                 } else {
                     None
@@ -181,21 +186,49 @@ pub enum Turn {
 }
 
 impl Board {
+    pub const DEFAULT_ATTACK_POWER: isize = 3;
+
+    /// Sets the attack power of all units of a class
+    pub fn set_attack_power(&mut self, class: Class, power: isize) {
+        self.attack_powers.insert(class, power);
+    }
+
+    pub fn attack_power(&self, class: Class) -> isize {
+        *self
+            .attack_powers
+            .get(&class)
+            .unwrap_or(&Board::DEFAULT_ATTACK_POWER)
+    }
+
+    /// The round in which the first elf died, if any
+    pub fn first_elf_death(&self) -> Option<usize> {
+        self.first_elf_death
+    }
+
+    /// Plays a round, returns the outcome of the battle once a unit finds no ennemy left
     pub fn next_turn(&mut self) -> Option<isize> {
-        let mut dead_units = vec![];
-        println!("Start round {}:", self.turn + 1);
+        debug!("Start round {}:", self.turn + 1);
         for i in 0..self.units.len() {
-            let last_move = self.choose_turn(i, &dead_units);
+            if self.units[i].hp <= 0 {
+                // killed earlier in this round
+                continue;
+            }
+            let last_move = self.choose_turn(i);
             match last_move {
                 Turn::Attacked(ennemy_idx) => {
+                    let power = self.attack_power(self.units[i].class);
                     let ennemy = self.units.get_mut(ennemy_idx).expect("cannot index ennemy");
-                    ennemy.hp -= 3;
-                    if ennemy.hp <= 0 {
-                        self.units.remove(i);
+                    ennemy.hp -= power;
+                    if ennemy.hp <= 0
+                        && ennemy.class == Class::Elf
+                        && self.first_elf_death.is_none()
+                    {
+                        self.first_elf_death = Some(self.turn + 1);
                     }
                 }
                 Turn::Victory(class) => {
-                    println!("Victory from {}", class);
+                    debug!("Victory from {}", class);
+                    self.units.retain(|u| u.hp > 0);
                     return Some(
                         self.turn as isize * self.units.iter().map(|&u| u.hp).sum::<isize>(),
                     );
@@ -203,122 +236,137 @@ impl Board {
                 _ => continue,
             };
         }
-        for dead_unit in dead_units.iter() {
-            self.units.remove(*dead_unit);
-        }
+        self.units.retain(|u| u.hp > 0);
         self.turn += 1;
-        println!("{}", self);
+        debug!("{}", self);
         self.units.sort();
         None
     }
 
-    /// Choose the step and perform attack if relevant
-    /// returns the index of the ennemy that got killed.
-    pub fn choose_turn(&mut self, idx: usize, dead_units: &Vec<usize>) -> Turn {
-        let mut current_units = self
-            .units
-            .iter()
-            .enumerate()
-            .filter(|(i, _)| !dead_units.contains(i))
-            .map(|(_, &u)| u)
-            .collect();
-        let unit = self.units.get_mut(idx);
-        let unit = unit.unwrap();
-        let current_tiles = self.tiles.clone();
-        let width = self.width.clone();
-
-        if let Some((ennemy_idx, ennemy_unit)) = unit.has_target_in_range(&mut current_units) {
-            println!(
-                "{:?} is already ready to attack {:?}",
-                unit, current_units[ennemy_idx]
-            );
-            return Turn::Attacked(ennemy_idx);
+    /// Plays the whole battle and returns its outcome
+    pub fn battle(&mut self) -> isize {
+        loop {
+            if let Some(battle_outcome) = self.next_turn() {
+                return battle_outcome;
+            }
         }
+    }
 
-        let find_reachable_tiles = |(x, y)| -> Vec<(usize, usize)> {
-            Unit::points_around(x, y)
-                .into_iter()
-                .filter(|(x, y)| {
-                    current_tiles[x + y * width] == Tile::Open
-                        && !current_units.iter().any(|u| u.x == *x && u.y == *y)
-                })
-                .map(|&(x, y)| (x, y))
-                .collect()
-        };
-        debug!("{:?}", unit);
+    /// Plays the battle with the given elf attack power, gives up as soon as an elf dies
+    pub fn battle_without_elf_losses(&self, elf_power: isize) -> Option<isize> {
+        let mut board = self.clone();
+        board.set_attack_power(Class::Elf, elf_power);
+        loop {
+            let battle_outcome = board.next_turn();
+            if board.first_elf_death.is_some() {
+                return None;
+            }
+            if battle_outcome.is_some() {
+                return battle_outcome;
+            }
+        }
+    }
 
-        // find all tiles each target can reach
-        let in_range: HashSet<(usize, usize)> = current_units
+    /// Finds the lowest elf attack power with which no elf dies
+    /// returns the power and the outcome of that battle.
+    pub fn min_elf_power(&self) -> (isize, isize) {
+        let mut elf_power = Board::DEFAULT_ATTACK_POWER + 1;
+        loop {
+            if let Some(battle_outcome) = self.battle_without_elf_losses(elf_power) {
+                return (elf_power, battle_outcome);
+            }
+            elf_power += 1;
+        }
+    }
+
+    fn is_free(&self, (x, y): (usize, usize)) -> bool {
+        self.tiles[x + y * self.width] == Tile::Open
+            && !self.units.iter().any(|u| u.hp > 0 && u.x == x && u.y == y)
+    }
+
+    fn find_reachable_tiles(&self, (x, y): (usize, usize)) -> Vec<(usize, usize)> {
+        Unit::points_around(x, y)
             .iter()
             .cloned()
-            .filter(|other: &Unit| other != unit && other.class != unit.class)
-            .flat_map(|unit| find_reachable_tiles((unit.x, unit.y)))
-            .collect();
-        debug!("In range: {:?}", in_range);
-        if in_range.len() == 0 {
-            return Turn::Victory(unit.class);
-        }
+            .filter(|&point| self.is_free(point))
+            .collect()
+    }
 
-        // find all reachable tiles for this unit
-        let mut reachable_tiles = HashSet::new();
+    /// Number of steps to all tiles reachable from `start`
+    fn distances(&self, start: (usize, usize)) -> HashMap<(usize, usize), usize> {
+        let mut distances = HashMap::new();
         let mut queue = VecDeque::new();
-        reachable_tiles.insert((unit.x, unit.y));
-        queue.push_back((unit.x, unit.y));
+        distances.insert(start, 0);
+        queue.push_back(start);
         while let Some(point) = queue.pop_front() {
-            for reachable in find_reachable_tiles(point).iter() {
-                if reachable_tiles.insert(*reachable) {
-                    queue.push_back(*reachable);
+            let distance = distances[&point] + 1;
+            for reachable in self.find_reachable_tiles(point) {
+                if !distances.contains_key(&reachable) {
+                    distances.insert(reachable, distance);
+                    queue.push_back(reachable);
                 }
             }
         }
+        distances
+    }
+
+    /// Choose the step and perform the move if relevant
+    /// returns the index of the ennemy to attack.
+    pub fn choose_turn(&mut self, idx: usize) -> Turn {
+        let unit = self.units[idx];
+        if !self.units.iter().any(|u| u.hp > 0 && u.class != unit.class) {
+            return Turn::Victory(unit.class);
+        }
+
+        if let Some((ennemy_idx, ennemy_unit)) = unit.has_target_in_range(&self.units) {
+            debug!("{:?} is already ready to attack {:?}", unit, ennemy_unit);
+            return Turn::Attacked(ennemy_idx);
+        }
+
+        debug!("{:?}", unit);
+
+        // find all tiles next to the ennemies
+        let in_range: HashSet<(usize, usize)> = self
+            .units
+            .iter()
+            .filter(|other| other.hp > 0 && other.class != unit.class)
+            .flat_map(|other| self.find_reachable_tiles((other.x, other.y)))
+            .collect();
+        debug!("In range: {:?}", in_range);
 
-        // intersect with the ennemy next tiles
-        // find nearest
-        // TODO: is this where it's better to use a priority queue? (i.e. do everything in one operation)
+        // find the nearest of them this unit can reach
+        let reachable_tiles = self.distances((unit.x, unit.y));
         let mut nearest_tiles: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
-        for (x, y) in in_range.intersection(&reachable_tiles) {
-            let distance =
-                (unit.x as isize - *x as isize).abs() + (unit.y as isize - *y as isize).abs();
-            if distance > 0 {
-                nearest_tiles
-                    .entry(distance as usize)
-                    .or_default()
-                    .push((*x, *y));
+        for tile in in_range.iter() {
+            if let Some(&distance) = reachable_tiles.get(tile) {
+                nearest_tiles.entry(distance).or_default().push(*tile);
             }
         }
 
         debug!("Nearest tiles are {:?}", nearest_tiles);
 
-        if let Some(chosen_tiles) = nearest_tiles.iter().min() {
-            let chosen_tile = chosen_tiles.1.iter().min_by_key(|(x, y)| (y, x)).unwrap();
+        if let Some((_, chosen_tiles)) = nearest_tiles.iter().next() {
+            let chosen_tile = *chosen_tiles.iter().min_by_key(|(x, y)| (y, x)).unwrap();
             debug!("Target tile: {:?}", chosen_tile);
 
-            // all reachable points around me
-            // cheapest move is the one with the lowest distance AND first in readability order
-            let mut around_unit = HashSet::new();
-            let pts = Unit::points_around(unit.x, unit.y);
-            around_unit.extend(pts.into_iter());
-            let cheapest_move = reachable_tiles
-                .intersection(&around_unit)
-                .min_by_key(|(x, y)| {
-                    let distance = (chosen_tile.0 as isize - *x as isize).abs()
-                        + (chosen_tile.1 as isize - *y as isize).abs();
-                    (distance, y, x)
-                })
+            // cheapest move is the one with the lowest distance to the target tile AND first in
+            // readability order
+            let to_chosen_tile = self.distances(chosen_tile);
+            let cheapest_move = Unit::points_around(unit.x, unit.y)
+                .iter()
+                .cloned()
+                .filter(|point| to_chosen_tile.contains_key(point))
+                .min_by_key(|&(x, y)| (to_chosen_tile[&(x, y)], y, x))
                 .expect("nothing's cheap??");
             debug!("Chosen tile to move to: {:?}", cheapest_move);
 
+            let unit = &mut self.units[idx];
             unit.x = cheapest_move.0;
             unit.y = cheapest_move.1;
-            // we replace the unit we just updated in the current_units array (ewww)
-            current_units[idx] = *unit;
         }
 
-        if let Some((ennemy_idx, _)) = unit.has_target_in_range(&current_units) {
-            println!(
-                "{:?} is ready to attack {:?}",
-                unit, current_units[ennemy_idx]
-            );
+        if let Some((ennemy_idx, ennemy_unit)) = self.units[idx].has_target_in_range(&self.units) {
+            debug!("{:?} is ready to attack {:?}", self.units[idx], ennemy_unit);
             return Turn::Attacked(ennemy_idx);
         }
 
@@ -354,4 +402,50 @@ mod tests {
             ]
         );
     }
+
+    const EXAMPLES: [&str; 5] = [
+        "#######\n#.G...#\n#...EG#\n#.#.#G#\n#..G#E#\n#.....#\n#######",
+        "#######\n#E..EG#\n#.#G.E#\n#E.##E#\n#G..#.#\n#..E#.#\n#######",
+        "#######\n#E.G#.#\n#.#G..#\n#G.#.G#\n#G..#.#\n#...E.#\n#######",
+        "#######\n#.E...#\n#.#..G#\n#.###.#\n#E#G#G#\n#...#G#\n#######",
+        "#########\n#G......#\n#.E.#...#\n#..##..G#\n#...##..#\n#...#...#\n#.G...G.#\n#.....G.#\n#########",
+    ];
+
+    #[test]
+    fn test_battle() {
+        let outcomes = [27730, 39514, 27755, 28944, 18740];
+        for (input, outcome) in EXAMPLES.iter().zip(outcomes.iter()) {
+            let mut board = Board::from_str(input).expect("could not parse board.");
+            assert_eq!(board.battle(), *outcome, "{}", input);
+        }
+        let mut board =
+            Board::from_str("#######\n#G..#E#\n#E#E.E#\n#G.##.#\n#...#E#\n#...E.#\n#######")
+                .expect("could not parse board.");
+        assert_eq!(board.battle(), 36334);
+    }
+
+    #[test]
+    fn test_min_elf_power() {
+        let expected = [(15, 4988), (4, 31284), (15, 3478), (12, 6474), (34, 1140)];
+        for (input, expected) in EXAMPLES.iter().zip(expected.iter()) {
+            let board = Board::from_str(input).expect("could not parse board.");
+            assert_eq!(board.min_elf_power(), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_first_elf_death() {
+        let mut board = Board::from_str(EXAMPLES[0]).expect("could not parse board.");
+        assert_eq!(board.battle_without_elf_losses(3), None);
+        assert_eq!(board.first_elf_death(), None);
+        board.battle();
+        assert!(board.first_elf_death().is_some());
+
+        let mut board = Board::from_str(EXAMPLES[0]).expect("could not parse board.");
+        board.set_attack_power(Class::Elf, 15);
+        board.set_attack_power(Class::Goblin, 2);
+        assert_eq!(board.attack_power(Class::Goblin), 2);
+        board.battle();
+        assert_eq!(board.first_elf_death(), None);
+    }
 }